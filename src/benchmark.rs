@@ -5,6 +5,7 @@ use mess::board::*;
 use mess::move_generation::*;
 use mess::MoveUnmove;
 use mess::evaluation::{MinimaxEvaluator, DynamicEvaluator, AlphaBetaEvaluator};
+use mess::search::{SearchEvaluator, SearchOptions};
 
 fn generate_moves_rec(board: &mut Board, depth: u32) {
     if depth == 0 {
@@ -12,12 +13,26 @@ fn generate_moves_rec(board: &mut Board, depth: u32) {
     }
     let moves = generate_moves(board);
     for m in moves.iter() {
-        let mut move_unmove = MoveUnmove::apply_move(board, &m);
+        let mut move_unmove = MoveUnmove::apply_move(board, m);
         generate_moves_rec(board, depth - 1);
         move_unmove.revert_move(board);
     }
 }
 
+// Number of positions at `depth` plies from `board`
+fn perft(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut count = 0;
+    for m in generate_moves(board).iter() {
+        let mut move_unmove = MoveUnmove::apply_move(board, m);
+        count += perft(board, depth - 1);
+        move_unmove.revert_move(board);
+    }
+    count
+}
+
 fn bench_move_generation(b: &mut bencher::Bencher) {
     let mut board = Board::create_populated();
 
@@ -26,6 +41,14 @@ fn bench_move_generation(b: &mut bencher::Bencher) {
     });
 }
 
+fn bench_perft(b: &mut bencher::Bencher) {
+    let mut board = Board::create_populated();
+
+    b.iter(|| {
+        assert_eq!(perft(&mut board, 4), 197281);
+    });
+}
+
 fn bench_minimax(b: &mut bencher::Bencher) {
     let mut board = Board::create_populated();
 
@@ -44,5 +67,23 @@ fn bench_alphabeta(b: &mut bencher::Bencher) {
     });
 }
 
-benchmark_group!(benches, bench_move_generation, bench_minimax, bench_alphabeta);
+fn bench_search(b: &mut bencher::Bencher) {
+    let mut board = Board::create_populated();
+
+    b.iter(|| {
+        let mut evaluator = SearchEvaluator::create(4);
+        evaluator.evaluate(&mut board);
+    });
+}
+
+fn bench_search_plain(b: &mut bencher::Bencher) {
+    let mut board = Board::create_populated();
+
+    b.iter(|| {
+        let mut evaluator = SearchEvaluator::create_with_options(4, SearchOptions::plain());
+        evaluator.evaluate(&mut board);
+    });
+}
+
+benchmark_group!(benches, bench_move_generation, bench_perft, bench_minimax, bench_alphabeta, bench_search, bench_search_plain);
 benchmark_main!(benches);
\ No newline at end of file
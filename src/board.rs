@@ -1,8 +1,10 @@
 use super::core::*;
 use super::move_::*;
 use super::move_generation::*;
+use super::piece_definition::*;
+use super::variant::*;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct PieceOnBoard {
     piece: Piece,
     square: Square,
@@ -15,8 +17,54 @@ impl PieceOnBoard {
             square: *square,
         }
     }
+
+    pub fn piece(&self) -> Piece {
+        self.piece
+    }
+
+    pub fn square(&self) -> Square {
+        self.square
+    }
 }
 
+// Pseudo-random number for Zobrist hashing, derived from `index` with SplitMix64
+fn zobrist_key(index: u64) -> u64 {
+    let mut z = index.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// Number of squares a rank in FEN describes, e.g. 10 for "rnabqkbcnr" or "4P5"
+fn fen_rank_width(rank: &str) -> i8 {
+    let mut width: i8 = 0;
+    let mut empty: i8 = 0;
+    for token in rank.chars() {
+        match token.to_digit(10) {
+            Some(digit) => empty = empty.saturating_mul(10).saturating_add(digit as i8),
+            None => {
+                width = width.saturating_add(empty);
+                empty = 0;
+                if token != '~' {
+                    width = width.saturating_add(1);
+                }
+            }
+        }
+    }
+    return width.saturating_add(empty);
+}
+
+// Offsets of the different kinds of Zobrist keys in the key sequence
+const ZOBRIST_PIECE_KEYS: u64 = 0;
+const ZOBRIST_SIDE_KEY: u64 = 2 * PIECE_KIND_COUNT as u64 * MAX_SQUARES as u64;
+const ZOBRIST_CASTLE_KEYS: u64 = ZOBRIST_SIDE_KEY + 1;
+const ZOBRIST_EN_PASSANT_KEYS: u64 = ZOBRIST_CASTLE_KEYS + 4;
+const ZOBRIST_CHECK_KEYS: u64 = ZOBRIST_EN_PASSANT_KEYS + Geometry::LARGEST.files as u64;
+const ZOBRIST_POCKET_KEYS: u64 = ZOBRIST_CHECK_KEYS + 8;
+const ZOBRIST_PROMOTED_KEYS: u64 = ZOBRIST_POCKET_KEYS + 2 * 5 * 32;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Ord, Eq)]
 struct PieceListEntry {
     piece: Piece,
@@ -41,26 +89,68 @@ pub struct Board {
     piece_list: Vec<Option<PieceListEntry>>,
     piece_free_list: Vec<u8>,
     square_list: Vec<Option<SquareListEntry>>,
+    pub geometry: Geometry,
     pub side: Color,
     pub en_passant: Option<Square>,
     pub castle_rights: BoardCastleRights,
+    pub variant: Variant,
+    // Number of checks each side has given, counted in Three-check only
+    pub checks: [u8; 2],
+    // Pieces each side holds in hand in Crazyhouse, indexed by color and then by PieceKind up to
+    // the queen
+    pub pockets: [[u8; 5]; 2],
+    // Squares of pieces that were promoted from pawns, as a bit per Geometry::index(). Tracked in
+    // Crazyhouse, where such pieces go into the pocket as pawns when captured.
+    pub promoted: u128,
+    // Halfmoves since the last capture or pawn move, for the fifty-move rule, and the number of the
+    // move in progress, counting from 1 and going up after Black's move
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    // How the pieces move, what they are worth and their FEN letters. PIECE_DEFINITIONS unless
+    // the board is given its own, e.g. a table built at runtime and leaked from a Box.
+    definitions: &'static PieceDefinitions,
+    pattern_kinds: PatternKinds,
+    // Pieces that Atomic captures blew up besides the captured and the capturing piece, most recent
+    // last, and how many each capture destroyed, for revert_move() to put them back
+    exploded: Vec<PieceOnBoard>,
+    explosion_sizes: Vec<u8>,
 }
 
 impl Board {
     pub fn create_empty() -> Board {
+        Board::create_empty_with_geometry(Geometry::STANDARD)
+    }
+
+    // Empty board of any size up to Geometry::LARGEST. There is room for two ranks of pieces per
+    // side, 32 on the standard board.
+    pub fn create_empty_with_geometry(geometry: Geometry) -> Board {
+        let piece_capacity = 4 * geometry.files as usize;
         let mut board = Board {
             piece_list: Vec::new(),
-            piece_free_list: Vec::with_capacity(32),
+            piece_free_list: Vec::with_capacity(piece_capacity),
             square_list: Vec::new(),
+            geometry,
             side: Color::White,
             en_passant: None,
             castle_rights: BoardCastleRights::none(),
+            variant: Variant::Standard,
+            checks: [0, 0],
+            pockets: [[0; 5]; 2],
+            promoted: 0,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            definitions: &PIECE_DEFINITIONS,
+            pattern_kinds: PatternKinds::create(&PIECE_DEFINITIONS),
+            exploded: Vec::new(),
+            explosion_sizes: Vec::new(),
         };
 
-        board.piece_list = vec![None; 32];
-        board.square_list = vec![None; 64];
+        board.piece_list = vec![None; piece_capacity];
+        board.square_list = vec![None; geometry.square_count()];
 
-        for idx in 0..board.piece_list.len() {
+        // Hand out the lowest free index first, so that the piece list and with it the generated
+        // moves keep the order in which pieces were added
+        for idx in (0..board.piece_list.len()).rev() {
             board.piece_free_list.push(idx as u8);
         }
 
@@ -100,6 +190,303 @@ impl Board {
         return board;
     }
 
+    // Only the kings and rooks of the initial position, with all castle rights
+    pub fn create_king_rooks() -> Board {
+        let mut board = Board::create_empty();
+
+        board.add_pieces(&[
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Rook.colored(Color::White).at(7, 0),
+            PieceKind::Rook.colored(Color::Black).at(0, 7),
+            PieceKind::King.colored(Color::Black).at(4, 7),
+            PieceKind::Rook.colored(Color::Black).at(7, 7)]);
+
+        board.castle_rights = BoardCastleRights::all();
+
+        return board;
+    }
+
+    // Chess960 start position number `index`, 0 to 959, in the standard numbering. Number 518 is
+    // the regular start position.
+    pub fn create_chess960(index: u32) -> Board {
+        assert!(index < 960);
+
+        // Put `kind` on the `n`th empty square of the back rank
+        let mut back_rank = [None; 8];
+        let mut place = |kind: PieceKind, n: u32| {
+            let file = (0..8).filter(|file| back_rank[*file].is_none()).nth(n as usize).unwrap();
+            back_rank[file] = Some(kind);
+        };
+
+        // Knight placements on the five squares left after the bishops and the queen
+        const KNIGHTS: [(u32, u32); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+        let mut n = index;
+        place(PieceKind::Bishop, n % 4 * 2 + 1);
+        n /= 4;
+        place(PieceKind::Bishop, n % 4 * 2);
+        n /= 4;
+        place(PieceKind::Queen, n % 6);
+        n /= 6;
+        let (first_knight, second_knight) = KNIGHTS[n as usize];
+        place(PieceKind::Knight, second_knight);
+        place(PieceKind::Knight, first_knight);
+        place(PieceKind::Rook, 0);
+        place(PieceKind::King, 0);
+        place(PieceKind::Rook, 0);
+
+        let mut board = Board::create_empty();
+        let mut pieces = Vec::with_capacity(32);
+        for x in 0..8 {
+            let kind = back_rank[x as usize].unwrap();
+            pieces.push(PieceKind::Pawn.colored(Color::White).at(x, 1));
+            pieces.push(PieceKind::Pawn.colored(Color::Black).at(x, 6));
+            pieces.push(kind.colored(Color::White).at(x, 0));
+            pieces.push(kind.colored(Color::Black).at(x, 7));
+        }
+        board.add_pieces(&pieces);
+
+        let rook_files: Vec<i8> = (0..8).filter(|file| back_rank[*file as usize] == Some(PieceKind::Rook)).collect();
+        for color in [Color::White, Color::Black] {
+            let rights = board.castle_rights.get_rights_mut(color);
+            rights.grant(Castle::QueenSide, rook_files[0]);
+            rights.grant(Castle::KingSide, rook_files[1]);
+        }
+
+        return board;
+    }
+
+    // Parse a position in Forsyth-Edwards Notation. The move counters are optional and default to
+    // 0 1.
+    // The number of ranks and the squares per rank give the board's geometry.
+    // Crazyhouse pockets follow the board in brackets, e.g. ".../RNBQKBNR[Qn] w ...", or as a ninth
+    // rank, and promoted pieces are marked with a '~'. A pocket selects the Crazyhouse variant.
+    // Three-check counters are either the checks left after the en-passant square, e.g. "3+2", or
+    // the checks given at the end, e.g. "+0+1", and select the Three-check variant.
+    pub fn from_fen(fen: &str) -> Result<Board, String> {
+        Board::from_fen_with_definitions(fen, &PIECE_DEFINITIONS)
+    }
+
+    // Parse a position whose pieces are described by `definitions` rather than PIECE_DEFINITIONS
+    pub fn from_fen_with_definitions(fen: &str, definitions: &'static PieceDefinitions) -> Result<Board, String> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 {
+            return Err(format!("Expected at least 4 fields in FEN '{}'", fen));
+        }
+
+        let mut placement = fields[0];
+        let mut pocket = None;
+        if let Some(open) = placement.find('[') {
+            if !placement.ends_with(']') {
+                return Err(format!("Unterminated pocket in FEN '{}'", fen));
+            }
+            pocket = Some(&placement[open + 1..placement.len() - 1]);
+            placement = &placement[..open];
+        }
+
+        // A ninth part without any empty squares is the pocket of an 8x8 board
+        let mut ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() == 9 && pocket.is_none() && !ranks[8].contains(|token: char| token.is_ascii_digit()) {
+            pocket = ranks.pop();
+        }
+        let geometry = Geometry::create(fen_rank_width(ranks[0]), ranks.len() as i8)
+            .ok_or(format!("Unsupported board size in FEN '{}'", fen))?;
+
+        let mut board = Board::create_empty_with_geometry(geometry);
+        board.definitions = definitions;
+        board.pattern_kinds = PatternKinds::create(definitions);
+
+        for (rank_idx, rank) in ranks.iter().enumerate() {
+            let y = geometry.ranks - 1 - rank_idx as i8;
+            let mut x: i8 = 0;
+            let mut empty: i8 = 0;
+            for token in rank.chars() {
+                if let Some(digit) = token.to_digit(10) {
+                    empty = empty.saturating_mul(10).saturating_add(digit as i8);
+                    continue;
+                }
+                x = x.saturating_add(empty);
+                empty = 0;
+
+                if token == '~' && x > 0 && board.has_piece_at(Square::at(x - 1, y)) {
+                    board.promoted |= 1 << geometry.index(Square::at(x - 1, y));
+                    continue;
+                }
+
+                let kind = kind_from_token(definitions, token).ok_or(format!("Invalid piece '{}' in FEN '{}'", token, fen))?;
+                let color = if token.is_ascii_uppercase() { Color::White } else { Color::Black };
+                if x >= geometry.files || board.piece_free_list.is_empty() {
+                    return Err(format!("Too many pieces in FEN '{}'", fen));
+                }
+                board.add_piece(&kind.colored(color).at(x, y));
+                x += 1;
+            }
+            x = x.saturating_add(empty);
+            if x != geometry.files {
+                return Err(format!("Rank '{}' doesn't have {} squares in FEN '{}'", rank, geometry.files, fen));
+            }
+        }
+
+        board.side = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(format!("Invalid side '{}' in FEN '{}'", fields[1], fen))
+        };
+
+        // KQkq refer to the outermost rooks, as in X-FEN. Shredder-FEN and X-FEN name other rooks by
+        // their file, e.g. HAha.
+        if fields[2] != "-" {
+            for token in fields[2].chars() {
+                let color = if token.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let (castle, file) = match token.to_ascii_lowercase() {
+                    'k' => (Castle::KingSide, board.outer_rook_file(color, Castle::KingSide).unwrap_or(geometry.files - 1)),
+                    'q' => (Castle::QueenSide, board.outer_rook_file(color, Castle::QueenSide).unwrap_or(0)),
+                    'a'..='j' if ((token.to_ascii_lowercase() as u8 - b'a') as i8) < geometry.files => {
+                        let file = (token.to_ascii_lowercase() as u8 - b'a') as i8;
+                        let king = board.king_square(color)
+                            .ok_or(format!("Castle rights '{}' without a king in FEN '{}'", fields[2], fen))?;
+                        (if file > king.file() { Castle::KingSide } else { Castle::QueenSide }, file)
+                    }
+                    _ => return Err(format!("Invalid castle rights '{}' in FEN '{}'", fields[2], fen))
+                };
+                board.castle_rights.get_rights_mut(color).grant(castle, file);
+            }
+        }
+
+        if fields[3] != "-" {
+            board.en_passant = Some(Square::from_coordinate(fields[3])
+                .filter(|square| geometry.contains(*square))
+                .ok_or(format!("Invalid en-passant square '{}' in FEN '{}'", fields[3], fen))?);
+        }
+
+        // The move counters are the numeric fields, after the Three-check counters if they come first
+        let mut move_counters = Vec::new();
+        for (idx, field) in fields.iter().enumerate().skip(4) {
+            let (counters, remaining) = match field.strip_prefix('+') {
+                Some(given) => (given, false),
+                None if idx == 4 && field.contains('+') => (*field, true),
+                None => {
+                    move_counters.push(field.parse::<u32>().map_err(|_| format!("Invalid move counter '{}' in FEN '{}'", field, fen))?);
+                    continue;
+                }
+            };
+            let counters: Vec<Option<u8>> = counters.split('+').map(|counter| counter.parse().ok().filter(|n| *n <= 3)).collect();
+            if counters.len() != 2 || counters.contains(&None) {
+                return Err(format!("Invalid check counters '{}' in FEN '{}'", field, fen));
+            }
+            for color in [Color::White, Color::Black] {
+                let counter = counters[color.index()].unwrap();
+                board.checks[color.index()] = if remaining { 3 - counter } else { counter };
+            }
+            board.variant = Variant::ThreeCheck;
+        }
+        if let Some(halfmove_clock) = move_counters.first() {
+            board.halfmove_clock = *halfmove_clock;
+        }
+        if let Some(fullmove_number) = move_counters.get(1) {
+            board.fullmove_number = (*fullmove_number).max(1);
+        }
+
+        if pocket.is_none() {
+            board.promoted = 0;
+        }
+        if let Some(pocket) = pocket {
+            board.variant = Variant::Crazyhouse;
+            for token in pocket.chars().filter(|token| *token != '-') {
+                let kind = kind_from_token(definitions, token)
+                    .filter(|kind| *kind != PieceKind::King)
+                    .ok_or(format!("Invalid pocket piece '{}' in FEN '{}'", token, fen))?;
+                let color = if token.is_ascii_uppercase() { Color::White } else { Color::Black };
+                board.pockets[color.index()][kind as usize] += 1;
+            }
+        }
+
+        return Ok(board);
+    }
+
+    // Inverse of from_fen()
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for y in (0..self.geometry.ranks).rev() {
+            let mut empty = 0;
+            for x in 0..self.geometry.files {
+                match self.piece_at(Square::at(x, y)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_token(self.definitions, piece));
+                        if self.is_promoted(Square::at(x, y)) {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                fen.push('/');
+            }
+        }
+
+        if self.variant.has_drops() {
+            fen.push('[');
+            for color in [Color::White, Color::Black] {
+                for kind in POCKET_KINDS.iter().rev() {
+                    let token = piece_token(self.definitions, kind.colored(color));
+                    for _ in 0..self.pockets[color.index()][*kind as usize] {
+                        fen.push(token);
+                    }
+                }
+            }
+            fen.push(']');
+        }
+
+        fen.push_str(match self.side {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+
+        // X-FEN, which only names the rook's file if it isn't the outermost one
+        let mut castle_rights = String::new();
+        for color in [Color::White, Color::Black] {
+            let rights = self.castle_rights.get_rights(color);
+            for (castle, token) in [(Castle::KingSide, 'K'), (Castle::QueenSide, 'Q')] {
+                if !rights.test(castle) {
+                    continue;
+                }
+                let standard_file = if castle == Castle::KingSide { self.geometry.files - 1 } else { 0 };
+                let token = if self.outer_rook_file(color, castle).unwrap_or(standard_file) == rights.rook_file(castle) {
+                    token
+                } else {
+                    (b'A' + rights.rook_file(castle) as u8) as char
+                };
+                castle_rights.push(if color == Color::White { token } else { token.to_ascii_lowercase() });
+            }
+        }
+        if castle_rights.is_empty() {
+            castle_rights.push('-');
+        }
+        fen.push_str(&castle_rights);
+
+        match self.en_passant {
+            Some(en_passant) => fen.push_str(&format!(" {}", en_passant.coordinate())),
+            None => fen.push_str(" -"),
+        }
+
+        if self.variant == Variant::ThreeCheck {
+            fen.push_str(&format!(" {}+{}", 3 - self.checks[Color::White.index()].min(3), 3 - self.checks[Color::Black.index()].min(3)));
+        }
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+
+        return fen;
+    }
+
     pub fn add_piece(&mut self, piece: &PieceOnBoard) {
         assert!(!self.piece_free_list.is_empty());
 
@@ -112,47 +499,64 @@ impl Board {
         });
 
         // Add piece to square list
-        let square_index = piece.square.index();
+        let square_index = self.geometry.index(piece.square);
         assert!(self.square_list[square_index].is_none());
         self.square_list[square_index] = Some(SquareListEntry { index: piece_list_index as u8 });
     }
 
-    pub fn add_pieces(&mut self, pieces: &Vec<PieceOnBoard>) {
+    pub fn add_pieces(&mut self, pieces: &[PieceOnBoard]) {
         for piece in pieces.iter() {
             self.add_piece(piece);
         }
     }
 
     pub fn remove_piece(&mut self, square: &Square) {
-        let piece_list_index = self.square_list[square.index()].unwrap().index as usize;
-        self.square_list[square.index()] = None;
+        let square_index = self.geometry.index(*square);
+        let piece_list_index = self.square_list[square_index].unwrap().index as usize;
+        self.square_list[square_index] = None;
         self.piece_list[piece_list_index] = None;
         self.piece_free_list.push(piece_list_index as u8);
     }
 
+    // Squares off the board are empty
     pub fn piece_at(&self, square: Square) -> Option<Piece> {
-        if let Some(entry) = self.square_list[square.index()] {
+        if !self.geometry.contains(square) {
+            return None;
+        }
+        if let Some(entry) = self.square_list[self.geometry.index(square)] {
             return Some(self.piece_list[entry.index as usize].unwrap().piece);
         }
         return None;
     }
 
+    pub fn definitions(&self) -> &'static PieceDefinitions {
+        self.definitions
+    }
+
+    // Kinds of pieces that move in each pattern according to definitions()
+    pub fn pattern_kinds(&self) -> &PatternKinds {
+        &self.pattern_kinds
+    }
+
+    // Whether the piece on `square` was promoted from a pawn, see `promoted`
+    pub fn is_promoted(&self, square: Square) -> bool {
+        return self.geometry.contains(square) && self.promoted & (1 << self.geometry.index(square)) != 0;
+    }
+
     pub fn has_piece_at(&self, square: Square) -> bool {
-        return self.square_list[square.index()].is_some();
+        return self.geometry.contains(square) && self.square_list[self.geometry.index(square)].is_some();
     }
 
     fn apply_move_impl(&mut self, m: Move) {
         assert_eq!(self.piece_at(m.from).unwrap().kind, m.piece_kind);
 
-        if let Some(castle) = m.castle {
-            self.apply_move_impl(Move::rook_castle(self, castle, m.from.rank()));
+        if let Some((rook_from, rook_to)) = m.castle_rook_squares() {
+            self.move_king_and_rook(m.from, m.to, rook_from, rook_to);
+            return;
         }
 
-        let from_square_index = m.from.index();
-        let to_square_index = m.to.index();
-
-        let piece_list_index = self.square_list[from_square_index].unwrap().index as usize;
-        self.square_list[from_square_index] = None;
+        let from_square_index = self.geometry.index(m.from);
+        let to_square_index = self.geometry.index(m.to);
 
         if let Some(promotion) = m.promotion {
             // Promotion is realised by removing the old piece and adding the promoted piece as a
@@ -161,26 +565,64 @@ impl Board {
             self.add_piece(&promotion.colored(self.side).at_square(&m.to));
         } else {
             // Normal from-to moves are realised by adjusting the piece and square lists.
-            self.piece_list[piece_list_index].unwrap().square = m.to;
+            let piece_list_index = self.square_list[from_square_index].unwrap().index as usize;
+            self.square_list[from_square_index] = None;
+            self.piece_list[piece_list_index].as_mut().unwrap().square = m.to;
             self.square_list[to_square_index] = Some(SquareListEntry::create(piece_list_index as u8));
         }
     }
 
+    // Castling moves king and rook at once, as in Chess960 either may land on the other's square
+    fn move_king_and_rook(&mut self, king_from: Square, king_to: Square, rook_from: Square, rook_to: Square) {
+        let king_index = self.square_list[self.geometry.index(king_from)].unwrap().index;
+        let rook_index = self.square_list[self.geometry.index(rook_from)].unwrap().index;
+        self.square_list[self.geometry.index(king_from)] = None;
+        self.square_list[self.geometry.index(rook_from)] = None;
+
+        self.piece_list[king_index as usize].as_mut().unwrap().square = king_to;
+        self.piece_list[rook_index as usize].as_mut().unwrap().square = rook_to;
+        self.square_list[self.geometry.index(king_to)] = Some(SquareListEntry::create(king_index));
+        self.square_list[self.geometry.index(rook_to)] = Some(SquareListEntry::create(rook_index));
+    }
+
     pub fn apply_move(&mut self, m: Move) {
         assert_eq!(m.en_passant_before, self.en_passant);
 
         // Capture the piece on the target square, if any
         if let Some(capture) = m.capture {
             self.remove_piece(&capture.square);
-        } else {
+        } else if m.castle.is_none() {
             assert!(!self.has_piece_at(m.to));
         }
 
-        self.apply_move_impl(m);
+        if m.is_drop {
+            self.pockets[self.side.index()][m.piece_kind as usize] -= 1;
+            self.add_piece(&m.piece_kind.colored(self.side).at_square(&m.to));
+        } else {
+            self.apply_move_impl(m);
+        }
+
+        if self.variant.has_drops() {
+            if let Some(kind) = m.pocketed() {
+                self.pockets[self.side.index()][kind as usize] += 1;
+            }
+            self.promoted = m.promoted_after(self.promoted, &self.geometry);
+        }
 
         self.en_passant = m.en_passant_after;
-        self.castle_rights = m.castle_rights_after(self.side);
+        self.castle_rights = m.castle_rights_after(self.side, &self.geometry);
+        if self.variant == Variant::Atomic && m.capture.is_some() {
+            self.explode(m);
+        }
+        self.halfmove_clock = if m.is_zeroing() { 0 } else { self.halfmove_clock + 1 };
+        if self.side == Color::Black {
+            self.fullmove_number += 1;
+        }
         self.side = self.side.switch();
+
+        if self.variant == Variant::ThreeCheck && is_check(self, self.side) {
+            self.checks[self.side.switch().index()] += 1;
+        }
     }
 
     fn revert_move_impl(&mut self, m: Move) {
@@ -190,85 +632,239 @@ impl Board {
             assert_eq!(self.piece_at(m.to).unwrap().kind, m.piece_kind);
         }
 
-        if let Some(castle) = m.castle {
-            self.revert_move_impl(Move::rook_castle(self, castle, m.from.rank()));
+        if let Some((rook_from, rook_to)) = m.castle_rook_squares() {
+            self.move_king_and_rook(m.to, m.from, rook_to, rook_from);
+            return;
         }
 
-        let from_square_index = m.from.index();
-        let to_square_index = m.to.index();
-
-        let piece_list_index = self.square_list[to_square_index].unwrap().index as usize;
-        self.square_list[to_square_index] = None;
+        let from_square_index = self.geometry.index(m.from);
+        let to_square_index = self.geometry.index(m.to);
 
-        if let Some(promotion) = m.promotion {
-            // Promotion is realised by removing the old piece and adding the promoted piece as a
-            // new piece.
+        if m.promotion.is_some() {
+            // Promotion is realised by removing the promoted piece and adding the pawn back as a
+            // new piece. The side to move is still the opponent of the promoting side.
             self.remove_piece(&m.to);
-            self.add_piece(&PieceKind::Pawn.colored(self.side).at_square(&m.from));
+            self.add_piece(&PieceKind::Pawn.colored(self.side.switch()).at_square(&m.from));
         } else {
             // Normal from-to moves are realised by adjusting the piece and square lists.
-            self.piece_list[piece_list_index as usize].unwrap().square = m.from;
+            let piece_list_index = self.square_list[to_square_index].unwrap().index as usize;
+            self.square_list[to_square_index] = None;
+            self.piece_list[piece_list_index].as_mut().unwrap().square = m.from;
             self.square_list[from_square_index] = Some(SquareListEntry::create(piece_list_index as u8));
         }
     }
 
     pub fn revert_move(&mut self, m: Move) {
-        self.revert_move_impl(m);
+        if self.variant == Variant::ThreeCheck && is_check(self, self.side) {
+            self.checks[self.side.switch().index()] -= 1;
+        }
+
+        if self.variant == Variant::Atomic && m.capture.is_some() {
+            self.revert_explosion(m);
+        }
+
+        if m.is_drop {
+            self.remove_piece(&m.to);
+            self.pockets[self.side.switch().index()][m.piece_kind as usize] += 1;
+        } else {
+            self.revert_move_impl(m);
+        }
 
         // Revert capture, if any
         if let Some(capture) = m.capture {
             self.add_piece(&capture);
         }
 
+        if self.variant.has_drops() {
+            if let Some(kind) = m.pocketed() {
+                self.pockets[self.side.switch().index()][kind as usize] -= 1;
+            }
+            self.promoted = m.promoted_before(self.promoted, &self.geometry);
+        }
+
         self.side = self.side.switch();
         self.en_passant = m.en_passant_before;
         self.castle_rights = m.castle_rights_before;
+        self.halfmove_clock = m.halfmove_clock_before;
+        if self.side == Color::Black {
+            self.fullmove_number -= 1;
+        }
+    }
+
+    pub fn pieces(&self) -> Vec<PieceOnBoard> {
+        self.piece_list.iter()
+            .filter_map(|entry| entry.map(|entry| PieceOnBoard::create(&entry.piece, &entry.square)))
+            .collect()
+    }
+
+    // An Atomic capture blows up the capturing piece and the pieces around it other than pawns.
+    // Exploded kings and rooks lose their castling rights as if they had moved.
+    fn explode(&mut self, m: Move) {
+        self.remove_piece(&m.to);
+        let mut count = 0;
+        for (x_delta, y_delta) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, 1), (-1, -1), (1, -1)] {
+            let square = m.to.delta(x_delta, y_delta);
+            let piece = match self.piece_at(square) {
+                Some(piece) if piece.kind != PieceKind::Pawn => piece,
+                _ => continue,
+            };
+
+            self.remove_piece(&square);
+            self.exploded.push(piece.at_square(&square));
+            count += 1;
+
+            let rights = self.castle_rights.get_rights_mut(piece.color);
+            match piece.kind {
+                PieceKind::King => *rights = ColorCastleRights::none(),
+                PieceKind::Rook if square.rank() == self.geometry.back_rank(piece.color) => {
+                    for castle in [Castle::KingSide, Castle::QueenSide] {
+                        if square.file() == rights.rook_file(castle) {
+                            rights.revoke(castle);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.explosion_sizes.push(count);
+    }
+
+    // Inverse of explode(), restores the pieces in reverse order so that they get their original
+    // places in the piece list back
+    fn revert_explosion(&mut self, m: Move) {
+        let count = self.explosion_sizes.pop().unwrap();
+        for _ in 0..count {
+            let piece = self.exploded.pop().unwrap();
+            self.add_piece(&piece);
+        }
+        let kind = m.promotion.unwrap_or(m.piece_kind);
+        self.add_piece(&kind.colored(self.side.switch()).at_square(&m.to));
+    }
+
+    // Pass the turn to the other side without moving a piece, as null-move pruning does. Returns
+    // the en-passant square to be handed to revert_null_move().
+    pub fn apply_null_move(&mut self) -> Option<Square> {
+        let en_passant = self.en_passant;
+        self.en_passant = None;
+        self.side = self.side.switch();
+        return en_passant;
+    }
+
+    pub fn revert_null_move(&mut self, en_passant: Option<Square>) {
+        self.side = self.side.switch();
+        self.en_passant = en_passant;
+    }
+
+    // Whether `color` has pieces besides pawns and the king. Without them, zugzwang is common.
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        for entry in self.piece_list.iter().flatten() {
+            if entry.piece.color == color && entry.piece.kind != PieceKind::Pawn &&
+                entry.piece.kind != PieceKind::King && entry.piece.kind != PieceKind::Dummy {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Number of pieces on the board, kings included
+    pub fn piece_count(&self) -> usize {
+        self.piece_list.iter().filter(|entry| entry.is_some()).count()
     }
 
     pub fn is_game_over(&mut self) -> bool {
         generate_moves(self).is_empty()
     }
 
+    // Whether a side may castle with its king off the middle file or with a rook other than the
+    // corner one, so that castling has to be written as in Chess960
+    pub fn is_chess960(&self) -> bool {
+        [Color::White, Color::Black].iter().any(|color| {
+            let rights = self.castle_rights.get_rights(*color);
+            let king_file = self.king_square(*color).map(|king| king.file());
+            [(Castle::KingSide, self.geometry.files - 1), (Castle::QueenSide, 0)].iter().any(|(castle, corner)| {
+                rights.test(*castle) && (rights.rook_file(*castle) != *corner || king_file != Some(self.geometry.files / 2))
+            })
+        })
+    }
+
+    // File of the rook of `color` furthest from the king on the `castle` side of the back rank
+    fn outer_rook_file(&self, color: Color, castle: Castle) -> Option<i8> {
+        let king = self.king_square(color).filter(|king| king.rank() == self.geometry.back_rank(color))?;
+        let rook = PieceKind::Rook.colored(color);
+        let is_rook = |file: &i8| self.piece_at(Square::at(*file, king.rank())) == Some(rook);
+
+        return match castle {
+            Castle::KingSide => (king.file() + 1..self.geometry.files).rev().find(is_rook),
+            Castle::QueenSide => (0..king.file()).find(is_rook),
+        };
+    }
+
     pub fn king_square(&self, color: Color) -> Option<Square> {
-        for entry in self.piece_list.iter() {
-            if let Some(entry) = entry {
-                if entry.piece.kind == PieceKind::King && entry.piece.color == color {
-                    return Some(entry.square);
-                }
+        for entry in self.piece_list.iter().flatten() {
+            if entry.piece.kind == PieceKind::King && entry.piece.color == color {
+                return Some(entry.square);
             }
         }
         None
     }
 
-    pub fn print(&self) {
-        print!("  ", );
-        for file in 0..8 {
-            print!("{} ", file);
+    // Zobrist hash of the position, used to identify it in the transposition table. Computed from
+    // scratch rather than incrementally.
+    pub fn hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for entry in self.piece_list.iter().flatten() {
+            let piece_index = (entry.piece.color.index() * PIECE_KIND_COUNT) as u64 + entry.piece.kind as u64;
+            hash ^= zobrist_key(ZOBRIST_PIECE_KEYS + piece_index * MAX_SQUARES as u64 + self.geometry.index(entry.square) as u64);
         }
-        println!();
 
-        for rank in 0..8 {
-            print!("{} ", rank);
-            for file in 0..8 {
-                let piece = self.piece_at(Square::at(file, rank));
+        if self.side == Color::Black {
+            hash ^= zobrist_key(ZOBRIST_SIDE_KEY);
+        }
 
-                let mut token = match piece {
-                    Some(piece) => piece.kind.token(),
-                    None => '.'
-                };
+        let castle_rights = [self.castle_rights.white.king_side, self.castle_rights.white.queen_side,
+            self.castle_rights.black.king_side, self.castle_rights.black.queen_side];
+        for (idx, right) in castle_rights.iter().enumerate() {
+            if *right {
+                hash ^= zobrist_key(ZOBRIST_CASTLE_KEYS + idx as u64);
+            }
+        }
 
-                if piece.is_some() && piece.unwrap().color == Color::White {
-                    token = token.to_uppercase().to_string().chars().nth(0).unwrap();
-                }
+        if let Some(en_passant) = self.en_passant {
+            hash ^= zobrist_key(ZOBRIST_EN_PASSANT_KEYS + en_passant.file() as u64);
+        }
+
+        for color in [Color::White, Color::Black] {
+            let checks = self.checks[color.index()] as u64;
+            if checks > 0 {
+                hash ^= zobrist_key(ZOBRIST_CHECK_KEYS + color.index() as u64 * 4 + checks.min(3));
+            }
 
-                print!("{} ", token.to_string());
+            for kind in POCKET_KINDS {
+                let count = self.pockets[color.index()][kind as usize] as u64;
+                if count > 0 {
+                    hash ^= zobrist_key(ZOBRIST_POCKET_KEYS + (color.index() as u64 * 5 + kind as u64) * 32 + count.min(31));
+                }
             }
-            println!();
         }
+
+        let mut promoted = self.promoted;
+        while promoted != 0 {
+            hash ^= zobrist_key(ZOBRIST_PROMOTED_KEYS + promoted.trailing_zeros() as u64);
+            promoted &= promoted - 1;
+        }
+
+        return hash;
+    }
+
+    pub fn print(&self) {
+        println!("{}", self);
     }
 
     pub fn semantic_eq(&self, other: &Self) -> bool {
-        if self.side != other.side || self.en_passant != other.en_passant || self.castle_rights != other.castle_rights {
+        if self.side != other.side || self.en_passant != other.en_passant || self.castle_rights != other.castle_rights ||
+            self.geometry != other.geometry || self.variant != other.variant || self.checks != other.checks || self.pockets != other.pockets ||
+            self.promoted != other.promoted {
             return false;
         }
 
@@ -289,9 +885,9 @@ mod test {
     #[test]
     fn board_apply_and_revert_move() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(0, 1)));
-        let mut move_ = TestMove::from_to(&board, Square::at(0, 1), Square::at(0, 2));
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 1)]);
+        let move_ = TestMove::from_to(&board, Square::at(0, 1), Square::at(0, 2));
         let original_board = board.clone();
 
         // Apply the move
@@ -299,8 +895,8 @@ mod test {
 
         let mut expected_board = Board::create_empty();
         expected_board.side = Color::Black;
-        expected_board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(0, 2)));
+        expected_board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 2)]);
         assert_eq!(board, expected_board);
 
         // Revert the move
@@ -311,12 +907,12 @@ mod test {
     #[test]
     fn board_apply_and_revert_move_with_capture() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 1),
-            PieceKind::Pawn.colored(Color::Black).at(1, 2)));
+            PieceKind::Pawn.colored(Color::Black).at(1, 2)]);
         let original_board = board.clone();
 
-        let mut move_ = TestMove::from_to_capture(&board, Square::at(0, 1), Square::at(1, 2), PieceKind::Pawn.colored(Color::Black).at(1, 2));
+        let move_ = TestMove::from_to_capture(&board, Square::at(0, 1), Square::at(1, 2), PieceKind::Pawn.colored(Color::Black).at(1, 2));
 
 
         // Apply the move
@@ -324,8 +920,8 @@ mod test {
 
         let mut expected_board = Board::create_empty();
         expected_board.side = Color::Black;
-        expected_board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(1, 2)));
+        expected_board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(1, 2)]);
 
         assert_eq!(board, expected_board);
 
@@ -337,13 +933,13 @@ mod test {
     #[test]
     fn board_apply_and_revert_move_with_en_passant_square() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(2, 4)));
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(2, 4)]);
         board.en_passant = Some(Square::at(4, 2));
 
         let original_board = board.clone();
 
-        let mut move_ = TestMove::from_to(&board, Square::at(2, 4), Square::at(2, 5));
+        let move_ = TestMove::from_to(&board, Square::at(2, 4), Square::at(2, 5));
 
         // Apply the move
         board.apply_move(move_);
@@ -351,8 +947,8 @@ mod test {
         let mut expected_board = Board::create_empty();
         expected_board.side = Color::Black;
         expected_board.en_passant = None;
-        expected_board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(2, 5)));
+        expected_board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(2, 5)]);
 
         assert_eq!(board, expected_board);
 
@@ -364,22 +960,22 @@ mod test {
     #[test]
     fn board_apply_and_revert_move_with_en_passant_capture() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::Black).at(1, 4),
-            PieceKind::Pawn.colored(Color::White).at(2, 4)));
+            PieceKind::Pawn.colored(Color::White).at(2, 4)]);
         let original_board = board.clone();
 
-        let mut move_ = TestMove::from_to_capture(&board, Square::at(2, 4), Square::at(1, 5), PieceKind::Pawn.colored(Color::Black).at(1, 4));
+        let move_ = TestMove::from_to_capture(&board, Square::at(2, 4), Square::at(1, 5), PieceKind::Pawn.colored(Color::Black).at(1, 4));
 
         // Apply the move
         board.apply_move(move_);
 
         let mut expected_board = Board::create_empty();
         expected_board.side = Color::Black;
-        expected_board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(1, 5)));
+        expected_board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(1, 5)]);
 
-        assert_eq!(board, expected_board);
+        assert!(board.semantic_eq(&expected_board));
 
         // Revert the move
         board.revert_move(move_);
@@ -389,19 +985,19 @@ mod test {
     #[test]
     fn board_apply_and_revert_move_with_promotion() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(1, 6)));
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(1, 6)]);
         let original_board = board.clone();
 
-        let mut move_ = TestMove::promotion(&board, Square::at(1, 6), Square::at(1, 7), PieceKind::Bishop);
+        let move_ = TestMove::promotion(&board, Square::at(1, 6), Square::at(1, 7), PieceKind::Bishop);
 
         // Apply the move
         board.apply_move(move_);
 
         let mut expected_board = Board::create_empty();
         expected_board.side = Color::Black;
-        expected_board.add_pieces(vec!(
-            PieceKind::Bishop.colored(Color::White).at(1, 7)));
+        expected_board.add_pieces(&[
+            PieceKind::Bishop.colored(Color::White).at(1, 7)]);
 
         assert_eq!(board, expected_board);
 
@@ -413,20 +1009,20 @@ mod test {
     #[test]
     fn board_apply_and_revert_move_with_capture_and_promotion() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(1, 6),
-            PieceKind::Pawn.colored(Color::Black).at(2, 7)));
+            PieceKind::Pawn.colored(Color::Black).at(2, 7)]);
         let original_board = board.clone();
 
-        let mut move_ = TestMove::promotion_capture(&board, Square::at(1, 6), Square::at(2, 7), PieceKind::Pawn.colored(Color::Black).at(2, 7), PieceKind::Bishop);
+        let move_ = TestMove::promotion_capture(&board, Square::at(1, 6), Square::at(2, 7), PieceKind::Pawn.colored(Color::Black).at(2, 7), PieceKind::Bishop);
 
         // Apply the move
         board.apply_move(move_);
 
         let mut expected_board = Board::create_empty();
         expected_board.side = Color::Black;
-        expected_board.add_pieces(vec!(
-            PieceKind::Bishop.colored(Color::White).at(2, 7)));
+        expected_board.add_pieces(&[
+            PieceKind::Bishop.colored(Color::White).at(2, 7)]);
 
         assert_eq!(board, expected_board);
 
@@ -438,14 +1034,14 @@ mod test {
     #[test]
     fn board_apply_and_revert_king_side_castling() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::King.colored(Color::White).at(4, 0),
             PieceKind::Rook.colored(Color::White).at(0, 0),
-            PieceKind::Rook.colored(Color::White).at(7, 0)));
+            PieceKind::Rook.colored(Color::White).at(7, 0)]);
         board.castle_rights = BoardCastleRights::all();
         let original_board = board.clone();
 
-        let mut move_ = TestMove::castle(&board, Color::White, Castle::KingSide);
+        let move_ = TestMove::castle(&board, Color::White, Castle::KingSide);
 
         // Apply the move
         board.apply_move(move_);
@@ -454,10 +1050,11 @@ mod test {
         expected_board.side = Color::Black;
         expected_board.castle_rights.white = ColorCastleRights::none();
         expected_board.castle_rights.black = ColorCastleRights::all();
-        expected_board.add_pieces(vec!(
+        expected_board.halfmove_clock = 1;
+        expected_board.add_pieces(&[
             PieceKind::King.colored(Color::White).at(6, 0),
             PieceKind::Rook.colored(Color::White).at(0, 0),
-            PieceKind::Rook.colored(Color::White).at(5, 0)));
+            PieceKind::Rook.colored(Color::White).at(5, 0)]);
         assert_eq!(board, expected_board);
 
         // Revert the move
@@ -469,14 +1066,14 @@ mod test {
     fn board_apply_and_revert_queen_side_castling() {
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::King.colored(Color::Black).at(4, 7),
             PieceKind::Rook.colored(Color::Black).at(0, 7),
-            PieceKind::Rook.colored(Color::Black).at(7, 7)));
+            PieceKind::Rook.colored(Color::Black).at(7, 7)]);
         board.castle_rights = BoardCastleRights::all();
         let original_board = board.clone();
 
-        let mut move_ = TestMove::castle(&board, Color::Black, Castle::QueenSide);
+        let move_ = TestMove::castle(&board, Color::Black, Castle::QueenSide);
 
         // Apply the move
         board.apply_move(move_);
@@ -484,10 +1081,12 @@ mod test {
         let mut expected_board = Board::create_empty();
         expected_board.castle_rights.white = ColorCastleRights::all();
         expected_board.castle_rights.black = ColorCastleRights::none();
-        expected_board.add_pieces(vec!(
+        expected_board.halfmove_clock = 1;
+        expected_board.fullmove_number = 2;
+        expected_board.add_pieces(&[
             PieceKind::King.colored(Color::Black).at(2, 7),
             PieceKind::Rook.colored(Color::Black).at(3, 7),
-            PieceKind::Rook.colored(Color::Black).at(7, 7)));
+            PieceKind::Rook.colored(Color::Black).at(7, 7)]);
         assert_eq!(board, expected_board);
 
         // Revert the move
@@ -499,11 +1098,10 @@ mod test {
     fn board_apply_and_revert_castle_rights_loss_through_normal_move() {
         let mut board = Board::create_empty();
         board.castle_rights = BoardCastleRights::all();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Rook.colored(Color::White).at(0, 0),
             PieceKind::King.colored(Color::White).at(4, 0),
-            PieceKind::Rook.colored(Color::White).at(7, 0)));
-        let original_board = board.clone();
+            PieceKind::Rook.colored(Color::White).at(7, 0)]);
 
         // Moving the king-side Rook looses queen side castle rights
         let move_ = TestMove::from_to(&board, Square::at(0, 0), Square::at(0, 1));
@@ -514,7 +1112,7 @@ mod test {
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         board.revert_move(move_);
-        let mut expected_castle_rights = BoardCastleRights::all();
+        let expected_castle_rights = BoardCastleRights::all();
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         // Moving the queen-side Rook looses king side castle rights
@@ -526,7 +1124,7 @@ mod test {
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         board.revert_move(move_);
-        let mut expected_castle_rights = BoardCastleRights::all();
+        let expected_castle_rights = BoardCastleRights::all();
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         // Moving the King looses castle rights on both sides
@@ -538,21 +1136,78 @@ mod test {
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         board.revert_move(move_);
-        let mut expected_castle_rights = BoardCastleRights::all();
+        let expected_castle_rights = BoardCastleRights::all();
         assert_eq!(board.castle_rights, expected_castle_rights);
     }
 
+    #[test]
+    fn board_apply_and_revert_null_move() {
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(2, 4)]);
+        board.en_passant = Some(Square::at(1, 5));
+        let original_board = board.clone();
+
+        let en_passant = board.apply_null_move();
+        assert_eq!(board.side, Color::Black);
+        assert_eq!(board.en_passant, None);
+
+        board.revert_null_move(en_passant);
+        assert_eq!(board, original_board);
+    }
+
+    #[test]
+    fn board_hash() {
+        let board = Board::create_populated();
+
+        // Transpositions have the same hash
+        let mut a = board.clone();
+        a.apply_move(TestMove::from_to(&a, Square::at(1, 0), Square::at(2, 2)));
+        a.apply_move(TestMove::from_to(&a, Square::at(1, 7), Square::at(2, 5)));
+        a.apply_move(TestMove::from_to(&a, Square::at(6, 0), Square::at(5, 2)));
+        let mut b = board.clone();
+        b.apply_move(TestMove::from_to(&b, Square::at(6, 0), Square::at(5, 2)));
+        b.apply_move(TestMove::from_to(&b, Square::at(1, 7), Square::at(2, 5)));
+        b.apply_move(TestMove::from_to(&b, Square::at(1, 0), Square::at(2, 2)));
+        assert_eq!(a.hash(), b.hash());
+
+        // Side to move, castle rights and en-passant square are part of the hash
+        let mut c = board.clone();
+        c.side = Color::Black;
+        assert_ne!(c.hash(), board.hash());
+
+        let mut c = board.clone();
+        c.castle_rights.white.king_side = false;
+        assert_ne!(c.hash(), board.hash());
+
+        let mut c = board.clone();
+        c.en_passant = Some(Square::at(4, 2));
+        assert_ne!(c.hash(), board.hash());
+    }
+
+    #[test]
+    fn board_has_non_pawn_material() {
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Pawn.colored(Color::White).at(4, 1),
+            PieceKind::King.colored(Color::Black).at(4, 7),
+            PieceKind::Knight.colored(Color::Black).at(1, 7)]);
+
+        assert!(!board.has_non_pawn_material(Color::White));
+        assert!(board.has_non_pawn_material(Color::Black));
+    }
+
     #[test]
     fn board_apply_and_revert_castle_rights_loss_through_capture() {
         let mut board = Board::create_empty();
         board.castle_rights = BoardCastleRights::all();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Rook.colored(Color::Black).at(0, 7),
             PieceKind::King.colored(Color::Black).at(4, 7),
             PieceKind::Rook.colored(Color::Black).at(7, 7),
             PieceKind::Pawn.colored(Color::White).at(1, 6),
-            PieceKind::Pawn.colored(Color::White).at(6, 6)));
-        let original_board = board.clone();
+            PieceKind::Pawn.colored(Color::White).at(6, 6)]);
 
         // Moving the king-side Rook looses queen side castle rights
         let queen_side_capture = TestMove::from_to_capture(&board, Square::at(1, 6), Square::at(0, 7), PieceKind::Rook.colored(Color::Black).at(0, 7));
@@ -563,7 +1218,7 @@ mod test {
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         board.revert_move(queen_side_capture);
-        let mut expected_castle_rights = BoardCastleRights::all();
+        let expected_castle_rights = BoardCastleRights::all();
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         // Moving the queen-side Rook looses king side castle rights
@@ -575,7 +1230,7 @@ mod test {
         assert_eq!(board.castle_rights, expected_castle_rights);
 
         board.revert_move(queen_side_capture);
-        let mut expected_castle_rights = BoardCastleRights::all();
+        let expected_castle_rights = BoardCastleRights::all();
         assert_eq!(board.castle_rights, expected_castle_rights);
     }
 
@@ -586,10 +1241,10 @@ mod test {
 
         let mut board = Board::create_empty();
         board.castle_rights = BoardCastleRights::none();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Rook.colored(Color::White).at(0, 0),
             PieceKind::King.colored(Color::White).at(4, 0),
-            PieceKind::Rook.colored(Color::White).at(7, 0)));
+            PieceKind::Rook.colored(Color::White).at(7, 0)]);
 
         // Moving the king-side Rook
         let move_ = TestMove::from_to(&board, Square::at(0, 0), Square::at(0, 1));
@@ -615,4 +1270,165 @@ mod test {
         board.revert_move(move_);
         assert_eq!(board.castle_rights, BoardCastleRights::none());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn board_fen() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        assert!(board.semantic_eq(&Board::create_populated()));
+        assert_eq!(board.to_fen(), START_FEN);
+
+        let fen = "4k3/8/8/3pP3/8/8/8/R3K3 w Q d6 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.piece_at(Square::at(3, 4)), Some(PieceKind::Pawn.colored(Color::Black)));
+        assert_eq!(board.piece_at(Square::at(0, 0)), Some(PieceKind::Rook.colored(Color::White)));
+        assert_eq!(board.en_passant, Some(Square::at(3, 5)));
+        assert_eq!(board.castle_rights.white, ColorCastleRights { king_side: false, queen_side: true, king_side_file: 7, queen_side_file: 0 });
+        assert_eq!(board.castle_rights.black, ColorCastleRights::none());
+        assert_eq!(board.to_fen(), fen);
+
+        // Any number of ranks up to ten is a board, but they have to be equally wide
+        assert!(Board::from_fen("8/8/8/8/8/8/7 w - -").is_err());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/7x w - -").is_err());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/8 x - -").is_err());
+    }
+
+    #[test]
+    fn board_chess960() {
+        assert!(Board::create_chess960(518).semantic_eq(&Board::create_populated()));
+        assert!(!Board::create_chess960(518).is_chess960());
+        assert!(Board::create_chess960(0).is_chess960());
+        assert!(!Variant::Capablanca.start_board().is_chess960());
+        assert_eq!(Board::create_chess960(0).to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(Board::create_chess960(959).to_fen(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1");
+
+        // Shredder-FEN names the rook files, X-FEN only if the rook isn't the outermost one
+        let board = Board::from_fen("1r2k1r1/8/8/8/8/8/8/RR2K3 w Bg - 0 1").unwrap();
+        assert_eq!(board.castle_rights.white.queen_side_file, 1);
+        assert!(!board.castle_rights.white.king_side);
+        assert_eq!(board.castle_rights.black.king_side_file, 6);
+        assert_eq!(board.to_fen(), "1r2k1r1/8/8/8/8/8/8/RR2K3 w Bk - 0 1");
+        assert!(Board::from_fen("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1").unwrap()
+            .semantic_eq(&Board::create_chess960(0)));
+    }
+
+    #[test]
+    fn board_crazyhouse_fen() {
+        let fen = "r1bqkb1r/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQ~KB1R[QNpp] w KQkq - 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.variant, Variant::Crazyhouse);
+        assert_eq!(board.pockets[Color::White.index()], [0, 1, 0, 0, 1]);
+        assert_eq!(board.pockets[Color::Black.index()], [2, 0, 0, 0, 0]);
+        assert_eq!(board.promoted, 1 << Square::at(3, 0).index());
+        assert_eq!(board.to_fen(), fen);
+
+        // The pocket may also be written as a ninth rank, and may be empty
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3/Qn w - - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3[Qn] w - - 0 1");
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3[] w - - 0 1").unwrap().to_fen(), "4k3/8/8/8/8/8/8/4K3[] w - - 0 1");
+
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3[K] w - - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3[Q w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn board_three_check_fen() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 1+3 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.variant, Variant::ThreeCheck);
+        assert_eq!(board.checks, [2, 0]);
+        assert_eq!(board.to_fen(), fen);
+
+        // Lichess counts the checks given at the end
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 +2+0").unwrap().to_fen(), fen);
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 4+3 0 1").is_err());
+    }
+
+    #[test]
+    fn board_geometry() {
+        // The dimensions follow from the ranks and their width
+        let fen = "r4k3r/pppppppppp/10/10/10/10/PPPPPPPPPP/R4K3R w KQkq - 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.geometry, Geometry::CAPABLANCA);
+        assert_eq!(board.to_fen(), fen);
+        let board = Board::from_fen("rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1").unwrap();
+        assert_eq!(board.geometry, Geometry::LOS_ALAMOS);
+        assert_eq!(board.piece_at(Square::at(5, 5)), Some(PieceKind::Rook.colored(Color::Black)));
+        assert_eq!(board.piece_at(Square::at(6, 0)), None);
+
+        assert!(Board::from_fen("8/8/8/8/8/8/8/9 w - - 0 1").is_err());
+        assert!(Board::from_fen("11/11/11/11/11/11/11/11 w - - 0 1").is_err());
+
+        // The king castles to the i- or c-file on ten files
+        let mut board = Board::from_fen(fen).unwrap();
+        let original_board = board.clone();
+        let castle = Move::castle(&board, Color::White, Castle::KingSide);
+        assert_eq!(castle.coordinate_notation(), "f1i1");
+        board.apply_move(castle);
+        assert_eq!(board.to_fen(), "r4k3r/pppppppppp/10/10/10/10/PPPPPPPPPP/R6RK1 b kq - 1 1");
+        board.revert_move(castle);
+        assert_eq!(board, original_board);
+        board.apply_move(Move::castle(&board, Color::White, Castle::QueenSide));
+        assert_eq!(board.to_fen(), "r4k3r/pppppppppp/10/10/10/10/PPPPPPPPPP/2KR5R b kq - 1 1");
+    }
+
+    #[test]
+    fn board_apply_and_revert_drop() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K3[Pn] w - - 0 1").unwrap();
+        let original_board = board.clone();
+
+        let move_ = Move::drop(&board, PieceKind::Pawn, Square::at(4, 3));
+        assert_eq!(move_.coordinate_notation(), "P@e4");
+        board.apply_move(move_);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/4P3/8/8/4K3[n] b - - 0 1");
+        assert_ne!(board.hash(), original_board.hash());
+        board.revert_move(move_);
+        assert_eq!(board, original_board);
+    }
+
+    #[test]
+    fn board_apply_and_revert_chess960_castling() {
+        // King and rook swap squares
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/5KR1 w K - 0 1").unwrap();
+        let original_board = board.clone();
+
+        let move_ = TestMove::castle(&board, Color::White, Castle::KingSide);
+        board.apply_move(move_);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+        board.revert_move(move_);
+        assert_eq!(board, original_board);
+
+        // The king stays where it is
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/1RK5 w Q - 0 1").unwrap();
+        let move_ = TestMove::castle(&board, Color::White, Castle::QueenSide);
+        board.apply_move(move_);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/2KR4 b - - 1 1");
+    }
+
+    #[test]
+    fn board_move_counters() {
+        let mut board = Board::from_fen("4k3/4p3/8/8/8/8/8/R3K3 b - - 12 40").unwrap();
+        let original_board = board.clone();
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (12, 40));
+
+        // Quiet moves count up, Black's moves start the next move and pawn moves reset the clock
+        let king_move = TestMove::from_to(&board, Square::at(4, 7), Square::at(3, 7));
+        board.apply_move(king_move);
+        assert_eq!(board.to_fen(), "3k4/4p3/8/8/8/8/8/R3K3 w - - 13 41");
+        let rook_move = TestMove::from_to(&board, Square::at(0, 0), Square::at(0, 1));
+        board.apply_move(rook_move);
+        assert_eq!(board.to_fen(), "3k4/4p3/8/8/8/8/R7/4K3 b - - 14 41");
+        let pawn_move = TestMove::from_to(&board, Square::at(4, 6), Square::at(4, 5));
+        board.apply_move(pawn_move);
+        assert_eq!(board.to_fen(), "3k4/8/4p3/8/8/8/R7/4K3 w - - 0 42");
+
+        board.revert_move(pawn_move);
+        board.revert_move(rook_move);
+        board.revert_move(king_move);
+        assert_eq!(board, original_board);
+
+        // Counters follow Three-check counters and default to 0 1
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 3+3 5 9").unwrap().fullmove_number, 9);
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap().to_fen(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::core::*;
+use super::opening_book::*;
+use super::pgn::*;
+use super::polyglot::*;
+use super::variant::*;
+
+// Games and results of a move in a position, from the perspective of the side that plays it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MoveStatistics {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStatistics {
+    // Fraction of the points the move scored, a draw counting half
+    pub fn score(&self) -> f32 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        (self.wins as f32 + self.draws as f32 / 2.0) / self.games as f32
+    }
+}
+
+// Collects the moves of PGN games into a Polyglot opening book
+pub struct BookBuilder {
+    max_ply: usize,
+    // Keyed by Polyglot position key and move
+    moves: HashMap<(u64, u16), MoveStatistics>,
+}
+
+impl BookBuilder {
+    // Only the first `max_ply` half-moves of each game go into the book
+    pub fn create(max_ply: usize) -> BookBuilder {
+        BookBuilder { max_ply, moves: HashMap::new() }
+    }
+
+    // Count the moves of a game. Games without result are skipped, as are games of other variants
+    // or on other boards than Polyglot's and games with illegal moves, which produce an error.
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<(), String> {
+        if game.result == GameResult::Unknown {
+            return Ok(());
+        }
+
+        let mut board = game.start_board()?;
+        if board.geometry != Geometry::STANDARD || board.variant != Variant::Standard {
+            return Ok(());
+        }
+        let moves = game.replay()?;
+
+        for m in moves.iter().take(self.max_ply) {
+            // Positions with pieces Polyglot doesn't know can't go into the book
+            if let Some(key) = polyglot_key(&board) {
+                let statistics = self.moves.entry((key, encode_move(m))).or_default();
+                statistics.games += 1;
+
+                match (game.result, board.side) {
+                    (GameResult::Draw, _) => statistics.draws += 1,
+                    (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => statistics.wins += 1,
+                    _ => statistics.losses += 1,
+                }
+            }
+
+            board.apply_move(*m);
+        }
+
+        return Ok(());
+    }
+
+    // Add all games of a PGN file, returns the number of games that couldn't be replayed
+    pub fn add_pgn(&mut self, text: &str) -> Result<usize, String> {
+        let mut failed = 0;
+        for game in parse_pgn(text)?.iter() {
+            if self.add_game(game).is_err() {
+                failed += 1;
+            }
+        }
+        return Ok(failed);
+    }
+
+    pub fn get_statistics(&self, key: u64, move_data: u16) -> Option<MoveStatistics> {
+        self.moves.get(&(key, move_data)).copied()
+    }
+
+    // Book entries of the moves played in at least `min_games` games that scored at least
+    // `min_score`, sorted by key and then by weight, highest first. The weight is Polyglot's
+    // 2 points per win and 1 per draw, scaled down to fit if necessary.
+    pub fn build(&self, min_games: u32, min_score: f32) -> Vec<BookEntry> {
+        let selected: Vec<(&(u64, u16), &MoveStatistics)> = self.moves.iter()
+            .filter(|(_, statistics)| statistics.games >= min_games && statistics.score() >= min_score)
+            .collect();
+
+        let max_points = selected.iter().map(|(_, statistics)| 2 * statistics.wins as u64 + statistics.draws as u64).max().unwrap_or(0);
+        let scale = (max_points as f64 / u16::MAX as f64).max(1.0);
+
+        let mut entries: Vec<BookEntry> = selected.iter().map(|((key, move_data), statistics)| {
+            let points = 2 * statistics.wins as u64 + statistics.draws as u64;
+            BookEntry {
+                key: *key,
+                move_data: *move_data,
+                // Keep moves that only drew or lost in the book
+                weight: ((points as f64 / scale) as u16).max(1),
+                learn: 0,
+            }
+        }).collect();
+
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)).then(a.move_data.cmp(&b.move_data)));
+        return entries;
+    }
+
+    pub fn to_bytes(&self, min_games: u32, min_score: f32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for entry in self.build(min_games, min_score).iter() {
+            entry.write(&mut bytes);
+        }
+        return bytes;
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P, min_games: u32, min_score: f32) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes(min_games, min_score))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::*;
+    use crate::uci::parse_move;
+
+    const PGN: &str = r#"[Result "1-0"]
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[Result "0-1"]
+1. e4 c5 2. Nf3 d6 0-1
+
+[Result "1/2-1/2"]
+1. d4 d5 1/2-1/2
+
+[Result "*"]
+1. c4 *
+
+[Result "1-0"]
+1. e4 e5 2. Ke3 1-0
+"#;
+
+    #[test]
+    fn book_builder() {
+        let mut builder = BookBuilder::create(3);
+        assert_eq!(builder.add_pgn(PGN), Ok(1));
+
+        let mut board = Board::create_populated();
+        let key = polyglot_key(&board).unwrap();
+        let e4 = encode_move(&parse_move(&mut board, "e2e4").unwrap());
+        let d4 = encode_move(&parse_move(&mut board, "d2d4").unwrap());
+        let c4 = encode_move(&parse_move(&mut board, "c2c4").unwrap());
+        assert_eq!(builder.get_statistics(key, e4), Some(MoveStatistics { games: 2, wins: 1, draws: 0, losses: 1 }));
+        assert_eq!(builder.get_statistics(key, d4), Some(MoveStatistics { games: 1, wins: 0, draws: 1, losses: 0 }));
+        assert_eq!(builder.get_statistics(key, c4), None);
+
+        // Beyond the ply limit
+        let mut board = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1").unwrap();
+        let nc6 = encode_move(&parse_move(&mut board, "b8c6").unwrap());
+        assert_eq!(builder.get_statistics(polyglot_key(&board).unwrap(), nc6), None);
+
+        // The book can be read back
+        let mut board = Board::create_populated();
+        let book = OpeningBook::from_bytes(&builder.to_bytes(1, 0.0));
+        let moves: Vec<(String, u16)> = book.probe(&mut board).iter().map(|(m, weight)| (m.coordinate_notation(), *weight)).collect();
+        assert_eq!(moves, vec!(("e2e4".to_string(), 2), ("d2d4".to_string(), 1)));
+
+        // Filters
+        let book = OpeningBook::from_bytes(&builder.to_bytes(2, 0.0));
+        assert_eq!(book.probe(&mut board).len(), 1);
+        let book = OpeningBook::from_bytes(&builder.to_bytes(1, 0.6));
+        assert!(book.probe(&mut board).is_empty());
+    }
+
+    #[test]
+    fn book_builder_other_games() {
+        // Games on a 10x8 or 6x6 board have no Polyglot keys and are skipped
+        let pgn = r#"[FEN "r3k4r/10/10/10/10/10/10/R3K4R w - - 0 1"]
+[Result "1-0"]
+1. Rj2 Rj7 1-0
+
+[FEN "rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1"]
+[Result "1/2-1/2"]
+1. a3 a4 1/2-1/2
+"#;
+        let mut builder = BookBuilder::create(10);
+        assert_eq!(builder.add_pgn(pgn), Ok(0));
+        assert!(builder.build(1, 0.0).is_empty());
+    }
+}
@@ -1,4 +1,5 @@
 use super::board::{PieceOnBoard};
+use super::piece_definition::{self, PieceDefinition, PIECE_DEFINITIONS};
 
 #[derive(Clone, Copy, Ord, Eq, PartialOrd, PartialEq)]
 pub struct Square {
@@ -32,12 +33,95 @@ impl Square {
         (self.y * 8 + self.x) as usize
     }
 
-    pub fn algebraic(&self) -> String {
-        assert!(self.is_on_board());
-        format!("{}{}", ('a' as u8 + self.x as u8) as char, self.y)
+    // Inverse of index()
+    pub fn from_index(index: usize) -> Square {
+        assert!(index < 64);
+        Square { x: (index % 8) as i8, y: (index / 8) as i8 }
+    }
+
+    // Coordinate as written in UCI and SAN, e.g. "e4" for Square::at(4, 3). Boards larger than 8x8
+    // go on to file 'j' and rank 10.
+    pub fn coordinate(&self) -> String {
+        assert!(Geometry::LARGEST.contains(*self));
+        format!("{}{}", (b'a' + self.x as u8) as char, self.y + 1)
+    }
+
+    // Inverse of coordinate()
+    pub fn from_coordinate(coordinate: &str) -> Option<Square> {
+        let file = coordinate.chars().next()?;
+        let rank = &coordinate[file.len_utf8()..];
+        if !file.is_ascii_lowercase() || rank.is_empty() || rank.starts_with('0') || !rank.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let square = Square::at(file as i8 - 'a' as i8, rank.parse::<i8>().ok()? - 1);
+        return Some(square).filter(|square| Geometry::LARGEST.contains(*square));
+    }
+}
+
+// Dimensions of the board, 8x8 in standard chess. Squares of a board are indexed rank by rank with
+// index(), Square::index() is the 8x8 index that tables made for standard chess use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Geometry {
+    pub files: i8,
+    pub ranks: i8,
+}
+
+impl Geometry {
+    pub const STANDARD: Geometry = Geometry { files: 8, ranks: 8 };
+    // Capablanca and Gothic chess
+    pub const CAPABLANCA: Geometry = Geometry { files: 10, ranks: 8 };
+    // Los Alamos chess
+    pub const LOS_ALAMOS: Geometry = Geometry { files: 6, ranks: 6 };
+    // Largest supported board, see MAX_SQUARES
+    pub const LARGEST: Geometry = Geometry { files: 10, ranks: 10 };
+
+    pub fn create(files: i8, ranks: i8) -> Option<Geometry> {
+        if files < 1 || ranks < 1 || files > Geometry::LARGEST.files || ranks > Geometry::LARGEST.ranks {
+            return None;
+        }
+        return Some(Geometry { files, ranks });
+    }
+
+    // Negative coordinates turn into large unsigned ones, which saves two comparisons on this hot
+    // path of move generation
+    pub fn contains(&self, square: Square) -> bool {
+        (square.x as u8) < self.files as u8 && (square.y as u8) < self.ranks as u8
+    }
+
+    pub fn square_count(&self) -> usize {
+        (self.files as usize) * (self.ranks as usize)
+    }
+
+    pub fn index(&self, square: Square) -> usize {
+        debug_assert!(self.contains(square));
+        (square.y * self.files + square.x) as usize
+    }
+
+    pub fn back_rank(&self, color: Color) -> i8 {
+        match color {
+            Color::White => 0,
+            Color::Black => self.ranks - 1
+        }
+    }
+
+    pub fn promotion_rank(&self, color: Color) -> i8 {
+        self.back_rank(color.switch())
+    }
+
+    // The rank in front of the promotion rank, the seventh rank in standard chess
+    pub fn seventh_rank(&self, color: Color) -> i8 {
+        self.promotion_rank(color) - color.forward()
+    }
+
+    // The rank the pawns start on
+    pub fn home_rank(&self, color: Color) -> i8 {
+        self.back_rank(color) + color.forward()
     }
 }
 
+// Number of squares of the largest board, an upper bound for Geometry::index()
+pub const MAX_SQUARES: usize = 100;
+
 impl std::fmt::Debug for Square {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}, {})", self.file(), self.rank())
@@ -81,24 +165,6 @@ impl Color {
             Color::Black => -1.0_f32
         }
     }
-    pub fn promotion_rank(&self) -> u8 {
-        match self {
-            Color::White => 7,
-            Color::Black => 0
-        }
-    }
-    pub fn back_rank(&self) -> i8 {
-        match self {
-            Color::White => 0,
-            Color::Black => 7
-        }
-    }
-    pub fn home_rank(&self) -> i8 {
-        match self {
-            Color::White => 1,
-            Color::Black => 6
-        }
-    }
     pub fn forward(&self) -> i8 {
         match self {
             Color::White => 1,
@@ -115,32 +181,35 @@ pub enum PieceKind {
     Rook,
     Queen,
     King,
+    Archbishop,
+    Chancellor,
+    Amazon,
     Dummy,
 }
 
+pub const PIECE_KIND_COUNT: usize = 10;
+
+// All kinds but the Dummy, which only tests use
+pub const PIECE_KINDS: [PieceKind; PIECE_KIND_COUNT - 1] = [PieceKind::Pawn, PieceKind::Knight, PieceKind::Bishop,
+    PieceKind::Rook, PieceKind::Queen, PieceKind::King, PieceKind::Archbishop, PieceKind::Chancellor, PieceKind::Amazon];
+
 impl PieceKind {
+    // The built-in definition, boards may use their own, see Board::definitions
+    pub fn definition(&self) -> &'static PieceDefinition {
+        &PIECE_DEFINITIONS[*self as usize]
+    }
+
     pub fn value(&self) -> f32 {
-        match self {
-            PieceKind::Pawn => 1.0,
-            PieceKind::Knight => 3.0,
-            PieceKind::Bishop => 3.0,
-            PieceKind::Rook => 5.0,
-            PieceKind::Queen => 9.0,
-            PieceKind::King => 200.0,
-            PieceKind::Dummy => 0.0
-        }
+        self.definition().value
     }
 
     pub fn token(&self) -> char {
-        match self {
-            PieceKind::Pawn => 'p',
-            PieceKind::Knight => 'n',
-            PieceKind::Bishop => 'b',
-            PieceKind::Rook => 'r',
-            PieceKind::Queen => 'q',
-            PieceKind::King => 'k',
-            PieceKind::Dummy => 'd'
-        }
+        self.definition().token
+    }
+
+    // Inverse of token(), case insensitive
+    pub fn from_token(token: char) -> Option<PieceKind> {
+        piece_definition::kind_from_token(&PIECE_DEFINITIONS, token)
     }
 
     pub fn colored(self, color: Color) -> Piece {
@@ -170,6 +239,14 @@ impl Piece {
     pub fn value(&self) -> f32 {
         self.kind.value() * self.color.evaluation_sign()
     }
+
+    // FEN letter, upper case for White
+    pub fn token(&self) -> char {
+        match self.color {
+            Color::White => self.kind.token().to_ascii_uppercase(),
+            Color::Black => self.kind.token(),
+        }
+    }
 }
 
 impl std::fmt::Debug for Piece {
@@ -180,24 +257,27 @@ impl std::fmt::Debug for Piece {
             token = token.to_uppercase().to_string().chars().nth(0).unwrap();
         }
 
-        write!(f, "{}", token.to_string())
+        write!(f, "{}", token)
     }
 }
 
-// Castle rights of one side
+// Castle rights of one side and the files of the rooks they refer to. In standard chess the rooks
+// start on files 7 and 0, in Chess960 on any files either side of the king.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ColorCastleRights {
     pub king_side: bool,
     pub queen_side: bool,
+    pub king_side_file: i8,
+    pub queen_side_file: i8,
 }
 
 impl ColorCastleRights {
     pub fn all() -> ColorCastleRights {
-        ColorCastleRights { king_side: true, queen_side: true }
+        ColorCastleRights { king_side: true, queen_side: true, king_side_file: 7, queen_side_file: 0 }
     }
 
     pub fn none() -> ColorCastleRights {
-        ColorCastleRights { king_side: false, queen_side: false }
+        ColorCastleRights { king_side: false, queen_side: false, king_side_file: 7, queen_side_file: 0 }
     }
 
     pub fn test(&self, side: Castle) -> bool {
@@ -206,6 +286,43 @@ impl ColorCastleRights {
             Castle::QueenSide => self.queen_side,
         }
     }
+
+    // File of the rook that castles to `side`
+    pub fn rook_file(&self, side: Castle) -> i8 {
+        match side {
+            Castle::KingSide => self.king_side_file,
+            Castle::QueenSide => self.queen_side_file,
+        }
+    }
+
+    // Grant castling to `side` with the rook on `file`
+    pub fn grant(&mut self, side: Castle, file: i8) {
+        match side {
+            Castle::KingSide => {
+                self.king_side = true;
+                self.king_side_file = file;
+            }
+            Castle::QueenSide => {
+                self.queen_side = true;
+                self.queen_side_file = file;
+            }
+        }
+    }
+
+    // Take away castling to `side`. The rook file falls back to the standard one, so that rights
+    // compare equal regardless of where the rooks started.
+    pub fn revoke(&mut self, side: Castle) {
+        match side {
+            Castle::KingSide => {
+                self.king_side = false;
+                self.king_side_file = 7;
+            }
+            Castle::QueenSide => {
+                self.queen_side = false;
+                self.queen_side_file = 0;
+            }
+        }
+    }
 }
 
 // Castle rights on the Board
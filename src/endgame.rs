@@ -0,0 +1,243 @@
+use super::board::*;
+use super::core::*;
+use super::kpk;
+
+// Bonus for endgames that are won whatever the rest of the position, e.g. KQK. It keeps the search
+// from trading into positions that are merely ahead in material instead.
+pub const KNOWN_WIN: f32 = 50.0;
+
+// Weights that guide the mate in KQK and KRK: drive the losing king to the edge and bring the
+// winning king closer
+const EDGE_WEIGHT: f32 = 0.2;
+const PROXIMITY_WEIGHT: f32 = 0.1;
+
+// Generate the tables of endgame knowledge, so that the first search doesn't pay for them
+pub fn initialize() {
+    kpk::initialize();
+}
+
+// Evaluation of endgames with known outcomes from White's perspective, None if the material
+// isn't recognised. Positions without both kings are never recognised.
+pub fn endgame_evaluation(board: &Board) -> Option<f32> {
+    let pieces = board.pieces();
+    if pieces.len() > 5 {
+        return None;
+    }
+
+    let king_count = |color: Color| pieces.iter()
+        .filter(|piece| piece.piece().kind == PieceKind::King && piece.piece().color == color)
+        .count();
+    if king_count(Color::White) != 1 || king_count(Color::Black) != 1 {
+        return None;
+    }
+
+    for strong_side in [Color::White, Color::Black] {
+        let weak_side = strong_side.switch();
+        let material = |color: Color| pieces.iter()
+            .filter(|piece| piece.piece().kind != PieceKind::King && piece.piece().color == color)
+            .copied()
+            .collect::<Vec<PieceOnBoard>>();
+
+        if !material(weak_side).is_empty() {
+            continue;
+        }
+
+        let strong_material = material(strong_side);
+        let strong_king = board.king_square(strong_side).unwrap();
+        let weak_king = board.king_square(weak_side).unwrap();
+
+        if strong_material.is_empty() {
+            return Some(0.0);
+        }
+
+        if strong_material.len() == 1 {
+            let piece = strong_material[0];
+            return match piece.piece().kind {
+                PieceKind::Queen | PieceKind::Rook => Some(mating_evaluation(strong_side, piece.piece().kind, strong_king, weak_king)),
+                PieceKind::Bishop | PieceKind::Knight => Some(0.0),
+                PieceKind::Pawn => Some(kpk_evaluation(board, strong_side, strong_king, piece.square(), weak_king)),
+                _ => None
+            };
+        }
+
+        if is_wrong_bishop_draw(board, strong_side, &strong_material, weak_king) {
+            return Some(0.0);
+        }
+    }
+
+    return None;
+}
+
+fn distance(a: Square, b: Square) -> i8 {
+    (a.file() - b.file()).abs().max((a.rank() - b.rank()).abs())
+}
+
+// Number of king moves from the centre, 0 to 6
+fn center_distance(square: Square) -> i8 {
+    let file_distance = (3 - square.file()).max(square.file() - 4);
+    let rank_distance = (3 - square.rank()).max(square.rank() - 4);
+    file_distance + rank_distance
+}
+
+// KQK and KRK
+fn mating_evaluation(strong_side: Color, kind: PieceKind, strong_king: Square, weak_king: Square) -> f32 {
+    let evaluation = KNOWN_WIN + kind.value() +
+        EDGE_WEIGHT * center_distance(weak_king) as f32 +
+        PROXIMITY_WEIGHT * (7 - distance(strong_king, weak_king)) as f32;
+    evaluation * strong_side.evaluation_sign()
+}
+
+// KPK, won if the bitbase says so and otherwise drawn. Won positions are better the further the
+// pawn has advanced and the closer the winning king is to the square in front of it.
+fn kpk_evaluation(board: &Board, strong_side: Color, strong_king: Square, pawn: Square, weak_king: Square) -> f32 {
+    if !kpk::probe(strong_side, strong_king, pawn, weak_king, board.side) {
+        return 0.0;
+    }
+
+    let advance = (pawn.rank() - board.geometry.home_rank(strong_side)).abs();
+    let escort = distance(strong_king, pawn.delta(0, strong_side.forward()));
+    let evaluation = KNOWN_WIN + PieceKind::Pawn.value() + EDGE_WEIGHT * advance as f32 +
+        PROXIMITY_WEIGHT * (7 - escort) as f32;
+    evaluation * strong_side.evaluation_sign()
+}
+
+// A bishop and pawns on a single rook file against a bare king are a draw if the bishop can't
+// control the promotion square and the king stands next to it or on it
+fn is_wrong_bishop_draw(board: &Board, strong_side: Color, material: &[PieceOnBoard], weak_king: Square) -> bool {
+    let bishops: Vec<&PieceOnBoard> = material.iter().filter(|piece| piece.piece().kind == PieceKind::Bishop).collect();
+    let pawns: Vec<&PieceOnBoard> = material.iter().filter(|piece| piece.piece().kind == PieceKind::Pawn).collect();
+    if bishops.len() != 1 || pawns.is_empty() || bishops.len() + pawns.len() != material.len() {
+        return false;
+    }
+
+    let file = pawns[0].square().file();
+    if (file != 0 && file != 7) || pawns.iter().any(|pawn| pawn.square().file() != file) {
+        return false;
+    }
+
+    let promotion = Square::at(file, board.geometry.promotion_rank(strong_side));
+    let is_dark = |square: Square| (square.file() + square.rank()) % 2 == 0;
+    if is_dark(bishops[0].square()) == is_dark(promotion) {
+        return false;
+    }
+
+    return distance(weak_king, promotion) <= 1;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::move_generation::*;
+    use crate::search::*;
+    use crate::evaluation::*;
+
+    // Let the search play both sides for at most `max_moves` moves. Returns the number of moves
+    // after which the game ended or `max_moves`.
+    fn self_play(board: &mut Board, max_moves: usize, stop: impl Fn(&Board) -> bool) -> usize {
+        for idx in 0..max_moves {
+            if generate_moves(board).is_empty() || stop(board) {
+                return idx;
+            }
+
+            let mut evaluator = SearchEvaluator::create(4);
+            evaluator.evaluate(board);
+            let m = evaluator.get_best_line().moves[0];
+            board.apply_move(m);
+        }
+        max_moves
+    }
+
+    #[test]
+    fn endgame_recognisers() {
+        let evaluate = |pieces: Vec<PieceOnBoard>| {
+            let mut board = Board::create_empty();
+            board.add_pieces(&pieces);
+            endgame_evaluation(&board)
+        };
+
+        // Minor pieces alone can't mate
+        assert_eq!(evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Bishop.colored(Color::White).at(2, 0),
+            PieceKind::King.colored(Color::Black).at(4, 7))), Some(0.0));
+        assert_eq!(evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::King.colored(Color::Black).at(4, 7),
+            PieceKind::Knight.colored(Color::Black).at(1, 7))), Some(0.0));
+
+        // The losing king is better off in the centre
+        let center = evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::King.colored(Color::Black).at(4, 4))).unwrap();
+        let edge = evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::King.colored(Color::Black).at(4, 7))).unwrap();
+        assert!(center > KNOWN_WIN && edge > center);
+        assert!(evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::King.colored(Color::Black).at(4, 7),
+            PieceKind::Queen.colored(Color::Black).at(3, 7))).unwrap() < -KNOWN_WIN);
+
+        // An h-pawn with the light-squared bishop can't be promoted if the king holds h8
+        assert_eq!(evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Bishop.colored(Color::White).at(3, 0),
+            PieceKind::Pawn.colored(Color::White).at(7, 4),
+            PieceKind::Pawn.colored(Color::White).at(7, 3),
+            PieceKind::King.colored(Color::Black).at(6, 7))), Some(0.0));
+        assert_eq!(evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Bishop.colored(Color::White).at(2, 0),
+            PieceKind::Pawn.colored(Color::White).at(7, 4),
+            PieceKind::King.colored(Color::Black).at(6, 7))), None);
+
+        // More material is left to the regular evaluation
+        assert_eq!(evaluate(vec!(
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::King.colored(Color::Black).at(4, 7),
+            PieceKind::Pawn.colored(Color::Black).at(0, 6))), None);
+    }
+
+    #[test]
+    fn endgame_self_play_mates() {
+        for (kind, max_moves) in [(PieceKind::Queen, 20), (PieceKind::Rook, 40)] {
+            let mut board = Board::create_empty();
+            board.add_pieces(&[
+                PieceKind::King.colored(Color::White).at(1, 1),
+                kind.colored(Color::White).at(2, 0),
+                PieceKind::King.colored(Color::Black).at(4, 4)]);
+
+            // Both sides move, so twice as many plies
+            assert!(self_play(&mut board, 2 * max_moves, |_| false) < 2 * max_moves);
+            assert!(is_check(&board, board.side));
+            assert_eq!(board.side, Color::Black);
+        }
+    }
+
+    #[test]
+    fn endgame_self_play_kpk() {
+        // White wins with the king in front of the pawn and promotes
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(4, 3),
+            PieceKind::Pawn.colored(Color::White).at(4, 2),
+            PieceKind::King.colored(Color::Black).at(4, 5)]);
+        board.side = Color::Black;
+        assert!(static_evaluation(&board) > KNOWN_WIN);
+
+        let promoted = |board: &Board| board.pieces().iter().any(|piece| piece.piece().kind == PieceKind::Queen);
+        assert!(self_play(&mut board, 40, promoted) < 40);
+
+        // With White to move Black holds the opposition, and Black's king stays in front of the pawn
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(4, 3),
+            PieceKind::Pawn.colored(Color::White).at(4, 2),
+            PieceKind::King.colored(Color::Black).at(4, 5)]);
+        assert_eq!(static_evaluation(&board), 0.0);
+        assert_eq!(self_play(&mut board, 20, promoted), 20);
+    }
+}
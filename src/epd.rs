@@ -0,0 +1,342 @@
+use std::time::Duration;
+
+use super::board::*;
+use super::move_::*;
+use super::san::*;
+use super::search::*;
+use super::search_handle::*;
+
+// A position of an EPD file with its operations, e.g. `bm Qxf7+; id "WAC.001";`
+#[derive(Clone, Debug)]
+pub struct EpdPosition {
+    pub board: Board,
+    // Opcodes with their operands, quotes removed, in the order of the file
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdPosition {
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    // The `id` operand, or the FEN if there is none
+    pub fn id(&self) -> String {
+        match self.operation("id").and_then(|operands| operands.first()) {
+            Some(id) => id.clone(),
+            None => self.board.to_fen(),
+        }
+    }
+
+    // The moves of a move list operation such as `bm` or `am`. Fails if one isn't legal.
+    pub fn moves(&self, opcode: &str) -> Result<Vec<Move>, String> {
+        let mut board = self.board.clone();
+        let mut moves = Vec::new();
+        for text in self.operation(opcode).unwrap_or(&[]) {
+            moves.push(parse_san(&mut board, text).ok_or(format!("Illegal move '{}' in {} of {}", text, opcode, self.id()))?);
+        }
+        return Ok(moves);
+    }
+
+    // Points of the moves as given by an STS-style `c0` comment, e.g. "Nf3=10, e4=7"
+    pub fn move_points(&self) -> Result<Vec<(Move, u32)>, String> {
+        let mut board = self.board.clone();
+        let mut points = Vec::new();
+        let text = self.operation("c0").map(|operands| operands.join(" ")).unwrap_or_default();
+        for entry in text.split(',').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+            let (san, value) = entry.split_once('=').ok_or(format!("Invalid c0 entry '{}' of {}", entry, self.id()))?;
+            let m = parse_san(&mut board, san.trim()).ok_or(format!("Illegal move '{}' in c0 of {}", san, self.id()))?;
+            let value = value.trim().parse().map_err(|_| format!("Invalid points '{}' in c0 of {}", value, self.id()))?;
+            points.push((m, value));
+        }
+        return Ok(points);
+    }
+
+    // Centipawn evaluation given by `ce`, from the perspective of the side to move
+    pub fn centipawn_evaluation(&self) -> Option<i32> {
+        self.operation("ce")?.first()?.parse().ok()
+    }
+
+    // Number of moves to mate given by `dm`
+    pub fn direct_mate(&self) -> Option<u32> {
+        self.operation("dm")?.first()?.parse().ok()
+    }
+}
+
+// Split the operations part of an EPD line into opcodes and operands. Semicolons end operations,
+// except inside quoted operands.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut was_quoted = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                was_quoted = true;
+            }
+            _ if quoted => token.push(c),
+            ';' | ' ' | '\t' => {
+                if !token.is_empty() || was_quoted {
+                    tokens.push(std::mem::take(&mut token));
+                    was_quoted = false;
+                }
+                if c == ';' && !tokens.is_empty() {
+                    let opcode = tokens.remove(0);
+                    operations.push((opcode, std::mem::take(&mut tokens)));
+                }
+            }
+            _ => token.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(format!("Unterminated string in '{}'", text));
+    }
+    if !token.is_empty() || was_quoted {
+        tokens.push(token);
+    }
+    if !tokens.is_empty() {
+        let opcode = tokens.remove(0);
+        operations.push((opcode, tokens));
+    }
+
+    return Ok(operations);
+}
+
+// Parse one line of EPD: the first four fields of a FEN followed by operations. The board's move
+// counters are taken from the `hmvc` and `fmvn` operations, if given.
+pub fn parse_epd_line(line: &str) -> Result<EpdPosition, String> {
+    let line = line.trim();
+    let mut fields = Vec::new();
+    let mut rest = line;
+    for _ in 0..4 {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let mut board = Board::from_fen(&format!("{} 0 1", fields.join(" ")))?;
+    let operations = parse_operations(rest.trim())?;
+    let counter = |opcode: &str| -> Option<u32> {
+        operations.iter().find(|(name, _)| name == opcode)?.1.first()?.parse().ok()
+    };
+    if let Some(halfmove_clock) = counter("hmvc") {
+        board.halfmove_clock = halfmove_clock;
+    }
+    if let Some(fullmove_number) = counter("fmvn") {
+        board.fullmove_number = fullmove_number.max(1);
+    }
+    return Ok(EpdPosition { board, operations });
+}
+
+// Parse all positions of an EPD file, skipping empty lines and lines starting with '#'
+pub fn parse_epd(text: &str) -> Result<Vec<EpdPosition>, String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_epd_line)
+        .collect()
+}
+
+// Outcome of searching one position of a suite
+#[derive(Clone, Debug)]
+pub struct EpdResult {
+    pub id: String,
+    pub best_move: Option<Move>,
+    // Whether the move matches `bm`, avoids `am` and mates in time if `dm` asks for it
+    pub solved: bool,
+    // Points of the move and of the best move by `c0`, if the position has them
+    pub points: Option<(u32, u32)>,
+    // Time from which on the search kept a solving move, None if it didn't end with one
+    pub time_to_solution: Option<Duration>,
+    pub node_count: u64,
+    // What was expected, for reporting failures
+    pub expected: String,
+}
+
+// Results of running a whole suite
+#[derive(Clone, Debug)]
+pub struct EpdReport {
+    pub results: Vec<EpdResult>,
+}
+
+impl EpdReport {
+    pub fn solved_count(&self) -> usize {
+        self.results.iter().filter(|result| result.solved).count()
+    }
+
+    pub fn failures(&self) -> Vec<&EpdResult> {
+        self.results.iter().filter(|result| !result.solved).collect()
+    }
+
+    // Points scored and points possible over the positions that have `c0` points
+    pub fn points(&self) -> (u32, u32) {
+        self.results.iter().filter_map(|result| result.points).fold((0, 0), |(sum, max), (points, best)| (sum + points, max + best))
+    }
+}
+
+// Summary as printed by `mess epd`, followed by one line per failure
+impl std::fmt::Display for EpdReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = Vec::new();
+        let solve_time: Duration = self.results.iter().filter_map(|result| result.time_to_solution).sum();
+        lines.push(format!("Solved {} of {} positions, {:.2}s total time to solution",
+                           self.solved_count(), self.results.len(), solve_time.as_secs_f32()));
+
+        let (points, max_points) = self.points();
+        if max_points > 0 {
+            lines.push(format!("Scored {} of {} points", points, max_points));
+        }
+
+        for failure in self.failures() {
+            let played = failure.best_move.map_or("none".to_string(), |m| m.coordinate_notation());
+            lines.push(format!("Failed {}: played {}, expected {}", failure.id, played, failure.expected));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// Whether `m` solves the position, given its search score from the side to move's perspective
+fn is_solution(m: &Move, score: Score, best_moves: &[Move], avoid_moves: &[Move], direct_mate: Option<u32>) -> bool {
+    if !best_moves.is_empty() && !best_moves.contains(m) {
+        return false;
+    }
+    if avoid_moves.contains(m) {
+        return false;
+    }
+    if let Some(moves) = direct_mate {
+        if !mate_distance(score).is_some_and(|distance| distance > 0 && distance as u32 <= moves) {
+            return false;
+        }
+    }
+    return true;
+}
+
+// Search `position` within `limits` and check the move against its operations
+pub fn run_position(position: &EpdPosition, limits: SearchLimits, options: SearchOptions) -> Result<EpdResult, String> {
+    let best_moves = position.moves("bm")?;
+    let avoid_moves = position.moves("am")?;
+    let move_points = position.move_points()?;
+    let direct_mate = position.direct_mate();
+
+    let mut expected = Vec::new();
+    let mut board = position.board.clone();
+    if !best_moves.is_empty() {
+        expected.push(format!("bm {}", best_moves.iter().map(|m| to_san(&mut board, m)).collect::<Vec<String>>().join(" ")));
+    }
+    if !avoid_moves.is_empty() {
+        expected.push(format!("am {}", avoid_moves.iter().map(|m| to_san(&mut board, m)).collect::<Vec<String>>().join(" ")));
+    }
+    if let Some(moves) = direct_mate {
+        expected.push(format!("dm {}", moves));
+    }
+
+    let handle = start_search_with_options(&position.board, limits, options);
+    let mut time_to_solution = None;
+    for info in handle.get_info_receiver().iter().filter(|info| info.multi_pv == 1) {
+        let solving = info.line.moves.first()
+            .is_some_and(|m| is_solution(m, info.evaluation, &best_moves, &avoid_moves, direct_mate));
+        if !solving {
+            time_to_solution = None;
+        } else if time_to_solution.is_none() {
+            time_to_solution = Some(info.duration);
+        }
+    }
+    let result = handle.wait();
+
+    let points = if move_points.is_empty() {
+        None
+    } else {
+        let best = move_points.iter().map(|(_, points)| *points).max().unwrap();
+        let points = result.best_move.and_then(|m| move_points.iter().find(|(other, _)| *other == m)).map_or(0, |(_, points)| *points);
+        Some((points, best))
+    };
+
+    let mut solved = match result.best_move {
+        Some(m) => {
+            // The principal variations are scored from White's perspective
+            let score = result.principal_variations.first().map_or(0.0, |(_, score, _)| *score) * position.board.side.evaluation_sign();
+            is_solution(&m, score, &best_moves, &avoid_moves, direct_mate)
+        }
+        None => false
+    };
+    // STS positions without bm are solved by the move with the most points
+    if let (Some((points, best)), true) = (points, best_moves.is_empty()) {
+        solved &= points == best;
+    }
+
+    return Ok(EpdResult {
+        id: position.id(),
+        best_move: result.best_move,
+        solved,
+        points,
+        time_to_solution: if solved { time_to_solution } else { None },
+        node_count: result.statistics.node_count,
+        expected: expected.join(", "),
+    });
+}
+
+// Run every position of a suite with the same limits
+pub fn run_suite(positions: &[EpdPosition], limits: SearchLimits, options: SearchOptions) -> Result<EpdReport, String> {
+    let mut results = Vec::with_capacity(positions.len());
+    for position in positions.iter() {
+        results.push(run_position(position, limits, options)?);
+    }
+    return Ok(EpdReport { results });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn epd_parse() {
+        let positions = parse_epd(r#"
+# Comments and empty lines are skipped
+2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - am Ng5 Nxe5; c0 "Bb5=10, Bc4=8, d4=6"; id "Quoted ; semicolon";
+"#).unwrap();
+        assert_eq!(positions.len(), 2);
+
+        assert_eq!(positions[0].id(), "WAC.001");
+        assert_eq!(positions[0].moves("bm").unwrap()[0].coordinate_notation(), "g3g6");
+        assert_eq!(positions[0].board.to_fen(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+
+        assert_eq!(positions[1].id(), "Quoted ; semicolon");
+        assert_eq!(positions[1].moves("am").unwrap().len(), 2);
+        let points: Vec<(String, u32)> = positions[1].move_points().unwrap().iter().map(|(m, points)| (m.coordinate_notation(), *points)).collect();
+        assert_eq!(points, vec!(("f1b5".to_string(), 10), ("f1c4".to_string(), 8), ("d2d4".to_string(), 6)));
+
+        let position = parse_epd_line("4k3/8/8/8/8/8/8/4K3 w - - ce -15; dm 3; bm Ke2").unwrap();
+        assert_eq!(position.centipawn_evaluation(), Some(-15));
+        assert_eq!(position.direct_mate(), Some(3));
+        assert!(parse_epd_line("4k3/8/8/8/8/8/8/4K3 w - - bm Ke3").unwrap().moves("bm").is_err());
+        assert_eq!(parse_epd_line("4k3/8/8/8/8/8/8/4K3 w - - hmvc 12; fmvn 40;").unwrap().board.to_fen(), "4k3/8/8/8/8/8/8/4K3 w - - 12 40");
+        assert!(parse_epd_line("4k3/8/8/8/8/8/8/4K3 w - - id \"open").is_err());
+    }
+
+    #[test]
+    fn epd_run() {
+        let positions = parse_epd(r#"
+6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; dm 1; id "mate";
+6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra8#; id "avoid";
+6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - c0 "Ra8=10, Kf1=2"; id "points";
+"#).unwrap();
+
+        let report = run_suite(&positions, SearchLimits::depth(2), SearchOptions::create()).unwrap();
+        assert_eq!(report.solved_count(), 2);
+        assert!(report.results[0].solved);
+        assert!(report.results[0].time_to_solution.is_some());
+        assert!(!report.results[1].solved);
+        assert_eq!(report.points(), (10, 10));
+
+        let text = report.to_string();
+        assert!(text.starts_with("Solved 2 of 3 positions, "));
+        assert!(text.contains("\nScored 10 of 10 points\n"));
+        assert!(text.ends_with("Failed avoid: played a1a8, expected am Ra8#"));
+    }
+}
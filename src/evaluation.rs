@@ -1,29 +1,71 @@
-use super::core::{Color, ColorCastleRights, PieceKind, Piece};
+use super::core::{Color, Geometry};
 use super::{Line, MoveUnmove};
 use super::board::{Board};
+use super::endgame::{endgame_evaluation};
 use super::move_generation::{generate_moves};
+use super::variant::{Variant};
 
 pub fn static_evaluation(board: &Board) -> f32 {
+    if board.variant == Variant::Standard && board.geometry == Geometry::STANDARD {
+        if let Some(evaluation) = endgame_evaluation(board) {
+            return evaluation;
+        }
+    }
+
     let mut evaluation = 0.0;
-    for (piece, _) in board.piece_list.iter() {
-        evaluation += piece.value();
+    for piece_on_board in board.pieces() {
+        let piece = piece_on_board.piece();
+        let definition = &board.definitions()[piece.kind as usize];
+        evaluation += (definition.value + definition.square_value(piece_on_board.square(), piece.color, &board.geometry)) * piece.color.evaluation_sign();
     }
-    return evaluation;
+    return board.variant.evaluation(board, evaluation);
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct DynamicEvaluatorStatistics {
     pub node_count: u64,
     pub duration: std::time::Duration,
+
+    // Number of null-window searches in Principal Variation Search that had to be repeated with
+    // a full window
+    pub pvs_re_searches: u64,
+
+    // Number of root searches that fell outside of the aspiration window and had to be repeated
+    pub aspiration_re_searches: u64,
+
+    // Number of moves that were searched one ply deeper, per reason
+    pub check_extensions: u64,
+    pub single_reply_extensions: u64,
+    pub pawn_push_extensions: u64,
+
+    // Number of positions found in the endgame tablebase
+    pub tablebase_hits: u64,
 }
 
 impl DynamicEvaluatorStatistics {
-    fn create() -> DynamicEvaluatorStatistics {
+    pub fn create() -> DynamicEvaluatorStatistics {
         DynamicEvaluatorStatistics {
             node_count: 0,
             duration: std::time::Duration::new(0, 0),
+            pvs_re_searches: 0,
+            aspiration_re_searches: 0,
+            check_extensions: 0,
+            single_reply_extensions: 0,
+            pawn_push_extensions: 0,
+            tablebase_hits: 0,
         }
     }
+
+    // Add the counts of `other`, e.g. of another search thread
+    pub fn accumulate(&mut self, other: &DynamicEvaluatorStatistics) {
+        self.node_count += other.node_count;
+        self.pvs_re_searches += other.pvs_re_searches;
+        self.aspiration_re_searches += other.aspiration_re_searches;
+        self.check_extensions += other.check_extensions;
+        self.single_reply_extensions += other.single_reply_extensions;
+        self.pawn_push_extensions += other.pawn_push_extensions;
+        self.tablebase_hits += other.tablebase_hits;
+    }
 }
 
 pub trait DynamicEvaluator {
@@ -44,12 +86,12 @@ impl MinimaxEvaluator {
         self.statistics.node_count += 1;
 
         if depth == self.max_depth {
-            return (static_evaluation(&board), Line::empty());
+            return (static_evaluation(board), Line::empty());
         }
 
         let moves = generate_moves(board);
         if moves.is_empty() {
-            return (static_evaluation(&board), Line::empty());
+            return (static_evaluation(board), Line::empty());
         }
 
         let mut best_line = None;
@@ -57,14 +99,16 @@ impl MinimaxEvaluator {
 
         for m in moves.iter() {
             let mut move_unmove = MoveUnmove::apply_move(board, m);
-            let (mut evaluation, line) = self.minimax(board, depth + 1, neg * -1.0);
+            let (mut evaluation, line) = self.minimax(board, depth + 1, -neg);
             evaluation *= neg;
             move_unmove.revert_move(board);
 
             if best_move_evaluation.is_none() || evaluation > best_move_evaluation.unwrap() {
                 best_move_evaluation = Some(evaluation);
                 best_line = Some(line);
-                best_line.as_mut().and_then(|line| {line.push_front(m); return Some(line);} );
+                if let Some(line) = best_line.as_mut() {
+                    line.push_front(m);
+                }
             }
         }
 
@@ -112,12 +156,12 @@ impl AlphaBetaEvaluator {
     fn alpha_beta_min(&mut self, board: &mut Board, alpha: f32, mut beta: f32, depth: u32) -> f32 {
         self.statistics.node_count += 1;
         if depth == self.max_depth {
-            return static_evaluation(&board);
+            return static_evaluation(board);
         }
 
-        let mut moves = generate_moves(board);
+        let moves = generate_moves(board);
         if moves.is_empty() {
-            return static_evaluation(&board);
+            return static_evaluation(board);
         }
 
         let mut best_move_evaluation = None;
@@ -135,7 +179,7 @@ impl AlphaBetaEvaluator {
                 beta = evaluation;
             }
 
-            if best_move_evaluation == None || evaluation < best_move_evaluation.unwrap() {
+            if best_move_evaluation.is_none() || evaluation < best_move_evaluation.unwrap() {
                 best_move_evaluation = Some(evaluation);
             }
         }
@@ -146,12 +190,12 @@ impl AlphaBetaEvaluator {
     fn alpha_beta_max(&mut self, board: &mut Board, mut alpha: f32, beta: f32, depth: u32) -> f32 {
         self.statistics.node_count += 1;
         if depth == self.max_depth {
-            return static_evaluation(&board);
+            return static_evaluation(board);
         }
 
-        let mut moves = generate_moves(board);
+        let moves = generate_moves(board);
         if moves.is_empty() {
-            return static_evaluation(&board);
+            return static_evaluation(board);
         }
 
         let mut best_move_evaluation = None;
@@ -169,7 +213,7 @@ impl AlphaBetaEvaluator {
                 alpha = evaluation;
             }
 
-            if best_move_evaluation == None || evaluation > best_move_evaluation.unwrap() {
+            if best_move_evaluation.is_none() || evaluation > best_move_evaluation.unwrap() {
                 best_move_evaluation = Some(evaluation);
             }
         }
@@ -208,82 +252,83 @@ impl DynamicEvaluator for AlphaBetaEvaluator {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::core::*;
 
     fn dynamic_evaluator_basic<DynamicEvaluatorT: DynamicEvaluator>() {
         // Just a white pawn
         let mut board = Board::create_empty();
         let mut evaluator = DynamicEvaluatorT::create(3);
-        board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(0, 1)));
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 1)]);
         assert_eq!(evaluator.evaluate(&mut board), 1.0);
 
         // Just a black pawn
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::Black).at(0, 6)));
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::Black).at(0, 6)]);
         let mut evaluator = DynamicEvaluatorT::create(3);
         assert_eq!(evaluator.evaluate(&mut board), -1.0);
 
         // A white pawn that can capture a black pawn
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 1),
-            PieceKind::Pawn.colored(Color::Black).at(1, 2)));
+            PieceKind::Pawn.colored(Color::Black).at(1, 2)]);
         let mut evaluator = DynamicEvaluatorT::create(3);
         assert_eq!(evaluator.evaluate(&mut board), 1.0);
 
         // A black pawn that can capture a white pawn
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 2),
-            PieceKind::Pawn.colored(Color::Black).at(1, 3)));
+            PieceKind::Pawn.colored(Color::Black).at(1, 3)]);
         let mut evaluator = DynamicEvaluatorT::create(3);
         assert_eq!(evaluator.evaluate(&mut board), -1.0);
 
         // A white pawn that can capture a black pawn and another black pawn
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 1),
             PieceKind::Pawn.colored(Color::Black).at(1, 2),
-            PieceKind::Pawn.colored(Color::Black).at(3, 2)));
+            PieceKind::Pawn.colored(Color::Black).at(3, 2)]);
         let mut evaluator = DynamicEvaluatorT::create(3);
         assert_eq!(evaluator.evaluate(&mut board), 0.0);
 
         // A white pawn that will be captured by a black pawn after it moves
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 4),
-            PieceKind::Pawn.colored(Color::Black).at(1, 6)));
+            PieceKind::Pawn.colored(Color::Black).at(1, 6)]);
         let mut evaluator = DynamicEvaluatorT::create(3);
         assert_eq!(evaluator.evaluate(&mut board), -1.0);
 
         // A white pawn that will capture a black pawn after the black pawn moves
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 3),
-            PieceKind::Pawn.colored(Color::Black).at(1, 5)));
+            PieceKind::Pawn.colored(Color::Black).at(1, 5)]);
         let mut evaluator = DynamicEvaluatorT::create(3);
         assert_eq!(evaluator.evaluate(&mut board), 1.0);
 
         // A white pawn that will be captured by a black pawn after a couple of moves
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 2),
-            PieceKind::Pawn.colored(Color::Black).at(1, 5), ));
+            PieceKind::Pawn.colored(Color::Black).at(1, 5), ]);
         let mut evaluator = DynamicEvaluatorT::create(10);
         assert_eq!(evaluator.evaluate(&mut board), -1.0);
 
         // ...
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 3),
             PieceKind::Pawn.colored(Color::White).at(1, 5),
-            PieceKind::Pawn.colored(Color::Black).at(0, 6), ));
+            PieceKind::Pawn.colored(Color::Black).at(0, 6), ]);
         let mut evaluator = DynamicEvaluatorT::create(10);
         assert_eq!(evaluator.evaluate(&mut board), -1.0);
     }
@@ -302,14 +347,15 @@ mod test {
     fn static_evaluation_basic() {
         let mut board = Board::create_empty();
 
-        board.add_pieces(vec!(
-            PieceKind::Pawn.colored(Color::White).at(0, 1)));
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 1)]);
         assert_eq!(static_evaluation(&board), 1.0);
 
-        board.add_pieces(vec!(
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 1),
             PieceKind::Pawn.colored(Color::Black).at(0, 2),
-            PieceKind::Pawn.colored(Color::Black).at(0, 3)));
+            PieceKind::Pawn.colored(Color::Black).at(0, 3)]);
         assert_eq!(static_evaluation(&board), -1.0);
     }
 }
\ No newline at end of file
@@ -0,0 +1,383 @@
+use std::io::{BufRead, Write};
+
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::move_generation::*;
+use super::pgn::*;
+use super::render::*;
+use super::san::*;
+use super::search::*;
+use super::search_handle::*;
+use super::uci::parse_move;
+use super::variant::*;
+
+const HELP: &str = "Enter moves in SAN or coordinate notation, e.g. Nf3 or g1f3. Commands:
+  moves        list the legal moves
+  undo         take back your last move and the engine's reply
+  hint         ask the engine for a move
+  flip         turn the board around
+  colors       draw the board in color with chess glyphs
+  think        show or hide the engine's thinking
+  save <file>  save the game as PGN
+  resign       give up the game
+  new          start a new game
+  quit         leave";
+
+// A game of a human against the engine in the terminal. Lines typed by the human are passed to
+// handle(), the board and the engine's replies are written to `output`.
+pub struct InteractiveGame<W: Write> {
+    output: W,
+    start_board: Board,
+    board: Board,
+    moves: Vec<Move>,
+    human: Color,
+    limits: SearchLimits,
+    options: SearchOptions,
+    // Whether the board is drawn from Black's side
+    flipped: bool,
+    // Whether the board is drawn with ANSI colors and glyphs, highlighting the last move
+    colors: bool,
+    // Whether the engine's intermediate results are shown while it searches
+    show_thinking: bool,
+    // Set once the game has ended, by the rules or by resignation
+    outcome: Option<Outcome>,
+}
+
+impl<W: Write> InteractiveGame<W> {
+    pub fn create(output: W, human: Color, limits: SearchLimits, options: SearchOptions) -> InteractiveGame<W> {
+        InteractiveGame {
+            output,
+            start_board: Board::create_populated(),
+            board: Board::create_populated(),
+            moves: Vec::new(),
+            human,
+            limits,
+            options,
+            flipped: human == Color::Black,
+            colors: false,
+            show_thinking: false,
+            outcome: None,
+        }
+    }
+
+    pub fn get_output(&self) -> &W {
+        &self.output
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    fn print(&mut self, text: &str) {
+        // Nothing to be done if the terminal has gone away
+        let _ = writeln!(self.output, "{}", text);
+        let _ = self.output.flush();
+    }
+
+    // Start the game from `board`, letting the engine move first if it plays the side to move
+    pub fn start(&mut self, board: Board) {
+        self.start_board = board.clone();
+        self.board = board;
+        self.moves.clear();
+        self.outcome = None;
+
+        self.print(&format!("You play {:?}. Type 'help' for the commands.", self.human));
+        self.print_board();
+        if self.board.side != self.human {
+            self.engine_move();
+        }
+    }
+
+    // Handle one line of input, returns false once the human wants to leave
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return true;
+        }
+
+        match tokens[0] {
+            "help" | "?" => self.print(HELP),
+            "moves" => {
+                let moves = self.legal_moves();
+                self.print(&format!("Legal moves: {}", moves.join(" ")));
+            }
+            "undo" | "takeback" => self.take_back(),
+            "hint" => {
+                if self.outcome.is_some() {
+                    self.print("The game is over");
+                } else if let Some(m) = self.search() {
+                    let san = to_san(&mut self.board, &m);
+                    self.print(&format!("Hint: {}", san));
+                }
+            }
+            "flip" => {
+                self.flipped = !self.flipped;
+                self.print_board();
+            }
+            "colors" => {
+                self.colors = !self.colors;
+                self.print_board();
+            }
+            "think" => {
+                self.show_thinking = !self.show_thinking;
+                let state = if self.show_thinking { "on" } else { "off" };
+                self.print(&format!("Showing the engine's thinking is {}", state));
+            }
+            "save" => match tokens.get(1) {
+                Some(path) => {
+                    let pgn = self.pgn().to_pgn();
+                    match std::fs::write(path, pgn) {
+                        Ok(()) => self.print(&format!("Saved the game to {}", path)),
+                        Err(error) => self.print(&format!("Can't save the game to {}: {}", path, error)),
+                    }
+                }
+                None => self.print("Usage: save <file>"),
+            },
+            "resign" => {
+                if self.outcome.is_none() {
+                    self.finish(Outcome::Win(self.human.switch()));
+                }
+            }
+            "new" => {
+                let board = self.board.variant.start_board();
+                self.start(board);
+            }
+            "quit" | "exit" => return false,
+            _ => self.human_move(tokens[0]),
+        }
+
+        return true;
+    }
+
+    fn legal_moves(&mut self) -> Vec<String> {
+        let mut moves: Vec<String> = generate_moves(&mut self.board).iter().map(|m| to_san(&mut self.board, m)).collect();
+        moves.sort();
+        return moves;
+    }
+
+    fn human_move(&mut self, text: &str) {
+        if self.outcome.is_some() {
+            self.print("The game is over, type 'new' to start again or 'undo' to take back");
+            return;
+        }
+        if self.board.side != self.human {
+            return;
+        }
+
+        match parse_san(&mut self.board, text).or_else(|| parse_move(&mut self.board, text)) {
+            Some(m) => {
+                self.play(m);
+                if self.outcome.is_none() {
+                    self.engine_move();
+                }
+            }
+            None => {
+                let moves = self.legal_moves();
+                self.print(&format!("Illegal move '{}'. Legal moves: {}", text, moves.join(" ")));
+            }
+        }
+    }
+
+    fn engine_move(&mut self) {
+        if let Some(m) = self.search() {
+            let san = to_san(&mut self.board, &m);
+            self.print(&format!("Engine plays {}", san));
+            self.play(m);
+        }
+    }
+
+    // Play `m`, show the board and announce the end of the game
+    fn play(&mut self, m: Move) {
+        self.board.apply_move(m);
+        self.moves.push(m);
+        self.print_board();
+
+        if let Some(outcome) = game_outcome(&mut self.board) {
+            self.finish(outcome);
+        }
+    }
+
+    fn finish(&mut self, outcome: Outcome) {
+        self.outcome = Some(outcome);
+        let message = match outcome {
+            Outcome::Win(color) if color == self.human => "You win",
+            Outcome::Win(_) => "The engine wins",
+            Outcome::Draw => "Draw",
+        };
+        let result = GameResult::from_outcome(self.outcome).token();
+        self.print(&format!("{} ({}). Type 'save <file>' to save the game, 'new' to play again or 'quit'.", message, result));
+    }
+
+    // Take back moves until it's the human's turn again, at least one of them the human's
+    fn take_back(&mut self) {
+        if !(0..self.moves.len()).any(|idx| self.mover(idx) == self.human) {
+            self.print("Nothing to take back");
+            return;
+        }
+
+        while let Some(m) = self.moves.pop() {
+            self.board.revert_move(m);
+            if self.board.side == self.human {
+                break;
+            }
+        }
+        self.outcome = None;
+        self.print_board();
+    }
+
+    // Color that played the `idx`th move
+    fn mover(&self, idx: usize) -> Color {
+        if idx.is_multiple_of(2) { self.start_board.side } else { self.start_board.side.switch() }
+    }
+
+    // Search the current position with the configured limits, showing the thinking if asked to
+    fn search(&mut self) -> Option<Move> {
+        let mut evaluator = SearchEvaluator::create_with_options(0, self.options);
+        evaluator.set_limits(self.limits);
+        let handle = start_search_with_evaluator(&self.board, evaluator);
+
+        for info in handle.get_info_receiver().iter() {
+            if self.show_thinking && info.multi_pv == 1 {
+                let line = self.thinking_string(&info);
+                self.print(&line);
+            }
+        }
+
+        return handle.wait().best_move;
+    }
+
+    fn thinking_string(&self, info: &SearchInfo) -> String {
+        let score = match mate_distance(info.evaluation) {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("{:+.2}", info.evaluation),
+        };
+
+        let mut board = self.board.clone();
+        let mut pv = Vec::new();
+        for m in info.line.moves.iter() {
+            pv.push(to_san(&mut board, m));
+            board.apply_move(*m);
+        }
+
+        format!("depth {} score {} nodes {} time {:.2}s pv {}", info.depth, score, info.node_count, info.duration.as_secs_f32(),
+                pv.join(" "))
+    }
+
+    pub fn pgn(&self) -> PgnGame {
+        let (white, black) = if self.human == Color::White { ("Human", "mess") } else { ("mess", "Human") };
+        let tags = vec!(
+            ("Event".to_string(), "Casual game".to_string()),
+            ("White".to_string(), white.to_string()),
+            ("Black".to_string(), black.to_string()),
+        );
+        PgnGame::from_moves(tags, &self.start_board, &self.moves, GameResult::from_outcome(self.outcome))
+    }
+
+    fn print_board(&mut self) {
+        let options = RenderOptions {
+            unicode: self.colors,
+            coordinates: true,
+            flipped: self.flipped,
+            colors: self.colors,
+            last_move: self.moves.last().copied(),
+            highlight_check: true,
+        };
+        let diagram = render(&self.board, &options);
+        self.print(&diagram);
+    }
+}
+
+// Play against the engine on `input` and `output` until the human quits or the input ends
+pub fn run<R: BufRead, W: Write>(input: R, output: W, human: Color, limits: SearchLimits) {
+    let mut game = InteractiveGame::create(output, human, limits, SearchOptions::create());
+    game.start(Board::create_populated());
+
+    for line in input.lines() {
+        match line {
+            Ok(line) => {
+                if !game.handle(&line) {
+                    return;
+                }
+            }
+            Err(_) => break
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output_text(game: &InteractiveGame<Vec<u8>>) -> String {
+        String::from_utf8(game.get_output().clone()).unwrap()
+    }
+
+    fn create_game(human: Color) -> InteractiveGame<Vec<u8>> {
+        InteractiveGame::create(Vec::new(), human, SearchLimits::depth(2), SearchOptions::create())
+    }
+
+    #[test]
+    fn interactive_game() {
+        let mut game = create_game(Color::White);
+        game.start(Board::create_populated());
+        assert!(output_text(&game).contains(" 1  R N B Q K B N R\n    a b c d e f g h"));
+
+        // Both notations are accepted, the engine replies right away
+        assert!(game.handle("e4"));
+        assert!(output_text(&game).contains("Engine plays "));
+        assert_eq!(game.get_board().side, Color::White);
+        game.handle("d2d4");
+        assert_eq!(game.get_board().side, Color::White);
+
+        game.handle("Ke3");
+        assert!(output_text(&game).lines().last().unwrap().starts_with("Illegal move 'Ke3'. Legal moves: "));
+
+        // Taking back undoes the engine's reply as well
+        game.handle("undo");
+        game.handle("undo");
+        assert!(game.get_board().semantic_eq(&Board::create_populated()));
+        game.handle("undo");
+        assert!(output_text(&game).ends_with("Nothing to take back\n"));
+
+        game.handle("hint");
+        assert!(output_text(&game).lines().last().unwrap().starts_with("Hint: "));
+
+        game.handle("think");
+        game.handle("Nf3");
+        assert!(output_text(&game).contains("depth 1 score "));
+
+        assert!(!game.handle("quit"));
+    }
+
+    #[test]
+    fn interactive_game_end() {
+        // The engine plays White and mates at once
+        let mut game = create_game(Color::Black);
+        game.start(Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap());
+        assert_eq!(game.get_outcome(), Some(Outcome::Win(Color::White)));
+        assert!(output_text(&game).contains("Engine plays Ra8#"));
+        assert!(output_text(&game).contains("The engine wins (1-0)."));
+
+        // The board is shown from Black's side
+        assert!(output_text(&game).contains(" 8  . k . . . . . R\n    h g f e d c b a"));
+
+        let path = std::env::temp_dir().join(format!("mess_interactive_{}.pgn", std::process::id()));
+        game.handle(&format!("save {}", path.display()));
+        let pgn = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(pgn.contains("[White \"mess\"]"));
+        assert!(pgn.ends_with("1. Ra8# 1-0\n"));
+
+        // Resigning after taking back
+        game.handle("undo");
+        assert!(output_text(&game).ends_with("Nothing to take back\n"));
+        game.handle("new");
+        game.handle("resign");
+        assert_eq!(game.get_outcome(), Some(Outcome::Win(Color::White)));
+    }
+}
@@ -0,0 +1,220 @@
+use super::core::*;
+use std::sync::OnceLock;
+
+// Bitbase for king and pawn versus king: whether the side with the pawn wins, for every placement
+// of the kings and the pawn and either side to move. Generated by retrograde analysis the first
+// time it is needed, see initialize().
+//
+// Positions are normalized so that White has the pawn on one of the files a to d. Squares are
+// indices as in Square::index().
+
+// White king, black king, side to move, pawn file a to d and pawn rank 2 to 7
+const MAX_INDEX: usize = 2 * 24 * 64 * 64;
+
+// Results during generation, as flags so that the results of all successors can be combined
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+static BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+fn index(side: Color, black_king: usize, white_king: usize, pawn: usize) -> usize {
+    white_king | (black_king << 6) | (side.index() << 12) | ((pawn % 8) << 13) | ((6 - pawn / 8) << 15)
+}
+
+struct Position {
+    side: Color,
+    white_king: usize,
+    black_king: usize,
+    pawn: usize,
+}
+
+impl Position {
+    // Inverse of index()
+    fn from_index(index: usize) -> Position {
+        Position {
+            side: if (index >> 12) & 1 == 0 { Color::White } else { Color::Black },
+            white_king: index & 63,
+            black_king: (index >> 6) & 63,
+            pawn: (6 - ((index >> 15) & 7)) * 8 + ((index >> 13) & 3),
+        }
+    }
+
+    // The result that follows from the position alone, UNKNOWN if the successors decide
+    fn initial_result(&self) -> u8 {
+        let white_king_attacks = king_attacks(self.white_king);
+        let black_king_attacks = king_attacks(self.black_king);
+        let pawn_attacks = pawn_attacks(self.pawn);
+
+        if distance(self.white_king, self.black_king) <= 1 || self.white_king == self.pawn || self.black_king == self.pawn ||
+            (self.side == Color::White && pawn_attacks & (1 << self.black_king) != 0) {
+            return INVALID;
+        }
+
+        // The pawn promotes and can't be captured
+        let promotion = self.pawn + 8;
+        if self.side == Color::White && self.pawn / 8 == 6 && self.white_king != promotion &&
+            (distance(self.black_king, promotion) > 1 || distance(self.white_king, promotion) == 1) {
+            return WIN;
+        }
+
+        // Stalemate, or the black king takes the pawn
+        if self.side == Color::Black &&
+            (black_king_attacks & !(white_king_attacks | pawn_attacks) == 0 ||
+                black_king_attacks & !white_king_attacks & (1 << self.pawn) != 0) {
+            return DRAW;
+        }
+
+        return UNKNOWN;
+    }
+
+    // The result that follows from the results of the successors in `results`
+    fn classify(&self, results: &[u8]) -> u8 {
+        let (good, bad) = match self.side {
+            Color::White => (WIN, DRAW),
+            Color::Black => (DRAW, WIN)
+        };
+
+        let mut result = INVALID;
+        let king = match self.side {
+            Color::White => self.white_king,
+            Color::Black => self.black_king
+        };
+
+        let mut targets = king_attacks(king);
+        while targets != 0 {
+            let target = targets.trailing_zeros() as usize;
+            targets &= targets - 1;
+
+            result |= match self.side {
+                Color::White => results[index(Color::Black, self.black_king, target, self.pawn)],
+                Color::Black => results[index(Color::White, target, self.white_king, self.pawn)]
+            };
+        }
+
+        if self.side == Color::White {
+            // A push onto a king is an invalid position and adds nothing
+            if self.pawn / 8 < 6 {
+                result |= results[index(Color::Black, self.black_king, self.white_king, self.pawn + 8)];
+            }
+            if self.pawn / 8 == 1 && self.pawn + 8 != self.white_king && self.pawn + 8 != self.black_king {
+                result |= results[index(Color::Black, self.black_king, self.white_king, self.pawn + 16)];
+            }
+        }
+
+        if result & good != 0 {
+            return good;
+        }
+        if result & UNKNOWN != 0 {
+            return UNKNOWN;
+        }
+        return bad;
+    }
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    let files = (a % 8).abs_diff(b % 8);
+    let ranks = (a / 8).abs_diff(b / 8);
+    files.max(ranks)
+}
+
+fn king_attacks(square: usize) -> u64 {
+    let mut attacks = 0;
+    for target in 0..64 {
+        if distance(square, target) == 1 {
+            attacks |= 1 << target;
+        }
+    }
+    attacks
+}
+
+// Squares attacked by a white pawn
+fn pawn_attacks(square: usize) -> u64 {
+    let mut attacks = 0;
+    if square / 8 < 7 {
+        if !square.is_multiple_of(8) {
+            attacks |= 1 << (square + 7);
+        }
+        if square % 8 < 7 {
+            attacks |= 1 << (square + 9);
+        }
+    }
+    attacks
+}
+
+fn generate() -> Vec<u64> {
+    let positions: Vec<Position> = (0..MAX_INDEX).map(Position::from_index).collect();
+    let mut results: Vec<u8> = positions.iter().map(|position| position.initial_result()).collect();
+
+    // Each pass decides the positions whose successors were decided by the previous passes
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..MAX_INDEX {
+            if results[idx] == UNKNOWN {
+                let result = positions[idx].classify(&results);
+                if result != UNKNOWN {
+                    results[idx] = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut bitbase = vec![0u64; MAX_INDEX / 64];
+    for (idx, result) in results.iter().enumerate() {
+        if *result == WIN {
+            bitbase[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+    return bitbase;
+}
+
+// Generate the bitbase now rather than during the first search that needs it
+pub fn initialize() {
+    BITBASE.get_or_init(generate);
+}
+
+// Whether `strong_side`, which has the pawn, wins with `side` to move. The position must be legal.
+pub fn probe(strong_side: Color, strong_king: Square, pawn: Square, weak_king: Square, side: Color) -> bool {
+    // Normalize to White having the pawn on the files a to d
+    let normalize = |square: Square| {
+        let rank = if strong_side == Color::White { square.rank() } else { 7 - square.rank() };
+        let file = if pawn.file() < 4 { square.file() } else { 7 - square.file() };
+        Square::at(file, rank).index()
+    };
+    let side = if strong_side == Color::White { side } else { side.switch() };
+
+    let idx = index(side, normalize(weak_king), normalize(strong_king), normalize(pawn));
+    let bitbase = BITBASE.get_or_init(generate);
+    return bitbase[idx / 64] & (1 << (idx % 64)) != 0;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kpk_bitbase() {
+        let square = |coordinate| Square::from_coordinate(coordinate).unwrap();
+
+        // The pawn runs away from the king
+        assert!(probe(Color::White, square("a1"), square("e5"), square("h1"), Color::White));
+        assert!(!probe(Color::White, square("a1"), square("e5"), square("e7"), Color::White));
+
+        // Opposition in front of the pawn decides
+        assert!(probe(Color::White, square("e6"), square("e5"), square("e8"), Color::White));
+        assert!(probe(Color::White, square("e6"), square("e5"), square("e8"), Color::Black));
+        assert!(!probe(Color::White, square("e4"), square("e3"), square("e6"), Color::White));
+        assert!(probe(Color::White, square("e4"), square("e3"), square("e6"), Color::Black));
+
+        // Rook pawns are drawn once the defending king reaches the corner
+        assert!(!probe(Color::White, square("b6"), square("a5"), square("a8"), Color::White));
+
+        // Mirrored for Black and the h-file
+        assert!(!probe(Color::Black, square("e5"), square("e6"), square("e3"), Color::Black));
+        assert!(probe(Color::Black, square("e5"), square("e6"), square("e3"), Color::White));
+        assert!(probe(Color::Black, square("g3"), square("h4"), square("a5"), Color::White));
+    }
+}
@@ -1,18 +1,49 @@
+// Functions end in explicit returns throughout
+#![allow(clippy::needless_return)]
+// The recursive search threads its window and node state through plain arguments
+#![allow(clippy::too_many_arguments)]
+
 pub mod board;
+pub mod book_builder;
 pub mod core;
+pub mod endgame;
+pub mod epd;
 pub mod evaluation;
+pub mod interactive;
+pub mod kpk;
+pub mod match_runner;
 pub mod move_;
 pub mod move_generation;
+pub mod opening_book;
+pub mod pgn;
+pub mod piece_definition;
+pub mod polyglot;
+pub mod render;
+pub mod san;
+pub mod search;
+pub mod search_handle;
+pub mod svg;
+pub mod syzygy;
+pub mod tablebase;
+#[cfg(test)]
 mod test_util;
+pub mod time_manager;
+pub mod tournament;
+pub mod transposition_table;
+pub mod uci;
+pub mod variant;
+pub mod xboard;
 
 use board::*;
 use crate::core::*;
 use evaluation::*;
 use move_::*;
 use move_generation::*;
+use opening_book::*;
 
 use num_traits::Float;
 
+#[derive(Clone, Debug)]
 pub struct Line {
     pub moves: Vec<Move>
 }
@@ -23,11 +54,7 @@ impl Line {
     }
 
     pub fn from_moves(moves: Vec<Move>) -> Line {
-        Line{moves: moves}
-    }
-
-    pub fn to_string(&self) -> String {
-        self.moves.iter().map(|m| m.long_algebraic()).collect::<Vec<String>>().join(" ")
+        Line{moves}
     }
 
     pub fn push_front(&mut self, move_: &Move) {
@@ -35,15 +62,19 @@ impl Line {
     }
 }
 
+impl std::fmt::Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.moves.iter().map(|m| m.long_algebraic()).collect::<Vec<String>>().join(" "))
+    }
+}
+
 pub struct MoveUnmove {
-    board_before: Board,
     move_: Move,
 }
 
 impl MoveUnmove {
     pub fn apply_move(board: &mut Board, move_: &Move) -> MoveUnmove {
         let move_unmove = MoveUnmove {
-            board_before: board.clone(),
             move_: *move_,
         };
         board.apply_move(*move_);
@@ -52,19 +83,23 @@ impl MoveUnmove {
 
     pub fn revert_move(&mut self, board: &mut Board) {
         board.revert_move(self.move_);
-
-        // if !board.semantic_eq(&self.board_before) {
-        //     panic!("Board mismatch after {:?}\n{:?}\nvs\n{:?}", self.move_, self.board_before, board);
-        // }
     }
 }
 
-pub fn best_move(board: &mut Board, evaluator: &mut dyn DynamicEvaluator) -> Option<Move> {
+// Pick a move from `book` if the position is in it, otherwise search for the best move
+pub fn best_move(board: &mut Board, evaluator: &mut dyn DynamicEvaluator, book: Option<&mut OpeningBook>) -> Option<Move> {
     if board.is_game_over() {
         return None;
     }
 
-    let mut moves = generate_moves(board);
+    if let Some(book) = book {
+        if let Some(book_move) = book.pick(board, BookSelection::WeightedRandom) {
+            println!("Chose book move {:?}", book_move);
+            return Some(book_move);
+        }
+    }
+
+    let moves = generate_moves(board);
     println!("{} moves to choose from", moves.len());
 
     let mut best_move = Option::None;
@@ -90,7 +125,7 @@ pub fn best_move(board: &mut Board, evaluator: &mut dyn DynamicEvaluator) -> Opt
     let nodes_per_second = evaluator.get_statistics().node_count as f32 / evaluator.get_statistics().duration.as_secs_f32();
 
     println!("Chose move {:?} with an evaluation of {}, evaluated {} nodes at {} nodes/s", best_move, best_move_evaluation * neg, evaluator.get_statistics().node_count, nodes_per_second);
-    println!("Line: {}", evaluator.get_best_line().to_string());
+    println!("Line: {}", evaluator.get_best_line());
 
     return best_move;
 }
@@ -101,11 +136,11 @@ pub fn play(board: &mut Board) {
     loop {
         let mut evaluator = MinimaxEvaluator::create(max_depth);
         let d = evaluator.evaluate(board);
-        println!("{:?}'s turn, static evaluation is {}, dynamic evaluation is {}", board.side, static_evaluation(&board), d);
+        println!("{:?}'s turn, static evaluation is {}, dynamic evaluation is {}", board.side, static_evaluation(board), d);
         board.print();
 
         let mut evaluator = AlphaBetaEvaluator::create(max_depth);
-        let best_move = best_move(board, &mut evaluator);
+        let best_move = best_move(board, &mut evaluator, None);
 
         match best_move {
             Some(best_move) => {
@@ -129,17 +164,30 @@ mod test {
     #[test]
     fn line_to_string() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 1),
             PieceKind::Pawn.colored(Color::White).at(0, 6)
-        ));
+        ]);
+
+        let moves = vec!(
+            TestMove::from_to(&board, Square::at(0, 1), Square::at(0, 3)),
+            TestMove::from_to(&board, Square::at(0, 6), Square::at(0, 5)));
+
+        let line = Line::from_moves(moves);
+
+        assert_eq!(line.to_string(), "a2-a4 a7-a6");
 
-        let mut moves = Vec::new();
-        moves.push(TestMove::from_to(&board, Square::at(0, 1), Square::at(0, 3)));
-        moves.push(TestMove::from_to(&board, Square::at(0, 6), Square::at(0, 5)));
+        // Files beyond h on a 10x8 board
+        let mut board = Board::create_empty_with_geometry(Geometry::CAPABLANCA);
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(9, 1),
+            PieceKind::Rook.colored(Color::Black).at(8, 7)
+        ]);
 
-        let mut line = Line::from_moves(moves);
+        let moves = vec!(
+            TestMove::from_to(&board, Square::at(9, 1), Square::at(9, 3)),
+            TestMove::from_to(&board, Square::at(8, 7), Square::at(8, 0)));
 
-        assert_eq!(line.to_string(), "a1-a3 a6-a5");
+        assert_eq!(Line::from_moves(moves).to_string(), "j2-j4 i8-i1");
     }
 }
\ No newline at end of file
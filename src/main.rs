@@ -1,6 +1,111 @@
 use mess::*;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    endgame::initialize();
+
+    // Talk UCI to a GUI when started as `mess uci`
+    if args.get(1).map(|arg| arg.as_str()) == Some("uci") {
+        let stdin = std::io::stdin();
+        uci::run(stdin.lock(), std::io::stdout());
+        return;
+    }
+
+    // Talk XBoard's protocol when started as `mess xboard`
+    if args.get(1).map(|arg| arg.as_str()) == Some("xboard") {
+        let stdin = std::io::stdin();
+        xboard::run(stdin.lock(), std::io::stdout());
+        return;
+    }
+
+    // Play against the engine with `mess play [white|black] [milliseconds per move]`
+    if args.get(1).map(|arg| arg.as_str()) == Some("play") {
+        let human = if args.get(2).map(|arg| arg.as_str()) == Some("black") { core::Color::Black } else { core::Color::White };
+        let move_time = args.get(3).and_then(|arg| arg.parse().ok()).unwrap_or(1000);
+        let stdin = std::io::stdin();
+        interactive::run(stdin.lock(), std::io::stdout(), human, search::SearchLimits::move_time(std::time::Duration::from_millis(move_time)));
+        return;
+    }
+
+    // Run a test suite with `mess epd <suite.epd> [milliseconds per position]`
+    if args.get(1).map(|arg| arg.as_str()) == Some("epd") && args.len() >= 3 {
+        let move_time = args.get(3).and_then(|arg| arg.parse().ok()).unwrap_or(1000);
+        let text = std::fs::read_to_string(&args[2]).expect("Can't read EPD file");
+        let positions = epd::parse_epd(&text).expect("Invalid EPD file");
+        let limits = search::SearchLimits::move_time(std::time::Duration::from_millis(move_time));
+        let report = epd::run_suite(&positions, limits, search::SearchOptions::create()).expect("Invalid EPD file");
+        println!("{}", report);
+        return;
+    }
+
+    // Play two UCI engines against each other with
+    // `mess match <openings.epd|pgn> <engine command> <engine command> [games] [milliseconds per move]`
+    if args.get(1).map(|arg| arg.as_str()) == Some("match") && args.len() >= 5 {
+        let games = args.get(5).and_then(|arg| arg.parse().ok()).unwrap_or(100);
+        let move_time = args.get(6).and_then(|arg| arg.parse().ok()).unwrap_or(100);
+        let text = std::fs::read_to_string(&args[2]).expect("Can't read openings");
+        let openings = if args[2].ends_with(".pgn") { match_runner::openings_from_pgn(&text, 16) } else { match_runner::openings_from_epd(&text) };
+        let first = match_runner::UciPlayer::start(&args[3]).expect("Can't start the first engine");
+        let second = match_runner::UciPlayer::start(&args[4]).expect("Can't start the second engine");
+
+        let limits = match_runner::MatchLimits::MoveTime(std::time::Duration::from_millis(move_time));
+        let mut runner = match_runner::MatchRunner::create(Box::new(first), Box::new(second), openings.expect("Invalid openings"), limits);
+        runner.set_sprt(match_runner::Sprt::create(0.0, 5.0));
+        let report = runner.run(games, &mut std::io::stdout()).expect("Match failed");
+        println!("{}", match_runner::status_line(&report.statistics, None));
+        return;
+    }
+
+    // Play a round robin or gauntlet between UCI engines with
+    // `mess tournament|gauntlet <openings.epd|pgn> <games.pgn> <rounds> <milliseconds per move> <concurrent games> <engine command>...`,
+    // resuming from the games already in games.pgn
+    let kind = match args.get(1).map(|arg| arg.as_str()) {
+        Some("tournament") => Some(tournament::TournamentKind::RoundRobin),
+        Some("gauntlet") => Some(tournament::TournamentKind::Gauntlet),
+        _ => None,
+    };
+    if let (Some(kind), true) = (kind, args.len() >= 9) {
+        let rounds = args[4].parse().expect("Invalid number of rounds");
+        let move_time = args[5].parse().expect("Invalid time per move");
+        let concurrency = args[6].parse().expect("Invalid number of concurrent games");
+        let text = std::fs::read_to_string(&args[2]).expect("Can't read openings");
+        let openings = if args[2].ends_with(".pgn") { match_runner::openings_from_pgn(&text, 16) } else { match_runner::openings_from_epd(&text) };
+        let participants = args[7..].iter().map(|command| {
+            let program = command.clone();
+            tournament::Participant::create(command, Box::new(move || {
+                Ok(Box::new(match_runner::UciPlayer::start(&program)?) as Box<dyn match_runner::MatchPlayer>)
+            }))
+        }).collect();
+
+        let limits = match_runner::MatchLimits::MoveTime(std::time::Duration::from_millis(move_time));
+        let mut tournament = tournament::Tournament::create(participants, kind, openings.expect("Invalid openings"), limits);
+        tournament.set_rounds(rounds);
+        tournament.set_concurrency(concurrency);
+        let completed = match std::fs::read_to_string(&args[3]) {
+            Ok(pgn) => tournament.completed_games(&pgn).expect("Can't resume from the PGN"),
+            Err(_) => Vec::new(),
+        };
+        let mut pgn = std::fs::OpenOptions::new().create(true).append(true).open(&args[3]).expect("Can't write PGN");
+        let report = tournament.run(&completed, &mut pgn, &mut std::io::stdout()).expect("Tournament failed");
+        println!("{}", report.crosstable());
+        return;
+    }
+
+    // Build an opening book with `mess book <games.pgn> <book.bin> [max ply] [min games] [min score]`
+    if args.get(1).map(|arg| arg.as_str()) == Some("book") && args.len() >= 4 {
+        let max_ply = args.get(4).and_then(|arg| arg.parse().ok()).unwrap_or(20);
+        let min_games = args.get(5).and_then(|arg| arg.parse().ok()).unwrap_or(1);
+        let min_score = args.get(6).and_then(|arg| arg.parse().ok()).unwrap_or(0.0);
+
+        let pgn = std::fs::read_to_string(&args[2]).expect("Can't read PGN file");
+        let mut builder = book_builder::BookBuilder::create(max_ply);
+        let failed = builder.add_pgn(&pgn).expect("Invalid PGN file");
+        builder.write(&args[3], min_games, min_score).expect("Can't write book");
+        println!("Wrote {}, {} games couldn't be replayed", args[3], failed);
+        return;
+    }
+
+    // Otherwise play against itself
     let mut board = board::Board::create_king_rooks();
     play(&mut board);
 }
@@ -0,0 +1,780 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::board::*;
+use super::core::*;
+use super::epd::*;
+use super::evaluation::*;
+use super::move_::*;
+use super::pgn::*;
+use super::search::*;
+use super::time_manager::*;
+use super::uci::find_move;
+use super::variant::*;
+
+// How long the players may think about their moves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchLimits {
+    Depth(u32),
+    MoveTime(Duration),
+    // Each side's clock starts with `base` and gains `increment` per move. Running out loses.
+    Clock { base: Duration, increment: Duration },
+}
+
+// An engine taking part in a match, either searching in this process or talking UCI in another one
+pub trait MatchPlayer {
+    fn name(&self) -> String;
+
+    // Called before each game
+    fn new_game(&mut self) -> Result<(), String>;
+
+    // The move to play on `board`, which is `start` after `moves`, and its score in pawns from the
+    // perspective of the side to move if the player reports one. `clocks` are the remaining times
+    // of White and Black if the match is played with a clock.
+    fn choose_move(&mut self, start: &Board, moves: &[Move], board: &Board, limits: &MatchLimits,
+                   clocks: [Duration; 2]) -> Result<(Move, Option<Score>), String>;
+}
+
+// What an in-process player searches with
+pub enum InProcessEngine {
+    // The regular search with the given options, obeying the match's limits
+    Search(SearchOptions),
+    // Any evaluator, searching to its own depth regardless of the match's limits
+    Evaluator(Box<dyn DynamicEvaluator>),
+}
+
+pub struct InProcessPlayer {
+    name: String,
+    engine: InProcessEngine,
+}
+
+impl InProcessPlayer {
+    pub fn create(name: &str, engine: InProcessEngine) -> InProcessPlayer {
+        InProcessPlayer { name: name.to_string(), engine }
+    }
+}
+
+impl MatchPlayer for InProcessPlayer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn choose_move(&mut self, _start: &Board, _moves: &[Move], board: &Board, limits: &MatchLimits,
+                   clocks: [Duration; 2]) -> Result<(Move, Option<Score>), String> {
+        let mut board = board.clone();
+        let side = board.side;
+
+        let (evaluation, line) = match &mut self.engine {
+            InProcessEngine::Search(options) => {
+                let mut evaluator = SearchEvaluator::create_with_options(0, *options);
+                evaluator.set_limits(match limits {
+                    MatchLimits::Depth(depth) => SearchLimits::depth(*depth),
+                    MatchLimits::MoveTime(move_time) => SearchLimits::move_time(*move_time),
+                    MatchLimits::Clock { increment, .. } =>
+                        SearchLimits::move_time(TimeControl::create(clocks[side.index()], *increment, None).allocate()),
+                });
+                let evaluation = evaluator.evaluate(&mut board);
+                (evaluation, evaluator.get_best_line().clone())
+            }
+            InProcessEngine::Evaluator(evaluator) => {
+                let evaluation = evaluator.evaluate(&mut board);
+                (evaluation, evaluator.get_best_line().clone())
+            }
+        };
+
+        let m = line.moves.first().copied().ok_or(format!("{} found no move", self.name))?;
+        // Evaluators report from White's perspective
+        return Ok((m, Some(evaluation * side.evaluation_sign())));
+    }
+}
+
+// How long an engine may take to answer `uci` and `isready`
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// How far an engine may overshoot a fixed time per move before it loses on time
+const MOVE_TIME_MARGIN: Duration = Duration::from_secs(1);
+
+// An engine running as a subprocess, spoken to with UCI
+pub struct UciPlayer {
+    name: String,
+    child: Child,
+    input: ChildStdin,
+    // Lines the engine writes, read on a thread of their own so that waiting for them can time out
+    output: Receiver<String>,
+    // Options last sent for the opening's rule set
+    variant: Variant,
+    chess960: bool,
+}
+
+impl UciPlayer {
+    // Start `command`, e.g. "./engine --option", and wait for it to be ready
+    pub fn start(command: &str) -> Result<UciPlayer, String> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or("Empty engine command")?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("Can't start '{}': {}", command, error))?;
+
+        let input = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) if sender.send(line.trim().to_string()).is_ok() => {}
+                    _ => return
+                }
+            }
+        });
+        let mut player = UciPlayer { name: command.to_string(), child, input, output, variant: Variant::Standard, chess960: false };
+
+        player.send("uci")?;
+        for line in player.read_until("uciok", Some(HANDSHAKE_TIMEOUT))? {
+            if let Some(name) = line.strip_prefix("id name ") {
+                player.name = name.to_string();
+            }
+        }
+        player.send("isready")?;
+        player.read_until("readyok", Some(HANDSHAKE_TIMEOUT))?;
+
+        return Ok(player);
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.input, "{}", line).and_then(|_| self.input.flush()).map_err(|error| format!("{} has gone away: {}", self.name, error))
+    }
+
+    // Read lines up to and including the first one starting with `prefix`. Fails if the engine
+    // exits or, given a `timeout`, doesn't get there in time.
+    fn read_until(&mut self, prefix: &str, timeout: Option<Duration>) -> Result<Vec<String>, String> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut lines = Vec::new();
+        loop {
+            let line = match deadline {
+                Some(deadline) => self.output.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self.output.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = match line {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Err(format!("{} sent no '{}' in time", self.name, prefix)),
+                Err(RecvTimeoutError::Disconnected) => return Err(format!("{} has gone away", self.name)),
+            };
+            let done = line.starts_with(prefix);
+            lines.push(line);
+            if done {
+                return Ok(lines);
+            }
+        }
+    }
+
+    // Tell the engine the rule set of `start` if it differs from the last one
+    fn set_rules(&mut self, start: &Board) -> Result<(), String> {
+        if start.variant != self.variant {
+            self.variant = start.variant;
+            self.send(&format!("setoption name UCI_Variant value {}", self.variant.name()))?;
+        }
+        if start.is_chess960() != self.chess960 {
+            self.chess960 = start.is_chess960();
+            self.send(&format!("setoption name UCI_Chess960 value {}", self.chess960))?;
+        }
+        return Ok(());
+    }
+}
+
+// Score of a UCI `info` line in pawns, mates as MATE_SCORE minus the plies to mate
+pub fn parse_info_score(line: &str) -> Option<Score> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let idx = tokens.iter().position(|token| *token == "score")?;
+    let value: i32 = tokens.get(idx + 2)?.parse().ok()?;
+    match *tokens.get(idx + 1)? {
+        "cp" => Some(value as f32 / 100.0),
+        "mate" if value > 0 => Some(MATE_SCORE - (value * 2 - 1) as f32),
+        "mate" => Some(-MATE_SCORE - (value * 2) as f32),
+        _ => None
+    }
+}
+
+impl MatchPlayer for UciPlayer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        // Also skips what is left over from a move the engine ran out of time on
+        self.read_until("readyok", Some(HANDSHAKE_TIMEOUT))?;
+        return Ok(());
+    }
+
+    fn choose_move(&mut self, start: &Board, moves: &[Move], board: &Board, limits: &MatchLimits,
+                   clocks: [Duration; 2]) -> Result<(Move, Option<Score>), String> {
+        self.set_rules(start)?;
+        let position = if start.semantic_eq(&Board::create_populated()) { "startpos".to_string() } else { format!("fen {}", start.to_fen()) };
+        let moves = moves.iter().map(|m| m.uci_notation(self.chess960)).collect::<Vec<String>>().join(" ");
+        self.send(&format!("position {} moves {}", position, moves))?;
+
+        let (go, timeout) = match limits {
+            MatchLimits::Depth(depth) => (format!("go depth {}", depth), None),
+            MatchLimits::MoveTime(move_time) => (format!("go movetime {}", move_time.as_millis()), Some(*move_time + MOVE_TIME_MARGIN)),
+            MatchLimits::Clock { increment, .. } => (format!("go wtime {} btime {} winc {} binc {}",
+                clocks[0].as_millis(), clocks[1].as_millis(), increment.as_millis(), increment.as_millis()), Some(clocks[board.side.index()])),
+        };
+        self.send(&go)?;
+
+        let lines = match self.read_until("bestmove", timeout) {
+            Ok(lines) => lines,
+            Err(error) => {
+                let _ = self.send("stop");
+                return Err(error);
+            }
+        };
+        let score = lines.iter().rev().filter(|line| line.starts_with("info")).find_map(|line| parse_info_score(line));
+        let text = lines.last().unwrap().split_whitespace().nth(1).unwrap_or("");
+        let m = find_move(&mut board.clone(), text, self.chess960).ok_or(format!("{} played the illegal move '{}'", self.name, text))?;
+
+        return Ok((m, score));
+    }
+}
+
+impl Drop for UciPlayer {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+// Start of a game: a position and the moves played from it
+#[derive(Clone, Debug)]
+pub struct Opening {
+    pub board: Board,
+    pub moves: Vec<Move>,
+}
+
+// Openings from the positions of an EPD file
+pub fn openings_from_epd(text: &str) -> Result<Vec<Opening>, String> {
+    Ok(parse_epd(text)?.into_iter().map(|position| Opening { board: position.board, moves: Vec::new() }).collect())
+}
+
+// Openings from the first `max_plies` moves of the games of a PGN file
+pub fn openings_from_pgn(text: &str, max_plies: usize) -> Result<Vec<Opening>, String> {
+    let mut openings = Vec::new();
+    for game in parse_pgn(text)? {
+        let mut moves = game.replay()?;
+        moves.truncate(max_plies);
+        openings.push(Opening { board: game.start_board()?, moves });
+    }
+    return Ok(openings);
+}
+
+// When games are decided without playing them out. Scores are in pawns from the mover's
+// perspective.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Adjudication {
+    // A side whose own score is at most -resign_score for resign_moves moves in a row loses, 0
+    // moves disables this
+    pub resign_score: Score,
+    pub resign_moves: u32,
+    // The game is drawn once both sides' scores stay within draw_score for draw_plies plies in a
+    // row, starting from ply draw_after_ply. 0 plies disables this.
+    pub draw_score: Score,
+    pub draw_plies: u32,
+    pub draw_after_ply: u32,
+    // Games this long are drawn
+    pub max_plies: u32,
+}
+
+impl Adjudication {
+    pub fn create() -> Adjudication {
+        Adjudication { resign_score: 10.0, resign_moves: 3, draw_score: 0.05, draw_plies: 8, draw_after_ply: 80, max_plies: 400 }
+    }
+}
+
+// A finished game of a match
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    pub opening: Opening,
+    // Moves played after the opening
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+    pub reason: String,
+}
+
+impl GameRecord {
+    pub fn to_pgn(&self) -> PgnGame {
+        let tags = vec!(
+            ("White".to_string(), self.white.clone()),
+            ("Black".to_string(), self.black.clone()),
+            ("Termination".to_string(), self.reason.clone()),
+        );
+        let moves: Vec<Move> = self.opening.moves.iter().chain(self.moves.iter()).copied().collect();
+        PgnGame::from_moves(tags, &self.opening.board, &moves, self.result)
+    }
+}
+
+// Wins, draws and losses of the first player of a match
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchStatistics {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+// Expected score of a player `elo` points stronger than its opponent
+fn elo_to_score(elo: f32) -> f32 {
+    1.0 / (1.0 + 10.0_f32.powf(-elo / 400.0))
+}
+
+fn score_to_elo(score: f32) -> f32 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl MatchStatistics {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Points per game, 1 for a win and 0.5 for a draw
+    pub fn score(&self) -> f32 {
+        (self.wins as f32 + self.draws as f32 / 2.0) / self.games() as f32
+    }
+
+    // Variance of a single game's points around score()
+    fn variance(&self) -> f32 {
+        let score = self.score();
+        let games = self.games() as f32;
+        (self.wins as f32 * (1.0 - score).powi(2) + self.draws as f32 * (0.5 - score).powi(2) + self.losses as f32 * score.powi(2)) / games
+    }
+
+    // Elo difference to the opponent and the half-width of its 95% confidence interval. None until
+    // both a point has been won and one has been lost.
+    pub fn elo(&self) -> Option<(f32, f32)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+
+        let margin = 1.96 * (self.variance() / self.games() as f32).sqrt();
+        let low = score_to_elo((score - margin).max(1e-6));
+        let high = score_to_elo((score + margin).min(1.0 - 1e-6));
+        return Some((score_to_elo(score), (high - low) / 2.0));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SprtDecision {
+    // The first player is at most elo0 stronger
+    AcceptH0,
+    // The first player is at least elo1 stronger
+    AcceptH1,
+}
+
+// Sequential probability ratio test of the hypotheses that the first player is elo0 (H0) or elo1
+// (H1) Elo stronger, with false positive rate `alpha` and false negative rate `beta`. The
+// log-likelihood ratio uses the normal approximation of the generalized SPRT.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f32,
+    pub elo1: f32,
+    pub alpha: f32,
+    pub beta: f32,
+}
+
+impl Sprt {
+    pub fn create(elo0: f32, elo1: f32) -> Sprt {
+        Sprt { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    // The LLR accepts H0 below the first and H1 above the second bound
+    pub fn bounds(&self) -> (f32, f32) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    pub fn llr(&self, statistics: &MatchStatistics) -> f32 {
+        let variance = if statistics.games() > 0 { statistics.variance() } else { 0.0 };
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let score0 = elo_to_score(self.elo0);
+        let score1 = elo_to_score(self.elo1);
+        let games = statistics.games() as f32;
+        return games * (score1 - score0) * (2.0 * statistics.score() - score0 - score1) / (2.0 * variance);
+    }
+
+    pub fn decision(&self, statistics: &MatchStatistics) -> Option<SprtDecision> {
+        let llr = self.llr(statistics);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            Some(SprtDecision::AcceptH0)
+        } else if llr >= upper {
+            Some(SprtDecision::AcceptH1)
+        } else {
+            None
+        }
+    }
+}
+
+// Result of a match, from the first player's perspective
+#[derive(Clone, Debug)]
+pub struct MatchReport {
+    pub statistics: MatchStatistics,
+    pub games: Vec<GameRecord>,
+    // Set if the SPRT stopped the match early
+    pub decision: Option<SprtDecision>,
+}
+
+// One line of the match log: the running W/D/L, Elo and LLR
+pub fn status_line(statistics: &MatchStatistics, sprt: Option<&Sprt>) -> String {
+    let mut line = format!("W/D/L {}/{}/{} ({} games)", statistics.wins, statistics.draws, statistics.losses, statistics.games());
+    if let Some((elo, error)) = statistics.elo() {
+        line.push_str(&format!(", Elo {:.1} +/- {:.1}", elo, error));
+    }
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        line.push_str(&format!(", LLR {:.2} [{:.2}, {:.2}]", sprt.llr(statistics), lower, upper));
+    }
+    return line;
+}
+
+// Plays games between two players from a list of openings, each opening twice with the colors
+// swapped
+pub struct MatchRunner {
+    players: [Box<dyn MatchPlayer>; 2],
+    openings: Vec<Opening>,
+    limits: MatchLimits,
+    adjudication: Adjudication,
+    sprt: Option<Sprt>,
+}
+
+impl MatchRunner {
+    pub fn create(first: Box<dyn MatchPlayer>, second: Box<dyn MatchPlayer>, openings: Vec<Opening>, limits: MatchLimits) -> MatchRunner {
+        MatchRunner { players: [first, second], openings, limits, adjudication: Adjudication::create(), sprt: None }
+    }
+
+    pub fn set_adjudication(&mut self, adjudication: Adjudication) {
+        self.adjudication = adjudication;
+    }
+
+    // Stop the match as soon as `sprt` accepts one of its hypotheses
+    pub fn set_sprt(&mut self, sprt: Sprt) {
+        self.sprt = Some(sprt);
+    }
+
+    // Play up to `games` games, writing a line per game to `log`
+    pub fn run(&mut self, games: usize, log: &mut dyn Write) -> Result<MatchReport, String> {
+        if self.openings.is_empty() {
+            self.openings.push(Opening { board: Board::create_populated(), moves: Vec::new() });
+        }
+
+        let mut statistics = MatchStatistics::default();
+        let mut records = Vec::new();
+        let mut decision = None;
+
+        for game in 0..games {
+            let opening = self.openings[(game / 2) % self.openings.len()].clone();
+            let first_plays_white = game % 2 == 0;
+            let record = self.play_game(&opening, first_plays_white)?;
+
+            let first_color = if first_plays_white { Color::White } else { Color::Black };
+            match (record.result, first_color) {
+                (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => statistics.wins += 1,
+                (GameResult::WhiteWins, Color::Black) | (GameResult::BlackWins, Color::White) => statistics.losses += 1,
+                _ => statistics.draws += 1,
+            }
+
+            let _ = writeln!(log, "Game {}: {} - {} {} ({}) | {}", game + 1, record.white, record.black, record.result.token(), record.reason,
+                             status_line(&statistics, self.sprt.as_ref()));
+            records.push(record);
+
+            decision = self.sprt.and_then(|sprt| sprt.decision(&statistics));
+            if let Some(decision) = decision {
+                let _ = writeln!(log, "SPRT: {:?}", decision);
+                break;
+            }
+        }
+
+        return Ok(MatchReport { statistics, games: records, decision });
+    }
+
+    fn play_game(&mut self, opening: &Opening, first_plays_white: bool) -> Result<GameRecord, String> {
+        let [first, second] = &mut self.players;
+        let (white, black) = if first_plays_white { (first, second) } else { (second, first) };
+        play_game(white.as_mut(), black.as_mut(), opening, &self.limits, &self.adjudication)
+    }
+}
+
+// Play a game from `opening` to its end
+pub fn play_game(white: &mut dyn MatchPlayer, black: &mut dyn MatchPlayer, opening: &Opening, limits: &MatchLimits,
+                 adjudication: &Adjudication) -> Result<GameRecord, String> {
+    white.new_game()?;
+    black.new_game()?;
+
+    let mut board = opening.board.clone();
+    let mut history = opening.moves.clone();
+    for m in opening.moves.iter() {
+        board.apply_move(*m);
+    }
+
+    let mut clocks = match *limits {
+        MatchLimits::Clock { base, .. } => [base, base],
+        _ => [Duration::MAX, Duration::MAX],
+    };
+    // The position the game starts from counts as its first occurrence
+    let mut repetitions: HashMap<u64, u32> = HashMap::from([(board.hash(), 1)]);
+    let mut resign_counts = [0, 0];
+    let mut draw_count = 0;
+    let mut moves = Vec::new();
+
+    let (result, reason) = loop {
+        if let Some(outcome) = game_outcome(&mut board) {
+            break (GameResult::from_outcome(Some(outcome)), "end of game".to_string());
+        }
+
+        let side = board.side;
+        let player: &mut dyn MatchPlayer = if side == Color::White { white } else { black };
+        let started = Instant::now();
+        let (m, score) = match player.choose_move(&opening.board, &history, &board, limits, clocks) {
+            Ok(choice) => choice,
+            Err(error) => break (GameResult::from_outcome(Some(Outcome::Win(side.switch()))), error),
+        };
+
+        if let MatchLimits::Clock { increment, .. } = *limits {
+            let elapsed = started.elapsed();
+            if elapsed > clocks[side.index()] {
+                break (GameResult::from_outcome(Some(Outcome::Win(side.switch()))), format!("{:?} lost on time", side));
+            }
+            clocks[side.index()] = clocks[side.index()] - elapsed + increment;
+        }
+
+        board.apply_move(m);
+        history.push(m);
+        moves.push(m);
+
+        if board.halfmove_clock == 0 {
+            repetitions.clear();
+        }
+        let count = repetitions.entry(board.hash()).or_insert(0);
+        *count += 1;
+
+        if *count >= 3 {
+            break (GameResult::Draw, "threefold repetition".to_string());
+        }
+        if board.halfmove_clock >= 100 && game_outcome(&mut board).is_none() {
+            break (GameResult::Draw, "fifty-move rule".to_string());
+        }
+
+        // Adjudication by the scores the players report
+        if let Some(score) = score {
+            resign_counts[side.index()] = if score <= -adjudication.resign_score { resign_counts[side.index()] + 1 } else { 0 };
+            if adjudication.resign_moves > 0 && resign_counts[side.index()] >= adjudication.resign_moves {
+                break (GameResult::from_outcome(Some(Outcome::Win(side.switch()))), "adjudication".to_string());
+            }
+
+            let ply = history.len() as u32;
+            draw_count = if ply >= adjudication.draw_after_ply && score.abs() <= adjudication.draw_score { draw_count + 1 } else { 0 };
+            if adjudication.draw_plies > 0 && draw_count >= adjudication.draw_plies {
+                break (GameResult::Draw, "adjudication".to_string());
+            }
+        }
+        if history.len() as u32 >= adjudication.max_plies {
+            break (GameResult::Draw, "maximum length".to_string());
+        }
+    };
+
+    return Ok(GameRecord {
+        white: white.name(),
+        black: black.name(),
+        opening: opening.clone(),
+        moves,
+        result,
+        reason,
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn search_player(name: &str) -> Box<dyn MatchPlayer> {
+        Box::new(InProcessPlayer::create(name, InProcessEngine::Search(SearchOptions::create())))
+    }
+
+    // Plays the moves given in coordinate notation one after the other, starting over at the end
+    struct ScriptedPlayer {
+        moves: Vec<&'static str>,
+        played: usize,
+    }
+
+    impl MatchPlayer for ScriptedPlayer {
+        fn name(&self) -> String {
+            "scripted".to_string()
+        }
+
+        fn new_game(&mut self) -> Result<(), String> {
+            self.played = 0;
+            Ok(())
+        }
+
+        fn choose_move(&mut self, _start: &Board, _moves: &[Move], board: &Board, _limits: &MatchLimits,
+                       _clocks: [Duration; 2]) -> Result<(Move, Option<Score>), String> {
+            let text = self.moves[self.played % self.moves.len()];
+            self.played += 1;
+            let m = find_move(&mut board.clone(), text, false).ok_or(format!("Illegal move '{}'", text))?;
+            Ok((m, None))
+        }
+    }
+
+    #[test]
+    fn match_draw_rules() {
+        let mut white = ScriptedPlayer { moves: vec!("a1a2", "a2a1"), played: 0 };
+        let mut black = ScriptedPlayer { moves: vec!("h8h7", "h7h8"), played: 0 };
+
+        // The rooks are back where they started after 4 plies, the third time after 8
+        let opening = openings_from_epd("4k2r/8/8/8/8/8/8/R3K3 w - -").unwrap().remove(0);
+        let record = play_game(&mut white, &mut black, &opening, &MatchLimits::Depth(1), &Adjudication::create()).unwrap();
+        assert_eq!((record.result, record.reason.as_str(), record.moves.len()), (GameResult::Draw, "threefold repetition", 8));
+
+        // The halfmove clock starts at the opening's
+        let opening = openings_from_epd("4k2r/8/8/8/8/8/8/R3K3 w - - hmvc 99;").unwrap().remove(0);
+        let record = play_game(&mut white, &mut black, &opening, &MatchLimits::Depth(1), &Adjudication::create()).unwrap();
+        assert_eq!((record.result, record.reason.as_str(), record.moves.len()), (GameResult::Draw, "fifty-move rule", 1));
+
+        let opening = openings_from_pgn("[FEN \"4k2r/8/8/8/8/8/8/R3K3 w - - 98 60\"]\n\n1. Ra2 *\n", 1).unwrap().remove(0);
+        assert_eq!(opening.board.halfmove_clock, 98);
+        let record = play_game(&mut white, &mut black, &opening, &MatchLimits::Depth(1), &Adjudication::create()).unwrap();
+        assert_eq!((record.result, record.reason.as_str(), record.moves.len()), (GameResult::Draw, "fifty-move rule", 1));
+    }
+
+    #[test]
+    fn match_statistics() {
+        let statistics = MatchStatistics { wins: 60, draws: 20, losses: 20 };
+        assert_eq!(statistics.score(), 0.7);
+        let (elo, error) = statistics.elo().unwrap();
+        assert!((elo - 147.2).abs() < 0.1);
+        assert!(error > 50.0 && error < 80.0);
+
+        let even = MatchStatistics { wins: 10, draws: 10, losses: 10 };
+        assert!(even.elo().unwrap().0.abs() < 1e-3);
+        assert_eq!(MatchStatistics { wins: 3, draws: 0, losses: 0 }.elo(), None);
+
+        // A clearly stronger player is accepted as at least 5 Elo stronger, an equal one isn't
+        let sprt = Sprt::create(0.0, 5.0);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+        assert_eq!(sprt.decision(&MatchStatistics { wins: 600, draws: 200, losses: 200 }), Some(SprtDecision::AcceptH1));
+        assert_eq!(sprt.decision(&statistics), None);
+        assert_eq!(sprt.decision(&MatchStatistics { wins: 2000, draws: 2000, losses: 2200 }), Some(SprtDecision::AcceptH0));
+        assert_eq!(sprt.decision(&MatchStatistics { wins: 10, draws: 10, losses: 10 }), None);
+        assert_eq!(sprt.llr(&MatchStatistics::default()), 0.0);
+
+        assert_eq!(parse_info_score("info depth 3 score cp -35 nodes 100 pv e2e4"), Some(-0.35));
+        assert_eq!(parse_info_score("info depth 3 score mate 2 pv e2e4"), Some(MATE_SCORE - 3.0));
+        assert_eq!(parse_info_score("info depth 3 nodes 100"), None);
+    }
+
+    #[test]
+    fn match_uci_player() {
+        // Answers with the move set up by the rule set's options, or not at all, and quits on a
+        // depth limit or when asked to
+        let path = std::env::temp_dir().join(format!("mess_uci_player_{}.sh", std::process::id()));
+        std::fs::write(&path, r#"
+while read -r line; do
+    case "$line" in
+        uci) echo "id name scripted"; echo uciok;;
+        isready) echo readyok;;
+        "setoption name UCI_Variant value crazyhouse") reply=N@e4;;
+        "setoption name UCI_Chess960 value true") reply=f1h1;;
+        "go depth"*|quit) exit 0;;
+        go*) [ -n "$reply" ] && echo "info score cp 50" && echo "bestmove $reply";;
+    esac
+done
+"#).unwrap();
+        let mut player = UciPlayer::start(&format!("sh {}", path.display())).unwrap();
+        assert_eq!(player.name(), "scripted");
+        let limits = MatchLimits::MoveTime(Duration::from_millis(10));
+        let clocks = [Duration::MAX, Duration::MAX];
+
+        // An engine that doesn't move in time loses
+        let board = Board::create_populated();
+        assert!(player.choose_move(&board, &[], &board, &limits, clocks).unwrap_err().contains("in time"));
+        player.new_game().unwrap();
+
+        // The variant and Chess960 castling are set from the opening
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3[N] w - - 0 1").unwrap();
+        let (m, score) = player.choose_move(&board, &[], &board, &limits, clocks).unwrap();
+        assert_eq!((m.coordinate_notation().as_str(), score), ("N@e4", Some(0.5)));
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/5K1R w K - 0 1").unwrap();
+        let (m, _) = player.choose_move(&board, &[], &board, &limits, clocks).unwrap();
+        assert_eq!(m.castle, Some(Castle::KingSide));
+
+        assert!(player.choose_move(&board, &[], &board, &MatchLimits::Depth(1), clocks).unwrap_err().contains("gone away"));
+        drop(player);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn match_in_process() {
+        let openings = openings_from_epd("4k3/8/8/8/8/8/8/R3K3 w - -\n4k3/pppppppp/8/8/8/8/8/4K3 w - -\n").unwrap();
+        let mut runner = MatchRunner::create(search_player("first"), search_player("second"), openings, MatchLimits::Depth(1));
+        runner.set_adjudication(Adjudication { max_plies: 20, ..Adjudication::create() });
+
+        let mut log = Vec::new();
+        let report = runner.run(4, &mut log).unwrap();
+        assert_eq!(report.games.len(), 4);
+        assert_eq!(report.statistics.games(), 4);
+
+        // Each opening is played with both colors
+        assert_eq!((report.games[0].white.as_str(), report.games[1].white.as_str()), ("first", "second"));
+        assert_eq!(report.games[0].opening.board.to_fen(), report.games[1].opening.board.to_fen());
+        assert_ne!(report.games[0].opening.board.to_fen(), report.games[2].opening.board.to_fen());
+        // Whoever has the rook wins or at least doesn't lose
+        assert_ne!(report.games[0].result, GameResult::BlackWins);
+        assert_ne!(report.games[1].result, GameResult::BlackWins);
+
+        let log = String::from_utf8(log).unwrap();
+        assert_eq!(log.lines().count(), 4);
+        assert!(log.starts_with("Game 1: first - second "));
+
+        // A hopeless position is adjudicated
+        let openings = openings_from_epd("4k3/8/8/8/8/8/8/QQQ1K3 b - -").unwrap();
+        let mut runner = MatchRunner::create(search_player("first"), search_player("second"), openings, MatchLimits::Depth(1));
+        runner.set_adjudication(Adjudication { resign_score: 5.0, resign_moves: 1, ..Adjudication::create() });
+        runner.set_sprt(Sprt::create(0.0, 5.0));
+        let report = runner.run(2, &mut Vec::new()).unwrap();
+        assert_eq!(report.games[0].result, GameResult::WhiteWins);
+        assert_eq!(report.games[0].reason, "adjudication");
+        assert_eq!(report.games[0].moves.len(), 1);
+        assert_eq!(report.statistics, MatchStatistics { wins: 1, draws: 0, losses: 1 });
+    }
+
+    #[test]
+    fn match_openings_from_pgn() {
+        let openings = openings_from_pgn("1. e4 e5 2. Nf3 Nc6 *\n\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 *\n", 3).unwrap();
+        assert_eq!(openings.len(), 2);
+        assert_eq!(openings[0].moves.len(), 3);
+        assert_eq!(openings[1].board.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+
+        let record = GameRecord {
+            white: "A".to_string(),
+            black: "B".to_string(),
+            opening: openings[0].clone(),
+            moves: Vec::new(),
+            result: GameResult::Draw,
+            reason: "adjudication".to_string(),
+        };
+        assert!(record.to_pgn().to_pgn().ends_with("1. e4 e5 2. Nf3 1/2-1/2\n"));
+    }
+}
@@ -11,21 +11,31 @@ pub struct Move {
     pub en_passant_after: Option<Square>,
 
     pub castle_rights_before: BoardCastleRights,
+    pub halfmove_clock_before: u32,
 
     pub castle: Option<Castle>,
     pub promotion: Option<PieceKind>,
+
+    // Crazyhouse drop of a `piece_kind` from the pocket. Drops have no origin, `from` equals `to`.
+    pub is_drop: bool,
+    // Whether the captured piece had been promoted from a pawn, the one bit of Board::promoted
+    // that the move loses
+    pub capture_promoted: bool,
 }
 
 impl Move {
+    // The king ends up on the second file from the edge wherever it starts, as in Chess960, e.g. on
+    // file 6 or 2 on the standard board. It may stay where it is.
     pub fn castle(board: &Board, color: Color, castle: Castle) -> Move {
         let file = match castle {
-            Castle::KingSide => 6,
+            Castle::KingSide => board.geometry.files - 2,
             Castle::QueenSide => 2,
         };
 
-        let rank = color.back_rank();
+        let rank = board.geometry.back_rank(color);
+        let king = board.king_square(color).unwrap_or(Square::at(board.geometry.files / 2, rank));
 
-        let mut m = Move::from_to(board, PieceKind::King, Square::at(4, rank), Square::at(file, rank));
+        let mut m = Move::from_to(board, PieceKind::King, king, Square::at(file, rank));
         m.castle = Some(castle);
 
         return m;
@@ -40,6 +50,7 @@ impl Move {
     pub fn promotion_capture(board: &Board, from: Square, to: Square, capture: PieceOnBoard, promotion: PieceKind) -> Move {
         let mut m = Move::from_to(board, PieceKind::Pawn, from, to);
         m.capture = Some(capture);
+        m.capture_promoted = board.is_promoted(to);
         m.promotion = Some(promotion);
         return m;
     }
@@ -53,11 +64,20 @@ impl Move {
             en_passant_before: board.en_passant,
             en_passant_after: None,
             castle_rights_before: board.castle_rights,
+            halfmove_clock_before: board.halfmove_clock,
             castle: None,
             promotion: None,
+            is_drop: false,
+            capture_promoted: false,
         }
     }
 
+    pub fn drop(board: &Board, piece_kind: PieceKind, to: Square) -> Move {
+        let mut m = Move::from_to(board, piece_kind, to, to);
+        m.is_drop = true;
+        return m;
+    }
+
     // Create a Move that creates an en-passant square
     pub fn from_to_en_passant(board: &Board, from: Square, to: Square, en_passant: Square) -> Move {
         let mut m = Move::from_to(board, PieceKind::Pawn, from, to);
@@ -68,10 +88,17 @@ impl Move {
     pub fn from_to_capture(board: &Board, piece_kind: PieceKind, from: Square, to: Square, capture: PieceOnBoard) -> Move {
         let mut m = Move::from_to(board, piece_kind, from, to);
         m.capture = Some(capture);
+        m.capture_promoted = board.is_promoted(capture.square());
         return m;
     }
 
-    pub fn castle_rights_after(&self, side: Color) -> BoardCastleRights {
+    // Whether the move is a capture or a pawn move, which reset the halfmove clock of the fifty-move
+    // rule
+    pub fn is_zeroing(&self) -> bool {
+        self.capture.is_some() || self.piece_kind == PieceKind::Pawn
+    }
+
+    pub fn castle_rights_after(&self, side: Color, geometry: &Geometry) -> BoardCastleRights {
         let mut rights = self.castle_rights_before;
         let other_side = side.switch();
 
@@ -80,43 +107,109 @@ impl Move {
                 rights.set_rights(side, &ColorCastleRights::none());
             }
             PieceKind::Rook => {
-                if self.from == Square::at(7, side.back_rank()) {
-                    rights.get_rights_mut(side).king_side = false;
-                }
-                if self.from == Square::at(0, side.back_rank()) {
-                    rights.get_rights_mut(side).queen_side = false;
+                for castle in [Castle::KingSide, Castle::QueenSide] {
+                    if self.from == Square::at(rights.get_rights(side).rook_file(castle), geometry.back_rank(side)) {
+                        rights.get_rights_mut(side).revoke(castle);
+                    }
                 }
             }
             _ => {}
         }
 
         if let Some(capture) = self.capture {
-            if capture.1 == Square::at(7, other_side.back_rank()) {
-                rights.get_rights_mut(other_side).king_side = false;
-            }
-            if capture.1 == Square::at(0, other_side.back_rank()) {
-                rights.get_rights_mut(other_side).queen_side = false;
+            for castle in [Castle::KingSide, Castle::QueenSide] {
+                if capture.square() == Square::at(rights.get_rights(other_side).rook_file(castle), geometry.back_rank(other_side)) {
+                    rights.get_rights_mut(other_side).revoke(castle);
+                }
             }
         }
 
         return rights;
     }
 
-    // Create the move a Rook makes during castling
-    pub fn rook_castle(board: &Board, castle: Castle, rank: i8) -> Move {
-        assert!(rank == 0 || rank == 7);
+    // Board::promoted after the move, given `promoted` before it: promoted pieces keep their mark
+    // when they move and lose it when they are captured
+    pub fn promoted_after(&self, promoted: u128, geometry: &Geometry) -> u128 {
+        let bit = |square: Square| 1u128 << geometry.index(square);
+        let mut promoted = promoted;
+        if let Some(capture) = self.capture {
+            promoted &= !bit(capture.square());
+        }
+        if !self.is_drop && promoted & bit(self.from) != 0 {
+            promoted &= !bit(self.from);
+            promoted |= bit(self.to);
+        }
+        if self.promotion.is_some() {
+            promoted |= bit(self.to);
+        }
+        return promoted;
+    }
 
-        return match castle {
-            Castle::KingSide => {
-                Move::from_to(board, PieceKind::Rook, Square::at(7, rank), Square::at(5, rank))
-            }
-            Castle::QueenSide => {
-                Move::from_to(board, PieceKind::Rook, Square::at(0, rank), Square::at(3, rank))
+    // Inverse of promoted_after(). Pawns and dropped pieces are never marked, so the mark on `to`
+    // goes back to `from` unless the move promoted.
+    pub fn promoted_before(&self, promoted: u128, geometry: &Geometry) -> u128 {
+        let bit = |square: Square| 1u128 << geometry.index(square);
+        let mut promoted = promoted;
+        if promoted & bit(self.to) != 0 {
+            promoted &= !bit(self.to);
+            if self.promotion.is_none() && !self.is_drop {
+                promoted |= bit(self.from);
             }
+        }
+        if let Some(capture) = self.capture.filter(|_| self.capture_promoted) {
+            promoted |= bit(capture.square());
+        }
+        return promoted;
+    }
+
+    // Kind of the captured piece as it goes into the capturer's pocket in Crazyhouse. Promoted
+    // pieces turn back into pawns.
+    pub fn pocketed(&self) -> Option<PieceKind> {
+        let capture = self.capture?;
+        if self.capture_promoted {
+            return Some(PieceKind::Pawn);
+        }
+        return Some(capture.piece().kind);
+    }
+
+    // The squares the rook moves from and to when castling, None for other moves. The rook lands
+    // next to the king on the inside wherever it starts, e.g. on file 5 or 3 on the standard board.
+    pub fn castle_rook_squares(&self) -> Option<(Square, Square)> {
+        let castle = self.castle?;
+        let rank = self.from.rank();
+        let color = if rank == 0 { Color::White } else { Color::Black };
+        let file = self.castle_rights_before.get_rights(color).rook_file(castle);
+
+        return match castle {
+            Castle::KingSide => Some((Square::at(file, rank), self.to.delta(-1, 0))),
+            Castle::QueenSide => Some((Square::at(file, rank), self.to.delta(1, 0))),
         };
     }
 
+    // Notation of the move as used by UCI, e.g. "e2e4", "e7e8q" or "N@f3" for a drop
+    pub fn coordinate_notation(&self) -> String {
+        if self.is_drop {
+            return format!("{}@{}", self.piece_kind.token().to_ascii_uppercase(), self.to.coordinate());
+        }
+
+        let promotion = match self.promotion {
+            Some(promotion) => promotion.token().to_string(),
+            None => String::new()
+        };
+        format!("{}{}{}", self.from.coordinate(), self.to.coordinate(), promotion)
+    }
+
+    // Notation of the move as used by UCI, which writes castling as the king taking its own rook
+    // in Chess960, e.g. "e1h1"
+    pub fn uci_notation(&self, chess960: bool) -> String {
+        match self.castle_rook_squares() {
+            Some((rook, _)) if chess960 => format!("{}{}", self.from.coordinate(), rook.coordinate()),
+            _ => self.coordinate_notation()
+        }
+    }
+
+    // e.g. "e2-e4" or "i2-i4" on a 10x8 board
     pub fn long_algebraic(&self) -> String {
-        format!("{}{}{}", self.from.algebraic(), "-", self.to.algebraic())
+        format!("{}{}{}", self.from.coordinate(), "-", self.to.coordinate())
     }
 }
@@ -1,19 +1,15 @@
 use super::core::*;
 use super::board::*;
 use super::move_::*;
+use super::piece_definition::*;
+use super::variant::*;
 use crate::MoveUnmove;
 
-type Direction = (i8, i8);
-
-const STRAIGHT_DIRECTIONS: [Direction; 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
-const DIAGONAL_DIRECTIONS: [Direction; 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
-const KNIGHT_DIRECTIONS: [Direction; 8] = [(-2, -1), (-1, -2), (1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1)];
-
 // Add a move by x_delta, y_delta to the moves if the target square is on board and is unoccupied
 // or can be captured. Return whether the target square was unoccupied.
 fn probe_move(board: &Board, piece: &Piece, current_square: &Square, x_delta: i8, y_delta: i8, moves: &mut Vec<Move>) -> bool {
     let target_square = current_square.delta(x_delta, y_delta);
-    if !target_square.is_on_board() {
+    if !board.geometry.contains(target_square) {
         return false;
     }
 
@@ -24,7 +20,7 @@ fn probe_move(board: &Board, piece: &Piece, current_square: &Square, x_delta: i8
             if target_piece.color == piece.color {
                 false
             } else {
-                moves.push(Move::from_to_capture(board, piece.kind, *current_square, target_square, (target_piece, target_square)));
+                moves.push(Move::from_to_capture(board, piece.kind, *current_square, target_square, target_piece.at_square(&target_square)));
                 false
             }
         }
@@ -48,8 +44,8 @@ fn generate_directional_moves(board: &Board, piece: &Piece, current_square: &Squ
 
 // Generate either a normal or a promotion move, depending on which rank the pawn is headed to
 fn generate_pawn_move(board: &Board, piece: &Piece, from: &Square, to: &Square, capture: &Option<PieceOnBoard>, moves: &mut Vec<Move>) {
-    if to.rank() as u8 == piece.color.promotion_rank() {
-        for promotion in &[PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen] {
+    if to.rank() == board.geometry.promotion_rank(piece.color) {
+        for promotion in board.variant.promotions() {
             if let Some(capture) = capture {
                 moves.push(Move::promotion_capture(board, *from, *to, *capture, *promotion));
             } else {
@@ -66,7 +62,9 @@ fn generate_pawn_move(board: &Board, piece: &Piece, from: &Square, to: &Square,
 }
 
 pub fn generate_moves_impl(board: &Board, moves: &mut Vec<Move>) {
-    for (piece, square) in board.piece_list.iter() {
+    for piece_on_board in board.pieces() {
+        let piece = &piece_on_board.piece();
+        let square = &piece_on_board.square();
         if piece.color != board.side {
             continue;
         }
@@ -74,103 +72,120 @@ pub fn generate_moves_impl(board: &Board, moves: &mut Vec<Move>) {
         match piece.kind {
             PieceKind::Pawn => {
                 let forward = piece.color.forward();
-                let home_rank = piece.color.home_rank();
+                let home_rank = board.geometry.home_rank(piece.color);
 
-                if !board.has_piece_at(square.delta(0, forward)) && square.delta(0, forward).is_on_board() {
+                if !board.has_piece_at(square.delta(0, forward)) && board.geometry.contains(square.delta(0, forward)) {
                     generate_pawn_move(board, piece, square, &square.delta(0, forward), &None, moves);
 
-                    if square.rank() == home_rank && !board.has_piece_at(square.delta(0, forward * 2)) && square.delta(0, forward * 2).is_on_board() {
+                    if square.rank() == home_rank && board.variant.allows_double_step() &&
+                        !board.has_piece_at(square.delta(0, forward * 2)) && board.geometry.contains(square.delta(0, forward * 2)) {
                         moves.push(Move::from_to_en_passant(board, *square, square.delta(0, forward * 2), square.delta(0, forward)));
                     }
                 }
 
                 // Generate capture moves
-                for file_delta in [-1 as i8, 1 as i8].iter() {
+                for file_delta in [-1_i8, 1_i8].iter() {
                     let target_piece = board.piece_at(square.delta(*file_delta, forward));
 
-                    if target_piece.is_some() {
-                        let target_piece = target_piece.unwrap();
+                    if let Some(target_piece) = target_piece {
                         if target_piece.color != piece.color {
-                            generate_pawn_move(board, piece, square, &square.delta(*file_delta, forward), &Some((target_piece, square.delta(*file_delta, forward))), moves);
+                            generate_pawn_move(board, piece, square, &square.delta(*file_delta, forward), &Some(target_piece.at_square(&square.delta(*file_delta, forward))), moves);
                         }
                     }
 
                     if board.en_passant.is_some() && board.en_passant.unwrap() == square.delta(*file_delta, forward) {
                         let en_passant_piece = board.piece_at(square.delta(*file_delta, 0)).unwrap();
-                        moves.push(Move::from_to_capture(board, piece.kind, *square, square.delta(*file_delta, forward), (en_passant_piece, square.delta(*file_delta, 0))));
+                        moves.push(Move::from_to_capture(board, piece.kind, *square, square.delta(*file_delta, forward), en_passant_piece.at_square(&square.delta(*file_delta, 0))));
                     }
                 }
             }
-            PieceKind::Rook => {
-                for (x_delta, y_delta) in STRAIGHT_DIRECTIONS.iter() {
-                    generate_directional_moves(board, piece, square, *x_delta as i8, *y_delta as i8, moves);
-                }
-            }
-            PieceKind::Bishop => {
-                for (x_delta, y_delta) in DIAGONAL_DIRECTIONS.iter() {
-                    generate_directional_moves(board, piece, square, *x_delta as i8, *y_delta as i8, moves);
-                }
-            }
-            PieceKind::Queen => {
-                for (x_delta, y_delta) in STRAIGHT_DIRECTIONS.iter() {
-                    generate_directional_moves(board, piece, square, *x_delta as i8, *y_delta as i8, moves);
-                }
-                for (x_delta, y_delta) in DIAGONAL_DIRECTIONS.iter() {
-                    generate_directional_moves(board, piece, square, *x_delta as i8, *y_delta as i8, moves);
-                }
-            }
-            PieceKind::King => {
-                for (x_delta, y_delta) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, 1), (-1, -1), (1, -1)].iter() {
-                    probe_move(board, piece, square, *x_delta as i8, *y_delta as i8, moves);
+            _ => {
+                let definition = &board.definitions()[piece.kind as usize];
+                for pattern in definition.sliders.iter() {
+                    for (x_delta, y_delta) in pattern.directions().iter() {
+                        generate_directional_moves(board, piece, square, *x_delta, *y_delta, moves);
+                    }
                 }
-
-                // Generate King side castle
-                if board.castle_rights.get_rights(piece.color).test(Castle::KingSide) {
-                    if !board.has_piece_at(Square::at(5, piece.color.back_rank() as i8)) &&
-                        !board.has_piece_at(Square::at(6, piece.color.back_rank() as i8)) {
-                        moves.push(Move::castle(board, piece.color, Castle::KingSide));
+                for pattern in definition.leapers.iter() {
+                    for (x_delta, y_delta) in pattern.directions().iter() {
+                        probe_move(board, piece, square, *x_delta, *y_delta, moves);
                     }
                 }
 
-                // Generate Queen side castle
-                if board.castle_rights.get_rights(piece.color).test(Castle::QueenSide) {
-                    if !board.has_piece_at(Square::at(3, piece.color.back_rank() as i8)) &&
-                        !board.has_piece_at(Square::at(2, piece.color.back_rank() as i8)) &&
-                        !board.has_piece_at(Square::at(1, piece.color.back_rank() as i8)) {
-                        moves.push(Move::castle(board, piece.color, Castle::QueenSide));
+                if piece.kind == PieceKind::King {
+                    for castle in [Castle::KingSide, Castle::QueenSide] {
+                        if board.variant.allows_castling() && board.castle_rights.get_rights(piece.color).test(castle) {
+                            let m = Move::castle(board, piece.color, castle);
+                            if can_castle(board, &m, piece.color) {
+                                moves.push(m);
+                            }
+                        }
                     }
                 }
             }
-            PieceKind::Knight => {
-                for (x_delta, y_delta) in KNIGHT_DIRECTIONS.iter() {
-                    probe_move(board, piece, square, *x_delta as i8, *y_delta as i8, moves);
+        }
+    }
+
+    if board.variant.has_drops() {
+        generate_drops(board, moves);
+    }
+}
+
+// Drops of the pieces in the pocket of the side to move onto the empty squares. Pawns can't be
+// dropped onto the first or the last rank.
+fn generate_drops(board: &Board, moves: &mut Vec<Move>) {
+    for kind in POCKET_KINDS {
+        if board.pockets[board.side.index()][kind as usize] == 0 {
+            continue;
+        }
+
+        for rank in 0..board.geometry.ranks {
+            if kind == PieceKind::Pawn && (rank == 0 || rank == board.geometry.ranks - 1) {
+                continue;
+            }
+            for file in 0..board.geometry.files {
+                let square = Square::at(file, rank);
+                if !board.has_piece_at(square) {
+                    moves.push(Move::drop(board, kind, square));
                 }
             }
-            PieceKind::Dummy => {}
         }
     }
 }
 
+// The legal moves of `board` under its variant's rules. Games the variant has decided have none.
 pub fn generate_moves(board: &mut Board) -> Vec<Move> {
+    if board.variant.outcome(board).is_some() {
+        return Vec::new();
+    }
+
     let mut unverified_moves = Vec::new();
     generate_moves_impl(board, &mut unverified_moves);
 
-    // Filter out illegal moves
-    let mut moves = Vec::new();
+    if !board.variant.has_royal_king() {
+        return board.variant.filter_moves(unverified_moves);
+    }
+
+    // Filter out illegal moves, after which the side that moved must not be in check. The king
+    // stays where it is unless it moves itself, so it is only looked up again then.
+    let color = board.side;
+    let king = board.king_square(color);
+    let mut moves = Vec::with_capacity(unverified_moves.len());
     for move_ in unverified_moves.iter_mut() {
         let mut move_unmove = MoveUnmove::apply_move(board, move_);
-        if !is_check(board, board.side) {
+        let king = if move_.piece_kind == PieceKind::King { board.king_square(color) } else { king };
+        if is_king_safe(board, color, king) {
             moves.push(*move_);
         }
         move_unmove.revert_move(board);
     }
 
-    return moves;
+    return board.variant.filter_moves(moves);
 }
 
 pub fn probe_direction(board: &Board, from: &Square, direction: &Direction) -> Option<Piece> {
     let mut square = from.delta(direction.0, direction.1);
-    while square.is_on_board() {
+    while board.geometry.contains(square) {
         if let Some(piece) = board.piece_at(square) {
             return Some(piece);
         }
@@ -179,41 +194,97 @@ pub fn probe_direction(board: &Board, from: &Square, direction: &Direction) -> O
     None
 }
 
-pub fn is_check(board: &Board, color: Color) -> bool {
-    let square = board.king_square(color);
-    if square.is_none() {
+// Castling requires the squares between the king and its target and between the rook and its
+// target to be empty, apart from king and rook themselves, which may cross each other in Chess960.
+// The king may not castle out of check or through an attacked square. Castling into check is
+// ruled out like any other move into check.
+fn can_castle(board: &Board, m: &Move, color: Color) -> bool {
+    let (rook_from, rook_to) = m.castle_rook_squares().unwrap();
+    if board.piece_at(rook_from) != Some(PieceKind::Rook.colored(color)) {
         return false;
     }
 
-    let square = square.unwrap();
+    let span = |from: Square, to: Square| from.file().min(to.file())..=from.file().max(to.file());
+    let rank = m.from.rank();
 
-    for direction in STRAIGHT_DIRECTIONS.iter() {
-        if let Some(piece) = probe_direction(board, &square, direction) {
-            if piece == PieceKind::Rook.colored(color.switch()) ||
-                piece == PieceKind::Queen.colored(color.switch()) {
-                return true;
-            }
+    for file in span(m.from, m.to).chain(span(rook_from, rook_to)) {
+        let square = Square::at(file, rank);
+        if square != m.from && square != rook_from && board.has_piece_at(square) {
+            return false;
         }
     }
 
-    for direction in DIAGONAL_DIRECTIONS.iter() {
-        if let Some(piece) = probe_direction(board, &square, direction) {
-            if piece == PieceKind::Bishop.colored(color.switch()) ||
-                piece == PieceKind::Queen.colored(color.switch()) {
-                return true;
+    for file in span(m.from, m.to) {
+        let square = Square::at(file, rank);
+        if square != m.to && is_attacked(board, square, color) {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+// Whether `color` may leave its king, which stands on `king`, where it is. In Atomic the king must
+// survive the move, but may stand in check if the move blew up the opponent's king.
+fn is_king_safe(board: &Board, color: Color, king: Option<Square>) -> bool {
+    if board.variant == Variant::Atomic {
+        return board.king_square(color).is_some() &&
+            (board.king_square(color.switch()).is_none() || !is_check(board, color));
+    }
+    return match king {
+        Some(king) => !is_attacked(board, king, color),
+        None => true
+    };
+}
+
+pub fn is_check(board: &Board, color: Color) -> bool {
+    match board.king_square(color) {
+        Some(square) => is_attacked(board, square, color),
+        None => false
+    }
+}
+
+// Whether a king of `color` on `square` would be in check
+pub fn is_attacked(board: &Board, square: Square, color: Color) -> bool {
+    // An Atomic king can't capture its neighbour without blowing itself up, so kings next to each
+    // other can't be checked
+    if board.variant == Variant::Atomic {
+        if let Some(king) = board.king_square(color.switch()) {
+            if (king.file() - square.file()).abs() <= 1 && (king.rank() - square.rank()).abs() <= 1 {
+                return false;
             }
         }
     }
 
-    for direction in KNIGHT_DIRECTIONS.iter() {
-        if let Some(piece) = board.piece_at(square.delta(direction.0, direction.1)) {
-            if piece == PieceKind::Knight.colored(color.switch()) {
-                return true;
+    // Look for enemy pieces that reach the square along the patterns they move in, which are all
+    // symmetric. The first piece in each direction attacks the square if it slides that way, or if
+    // it leaps that way and stands next to the square. Kings are leapers too, so they may not stand
+    // next to each other.
+    let pattern_kinds = board.pattern_kinds();
+    for pattern in PATTERNS {
+        let (sliders, leapers) = (pattern_kinds.sliders[pattern as usize], pattern_kinds.leapers[pattern as usize]);
+        for direction in pattern.directions().iter() {
+            // Kinds of pieces that attack from the distance of `target`, beyond the first step only
+            // sliders
+            let mut target = square.delta(direction.0, direction.1);
+            let mut kinds = sliders | leapers;
+            while board.geometry.contains(target) {
+                if let Some(piece) = board.piece_at(target) {
+                    if piece.color != color && kinds & (1 << piece.kind as u16) != 0 {
+                        return true;
+                    }
+                    break;
+                }
+                kinds = sliders;
+                if kinds == 0 {
+                    break;
+                }
+                target = target.delta(direction.0, direction.1);
             }
         }
     }
 
-    for x_delta in [-1 as i8, 1 as i8].iter() {
+    for x_delta in [-1_i8, 1_i8].iter() {
         if let Some(piece) = board.piece_at(square.delta(*x_delta, color.forward())) {
             if piece == PieceKind::Pawn.colored(color.switch()) {
                 return true;
@@ -228,14 +299,15 @@ pub fn is_check(board: &Board, color: Color) -> bool {
 mod test {
     use super::*;
     use crate::test_util::*;
+    use crate::evaluation::static_evaluation;
 
     #[test]
     fn pawn_moves() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::Black).at(0, 6),
             PieceKind::Pawn.colored(Color::White).at(2, 1),
-            PieceKind::Pawn.colored(Color::White).at(3, 2)));
+            PieceKind::Pawn.colored(Color::White).at(3, 2)]);
 
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(2, 1), Square::at(2, 2)),
@@ -255,7 +327,7 @@ mod test {
     #[test]
     fn pawn_moves_blocked() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::Black).at(0, 6),
             PieceKind::Dummy.colored(Color::White).at(0, 4),
             PieceKind::Pawn.colored(Color::Black).at(5, 3),
@@ -263,7 +335,7 @@ mod test {
             PieceKind::Pawn.colored(Color::White).at(2, 1),
             PieceKind::Dummy.colored(Color::White).at(2, 2),
             PieceKind::Pawn.colored(Color::White).at(3, 1),
-            PieceKind::Dummy.colored(Color::White).at(3, 3)));
+            PieceKind::Dummy.colored(Color::White).at(3, 3)]);
 
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(3, 1), Square::at(3, 2))
@@ -281,10 +353,10 @@ mod test {
     fn pawn_moves_capture() {
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::Black).at(0, 6),
             PieceKind::Pawn.colored(Color::White).at(0, 5),
-            PieceKind::Pawn.colored(Color::White).at(1, 5), ));
+            PieceKind::Pawn.colored(Color::White).at(1, 5), ]);
         let expected_moves = vec!(
             TestMove::from_to_capture(&board, Square::at(0, 6), Square::at(1, 5), PieceKind::Pawn.colored(Color::White).at(1, 5)),
         );
@@ -294,12 +366,12 @@ mod test {
     #[test]
     fn pawn_moves_en_passant() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(1, 4),
             PieceKind::Pawn.colored(Color::Black).at(2, 4),
             PieceKind::Pawn.colored(Color::Black).at(4, 3),
             PieceKind::Pawn.colored(Color::White).at(5, 3),
-            PieceKind::Pawn.colored(Color::Black).at(7, 3), ));
+            PieceKind::Pawn.colored(Color::Black).at(7, 3), ]);
 
         board.en_passant = Some(Square::at(2, 5));
         let mut expected_moves = vec!(
@@ -307,7 +379,7 @@ mod test {
             TestMove::from_to_capture(&board, Square::at(1, 4), Square::at(2, 5), PieceKind::Pawn.colored(Color::Black).at(2, 4)),
             TestMove::from_to(&board, Square::at(5, 3), Square::at(5, 4))
         );
-        for mut move_ in expected_moves.iter_mut() {
+        for move_ in expected_moves.iter_mut() {
             move_.en_passant_before = board.en_passant;
         }
         assert_eq!(generate_moves(&mut board), expected_moves);
@@ -315,12 +387,12 @@ mod test {
         board.side = Color::Black;
         board.en_passant = Some(Square::at(5, 2));
         let mut expected_moves = vec!(
+            TestMove::from_to(&board, Square::at(2, 4), Square::at(2, 3)),
             TestMove::from_to(&board, Square::at(4, 3), Square::at(4, 2)),
             TestMove::from_to_capture(&board, Square::at(4, 3), Square::at(5, 2), PieceKind::Pawn.colored(Color::White).at(5, 3)),
             TestMove::from_to(&board, Square::at(7, 3), Square::at(7, 2)),
-            TestMove::from_to(&board, Square::at(2, 4), Square::at(2, 3)),
         );
-        for mut move_ in expected_moves.iter_mut() {
+        for move_ in expected_moves.iter_mut() {
             move_.en_passant_before = board.en_passant;
         }
         assert_eq!(generate_moves(&mut board), expected_moves);
@@ -330,9 +402,9 @@ mod test {
     fn pawn_moves_promotion() {
         // White pawn that can promote
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(1, 6),
-            PieceKind::Pawn.colored(Color::Black).at(2, 7), ));
+            PieceKind::Pawn.colored(Color::Black).at(2, 7), ]);
         let expected_moves = vec!(
             TestMove::promotion(&board, Square::at(1, 6), Square::at(1, 7), PieceKind::Knight),
             TestMove::promotion(&board, Square::at(1, 6), Square::at(1, 7), PieceKind::Bishop),
@@ -348,9 +420,9 @@ mod test {
         // Black pawn that can promote
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::Black).at(1, 1),
-            PieceKind::Pawn.colored(Color::White).at(2, 0), ));
+            PieceKind::Pawn.colored(Color::White).at(2, 0), ]);
         let expected_moves = vec!(
             TestMove::promotion(&board, Square::at(1, 1), Square::at(1, 0), PieceKind::Knight),
             TestMove::promotion(&board, Square::at(1, 1), Square::at(1, 0), PieceKind::Bishop),
@@ -367,10 +439,10 @@ mod test {
     #[test]
     fn rook_moves() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Rook.colored(Color::White).at(3, 3),
             PieceKind::Dummy.colored(Color::White).at(3, 5),
-            PieceKind::Pawn.colored(Color::Black).at(1, 3), ));
+            PieceKind::Pawn.colored(Color::Black).at(1, 3), ]);
 
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(3, 3), Square::at(4, 3)),
@@ -390,10 +462,10 @@ mod test {
     #[test]
     fn bishop_moves() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Bishop.colored(Color::White).at(3, 3),
             PieceKind::Dummy.colored(Color::White).at(1, 1),
-            PieceKind::Pawn.colored(Color::Black).at(1, 5), ));
+            PieceKind::Pawn.colored(Color::Black).at(1, 5), ]);
 
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(3, 3), Square::at(4, 4)),
@@ -413,12 +485,12 @@ mod test {
     #[test]
     fn queen_moves() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Queen.colored(Color::White).at(3, 3),
             PieceKind::Dummy.colored(Color::White).at(1, 1),
             PieceKind::Pawn.colored(Color::Black).at(1, 5),
             PieceKind::Dummy.colored(Color::White).at(3, 5),
-            PieceKind::Pawn.colored(Color::Black).at(1, 3), ));
+            PieceKind::Pawn.colored(Color::Black).at(1, 3), ]);
 
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(3, 3), Square::at(4, 3)),
@@ -449,8 +521,8 @@ mod test {
     fn king_basic_moves() {
         // Freestanding King
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
-            PieceKind::King.colored(Color::White).at(3, 2), ));
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(3, 2), ]);
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(3, 2), Square::at(4, 2)),
             TestMove::from_to(&board, Square::at(3, 2), Square::at(2, 2)),
@@ -465,10 +537,10 @@ mod test {
 
         // Blocked and capturing king at the edge of the board
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::King.colored(Color::White).at(3, 0),
             PieceKind::Dummy.colored(Color::White).at(4, 0),
-            PieceKind::Pawn.colored(Color::Black).at(2, 1)));
+            PieceKind::Pawn.colored(Color::Black).at(2, 1)]);
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(3, 0), Square::at(2, 0)),
             TestMove::from_to(&board, Square::at(3, 0), Square::at(3, 1)),
@@ -481,10 +553,10 @@ mod test {
     #[test]
     fn king_castling_moves() {
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::King.colored(Color::White).at(4, 0),
             PieceKind::Rook.colored(Color::White).at(0, 0),
-            PieceKind::Rook.colored(Color::White).at(7, 0)));
+            PieceKind::Rook.colored(Color::White).at(7, 0)]);
 
         // No castle rights, no castle
         board.castle_rights = BoardCastleRights::none();
@@ -509,10 +581,10 @@ mod test {
     #[test]
     fn king_castling_moves_blocked() {
         let mut original_board = Board::create_empty();
-        original_board.add_pieces(vec!(
+        original_board.add_pieces(&[
             PieceKind::King.colored(Color::Black).at(4, 7),
             PieceKind::Rook.colored(Color::Black).at(0, 7),
-            PieceKind::Rook.colored(Color::Black).at(7, 7)));
+            PieceKind::Rook.colored(Color::Black).at(7, 7)]);
         original_board.side = Color::Black;
         original_board.castle_rights = BoardCastleRights::all();
 
@@ -523,26 +595,153 @@ mod test {
 
         // Blocker on the queen side, not on the king side
         let mut board = original_board.clone();
-        board.piece_list.push(PieceKind::Dummy.colored(Color::Black).at(1, 7));
+        board.add_piece(&PieceKind::Dummy.colored(Color::Black).at(1, 7));
         assert!(generate_moves(&mut board).contains(&TestMove::castle(&board, Color::Black, Castle::KingSide)));
         assert!(!generate_moves(&mut board).contains(&TestMove::castle(&board, Color::Black, Castle::QueenSide)));
 
         // Blocker on the king side, not on the queen side
         let mut board = original_board.clone();
-        board.piece_list.push(PieceKind::Dummy.colored(Color::White).at(5, 7));
+        board.add_piece(&PieceKind::Dummy.colored(Color::White).at(5, 7));
         assert!(!generate_moves(&mut board).contains(&TestMove::castle(&board, Color::Black, Castle::KingSide)));
         assert!(generate_moves(&mut board).contains(&TestMove::castle(&board, Color::Black, Castle::QueenSide)));
     }
 
+    #[test]
+    fn chess960_castling_moves() {
+        // The king castles onto the rook's square and the rook jumps over it
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/5KR1 w K - 0 1").unwrap();
+        assert!(generate_moves(&mut board).contains(&TestMove::castle(&board, Color::White, Castle::KingSide)));
+
+        // Not out of check or through an attacked square
+        let mut board = Board::from_fen("4kr2/8/8/8/8/8/8/1R2K3 w Q - 0 1").unwrap();
+        assert!(generate_moves(&mut board).contains(&TestMove::castle(&board, Color::White, Castle::QueenSide)));
+        let mut board = Board::from_fen("3rk3/8/8/8/8/8/8/1R2K3 w Q - 0 1").unwrap();
+        assert!(!generate_moves(&mut board).contains(&TestMove::castle(&board, Color::White, Castle::QueenSide)));
+        let mut board = Board::from_fen("4r1k1/8/8/8/8/8/8/1R2K3 w Q - 0 1").unwrap();
+        assert!(!generate_moves(&mut board).contains(&TestMove::castle(&board, Color::White, Castle::QueenSide)));
+
+        // The squares the rook passes must be empty as well
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/RN2K3 w Q - 0 1").unwrap();
+        assert!(!generate_moves(&mut board).contains(&TestMove::castle(&board, Color::White, Castle::QueenSide)));
+    }
+
+    fn perft(board: &mut Board, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let mut count = 0;
+        for m in generate_moves(board).iter() {
+            let mut move_unmove = MoveUnmove::apply_move(board, m);
+            count += perft(board, depth - 1);
+            move_unmove.revert_move(board);
+        }
+        count
+    }
+
+    #[test]
+    fn chess960_perft() {
+        let mut board = Board::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+        assert_eq!(perft(&mut board, 1), 21);
+        assert_eq!(perft(&mut board, 2), 528);
+        assert_eq!(perft(&mut board, 3), 12189);
+    }
+
+    #[test]
+    fn atomic_perft() {
+        let mut board = Board::create_populated();
+        board.variant = Variant::Atomic;
+        assert_eq!(perft(&mut board, 4), 197326);
+
+        let mut board = Board::from_fen("rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1").unwrap();
+        board.variant = Variant::Atomic;
+        assert_eq!(perft(&mut board, 1), 40);
+        assert_eq!(perft(&mut board, 2), 1238);
+        assert_eq!(perft(&mut board, 3), 45237);
+
+        let mut board = Board::from_fen("rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1").unwrap();
+        board.variant = Variant::Atomic;
+        assert_eq!(perft(&mut board, 1), 28);
+        assert_eq!(perft(&mut board, 2), 833);
+        assert_eq!(perft(&mut board, 3), 23353);
+    }
+
+    #[test]
+    fn crazyhouse_perft() {
+        let mut board = Board::from_fen("r2q1rk1/1P3ppp/2n1pn2/8/1b1P4/2N2N2/PP3PPP/R1BQ~KB1R[BNPpp] w KQ - 0 1").unwrap();
+        let original_board = board.clone();
+        assert_eq!(perft(&mut board, 1), 149);
+        assert_eq!(perft(&mut board, 2), 10939);
+        assert_eq!(perft(&mut board, 3), 1314771);
+        assert_eq!(board, original_board);
+    }
+
+    #[test]
+    fn three_check_perft() {
+        let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 1+1 0 1").unwrap();
+        assert_eq!(board.variant, Variant::ThreeCheck);
+        assert_eq!(perft(&mut board, 1), 48);
+        assert_eq!(perft(&mut board, 2), 2039);
+        assert_eq!(perft(&mut board, 3), 97848);
+    }
+
+    #[test]
+    fn geometry_perft() {
+        let mut board = Variant::Capablanca.start_board();
+        assert_eq!(perft(&mut board, 1), 28);
+        assert_eq!(perft(&mut board, 2), 784);
+        assert_eq!(perft(&mut board, 3), 25228);
+
+        // Pawns only step once and promote on the sixth rank
+        let mut board = Variant::LosAlamos.start_board();
+        assert_eq!(perft(&mut board, 1), 10);
+        assert_eq!(perft(&mut board, 2), 100);
+        let mut board = Board::from_fen("1k4/4P1/6/6/6/K5 w - - 0 1").unwrap();
+        board.variant = Variant::LosAlamos;
+        assert_eq!(generate_moves(&mut board).iter().filter(|m| m.promotion.is_some()).count(), 3);
+    }
+
+    #[test]
+    fn fairy_moves() {
+        // Compound pieces move like both of their parts
+        for (kind, expected) in [(PieceKind::Archbishop, 13 + 8), (PieceKind::Chancellor, 14 + 8), (PieceKind::Amazon, 27 + 8)] {
+            let mut board = Board::create_empty();
+            board.add_pieces(&[kind.colored(Color::White).at(3, 3)]);
+            assert_eq!(generate_moves(&mut board).len(), expected);
+        }
+
+        // They give check with the knight's jump as well as along lines
+        let board = Board::from_fen("4k3/8/3A4/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(is_check(&board, Color::Black));
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/c3K3 w - - 0 1").unwrap();
+        assert!(is_check(&board, Color::White));
+        assert!(!generate_moves(&mut board).iter().any(|m| m.to == Square::at(3, 0)));
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/c3K3 w - - 0 1");
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/M3K3 w - - 0 1").unwrap().piece_at(Square::at(0, 0)),
+            Some(PieceKind::Amazon.colored(Color::White)));
+
+        // A board can have definitions of its own, here turning the Archbishop into a Centaur,
+        // which steps like a king and jumps like a knight, and giving it another letter
+        let mut board = Board::from_fen_with_definitions("4k3/8/8/8/3E4/8/8/4K3 w - - 0 1", centaur_definitions()).unwrap();
+        assert_eq!(board.piece_at(Square::at(3, 3)), Some(PieceKind::Archbishop.colored(Color::White)));
+        assert_eq!(generate_moves(&mut board).iter().filter(|m| m.piece_kind == PieceKind::Archbishop).count(), 8 + 8);
+        assert_eq!(static_evaluation(&board), 6.5);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/3E4/8/8/4K3 w - - 0 1");
+
+        // Other boards keep the built-in definitions
+        let mut board = Board::from_fen("4k3/8/8/8/3A4/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(generate_moves(&mut board).iter().filter(|m| m.piece_kind == PieceKind::Archbishop).count(), 13 + 8);
+    }
+
     #[test]
     fn knight_moves() {
         // Freestanding and capturing knight
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Knight.colored(Color::White).at(3, 4),
             PieceKind::Pawn.colored(Color::Black).at(4, 3),
             PieceKind::Pawn.colored(Color::Black).at(4, 4),
-            PieceKind::Pawn.colored(Color::Black).at(5, 3)));
+            PieceKind::Pawn.colored(Color::Black).at(5, 3)]);
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(3, 4), Square::at(1, 3)),
             TestMove::from_to(&board, Square::at(3, 4), Square::at(2, 2)),
@@ -557,15 +756,36 @@ mod test {
 
         // Blocked knight at the edge of the board
         let mut board = Board::create_empty();
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Knight.colored(Color::White).at(0, 7),
-            PieceKind::Dummy.colored(Color::White).at(1, 5)));
+            PieceKind::Dummy.colored(Color::White).at(1, 5)]);
         let expected_moves = vec!(
             TestMove::from_to(&board, Square::at(0, 7), Square::at(2, 6))
         );
         assert_eq!(generate_moves(&mut board), expected_moves);
     }
 
+    #[test]
+    fn no_moves_into_check() {
+        // A pinned rook may only move along the pin
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(4, 0),
+            PieceKind::Rook.colored(Color::White).at(4, 1),
+            PieceKind::Rook.colored(Color::Black).at(4, 7)]);
+
+        let moves = generate_moves(&mut board);
+        assert!(moves.iter().filter(|m| m.piece_kind == PieceKind::Rook).all(|m| m.to.file() == 4));
+        assert_eq!(moves.len(), 6 + 4);
+
+        // The king may not step onto a square the enemy rook attacks
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(0, 0),
+            PieceKind::Rook.colored(Color::Black).at(1, 7)]);
+        assert_eq!(generate_moves(&mut board), vec!(TestMove::from_to(&board, Square::at(0, 0), Square::at(0, 1))));
+    }
+
     #[test]
     fn no_king_exposure() {
         // Test that no moves that expose the king to check are generated
@@ -573,12 +793,12 @@ mod test {
         let mut board = Board::create_empty();
         board.side = Color::Black;
 
-        board.add_pieces(vec!(
+        board.add_pieces(&[
             PieceKind::Rook.colored(Color::Black).at(6, 7),
             PieceKind::King.colored(Color::Black).at(7, 7),
             PieceKind::Dummy.colored(Color::Black).at(6, 6),
             PieceKind::Rook.colored(Color::White).at(4, 7)
-        ));
+        ]);
 
         let expected_moves = vec!(
           TestMove::from_to(&board, Square::at(6, 7), Square::at(5, 7)),
@@ -591,9 +811,27 @@ mod test {
 
     #[test]
     fn is_check_empty_board() {
+        let board = Board::create_empty();
+        assert!(!is_check(&board, Color::Black));
+        assert!(!is_check(&board, Color::White));
+    }
+
+    #[test]
+    fn is_check_adjacent_kings() {
+        // A king next to the enemy king counts as attacked, so neither king may approach the other
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(3, 3),
+            PieceKind::King.colored(Color::Black).at(4, 4)]);
+        assert!(is_check(&board, Color::White));
+        assert!(is_check(&board, Color::Black));
+
         let mut board = Board::create_empty();
-        assert_eq!(is_check(&board, Color::Black), false);
-        assert_eq!(is_check(&board, Color::White), false);
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(0, 0),
+            PieceKind::King.colored(Color::Black).at(2, 0)]);
+        assert!(!is_check(&board, Color::White));
+        assert!(generate_moves(&mut board).iter().all(|m| m.to.file() == 0));
     }
 
     #[test]
@@ -601,19 +839,19 @@ mod test {
         let mut board = Board::create_empty();
 
         // White rook checks black
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::King.colored(Color::White).at(3, 3),
             PieceKind::Rook.colored(Color::White).at(4, 3),
             PieceKind::King.colored(Color::Black).at(4, 6)
         ]);
-        assert_eq!(is_check(&board, Color::Black), true);
+        assert!(is_check(&board, Color::Black));
 
         // White is not in check
-        assert_eq!(is_check(&board, Color::White), false);
+        assert!(!is_check(&board, Color::White));
 
         // A white pawn blocks the black rook from checking the king
-        board.piece_list.push(PieceKind::Pawn.colored(Color::Black).at(4, 5));
-        assert_eq!(is_check(&board, Color::Black), false);
+        board.add_piece(&PieceKind::Pawn.colored(Color::Black).at(4, 5));
+        assert!(!is_check(&board, Color::Black));
     }
 
     #[test]
@@ -621,11 +859,11 @@ mod test {
         let mut board = Board::create_empty();
 
         // White knight checks black
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Knight.colored(Color::White).at(2, 5),
             PieceKind::King.colored(Color::Black).at(4, 6)
         ]);
-        assert_eq!(is_check(&board, Color::Black), true);
+        assert!(is_check(&board, Color::Black));
     }
 
     #[test]
@@ -633,15 +871,15 @@ mod test {
         let mut board = Board::create_empty();
 
         // Black bishop checks white
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Bishop.colored(Color::Black).at(2, 4),
             PieceKind::King.colored(Color::White).at(4, 6)
         ]);
-        assert_eq!(is_check(&board, Color::White), true);
+        assert!(is_check(&board, Color::White));
 
         // A black knight blocks the black bishop from checking
-        board.piece_list.push(PieceKind::Knight.colored(Color::Black).at(3, 5));
-        assert_eq!(is_check(&board, Color::White), false);
+        board.add_piece(&PieceKind::Knight.colored(Color::Black).at(3, 5));
+        assert!(!is_check(&board, Color::White));
     }
 
     #[test]
@@ -649,15 +887,15 @@ mod test {
         let mut board = Board::create_empty();
 
         // White queen checks black horizontally
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Queen.colored(Color::White).at(5, 4),
             PieceKind::King.colored(Color::Black).at(1, 4)
         ]);
-        assert_eq!(is_check(&board, Color::Black), true);
+        assert!(is_check(&board, Color::Black));
 
         // A white knight blocks the white queen from checking
-        board.piece_list.push(PieceKind::Knight.colored(Color::White).at(3, 4));
-        assert_eq!(is_check(&board, Color::Black), false);
+        board.add_piece(&PieceKind::Knight.colored(Color::White).at(3, 4));
+        assert!(!is_check(&board, Color::Black));
     }
 
     #[test]
@@ -665,58 +903,56 @@ mod test {
         let mut board = Board::create_empty();
 
         // White queen checks black horizontally
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Queen.colored(Color::White).at(0, 5),
             PieceKind::King.colored(Color::Black).at(1, 4)
         ]);
-        assert_eq!(is_check(&board, Color::Black), true);
+        assert!(is_check(&board, Color::Black));
     }
 
     #[test]
     fn is_check_by_pawn() {
-        let mut board = Board::create_empty();
-
         // White pawn checks black
         let mut board = Board::create_empty();
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 3),
             PieceKind::King.colored(Color::Black).at(1, 4)
         ]);
-        assert_eq!(is_check(&board, Color::Black), true);
+        assert!(is_check(&board, Color::Black));
 
         // White pawn horizontally in front of black king does not check
         let mut board = Board::create_empty();
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(1, 3),
             PieceKind::King.colored(Color::Black).at(1, 4)
         ]);
-        assert_eq!(is_check(&board, Color::Black), false);
+        assert!(!is_check(&board, Color::Black));
 
         // White pawn has passed the black king and therefore does not check
         let mut board = Board::create_empty();
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::White).at(0, 5),
             PieceKind::King.colored(Color::Black).at(1, 4)
         ]);
-        assert_eq!(is_check(&board, Color::Black), false);
+        assert!(!is_check(&board, Color::Black));
 
         // Black pawn checks white
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::Black).at(0, 5),
             PieceKind::King.colored(Color::White).at(1, 4)
         ]);
-        assert_eq!(is_check(&board, Color::White), true);
+        assert!(is_check(&board, Color::White));
 
         // Black pawn has passed the white king and therefore does not check
         let mut board = Board::create_empty();
         board.side = Color::Black;
-        board.add_pieces(vec![
+        board.add_pieces(&[
             PieceKind::Pawn.colored(Color::Black).at(0, 3),
             PieceKind::King.colored(Color::White).at(1, 4)
         ]);
-        assert_eq!(is_check(&board, Color::White), false);
+        assert!(!is_check(&board, Color::White));
     }
 }
 
@@ -0,0 +1,200 @@
+use std::path::Path;
+
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::polyglot::*;
+use super::variant::*;
+
+// Size of an entry in a Polyglot book: key (8 bytes), move (2), weight (2), learn (4), big-endian
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub move_data: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn read(bytes: &[u8]) -> BookEntry {
+        BookEntry {
+            key: u64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]),
+            move_data: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        }
+    }
+
+    pub fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.key.to_be_bytes());
+        bytes.extend_from_slice(&self.move_data.to_be_bytes());
+        bytes.extend_from_slice(&self.weight.to_be_bytes());
+        bytes.extend_from_slice(&self.learn.to_be_bytes());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BookSelection {
+    // Always the move with the highest weight
+    Best,
+    // A random move, with a probability proportional to its weight
+    WeightedRandom,
+}
+
+// An opening book in the Polyglot .bin format, i.e. entries sorted by position key
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+    random_state: u64,
+}
+
+impl OpeningBook {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<OpeningBook> {
+        return Ok(OpeningBook::from_bytes(&std::fs::read(path)?));
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> OpeningBook {
+        let mut entries: Vec<BookEntry> = bytes.chunks_exact(ENTRY_SIZE).map(BookEntry::read).collect();
+        // Books should be sorted already, but lookups depend on it
+        entries.sort_by_key(|entry| entry.key);
+
+        let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or(0);
+
+        OpeningBook { entries, random_state: seed }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Seed the random number generator behind BookSelection::WeightedRandom, for reproducible picks
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random_state = seed;
+    }
+
+    // The legal book moves of `board` with their weights, in book order. Entries whose move isn't
+    // legal, e.g. because of a key collision, are skipped.
+    pub fn probe(&self, board: &mut Board) -> Vec<(Move, u16)> {
+        // Polyglot books only know the standard board and pieces
+        if board.variant != Variant::Standard || board.geometry != Geometry::STANDARD {
+            return Vec::new();
+        }
+
+        let key = match polyglot_key(board) {
+            Some(key) => key,
+            None => return Vec::new(),
+        };
+        let start = self.entries.partition_point(|entry| entry.key < key);
+
+        let mut moves = Vec::new();
+        for entry in self.entries[start..].iter().take_while(|entry| entry.key == key) {
+            if let Some(m) = decode_move(board, entry.move_data) {
+                moves.push((m, entry.weight));
+            }
+        }
+        return moves;
+    }
+
+    pub fn pick(&mut self, board: &mut Board, selection: BookSelection) -> Option<Move> {
+        let moves = self.probe(board);
+
+        match selection {
+            BookSelection::Best => {
+                // The first of several moves with the same weight, as Polyglot does
+                let mut best: Option<(Move, u16)> = None;
+                for (m, weight) in moves.iter() {
+                    if best.is_none() || *weight > best.unwrap().1 {
+                        best = Some((*m, *weight));
+                    }
+                }
+                best.map(|(m, _)| m)
+            }
+            BookSelection::WeightedRandom => {
+                let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+                if total == 0 {
+                    return moves.first().map(|(m, _)| *m);
+                }
+
+                let mut choice = self.next_random() % total;
+                for (m, weight) in moves.iter() {
+                    if choice < *weight as u64 {
+                        return Some(*m);
+                    }
+                    choice -= *weight as u64;
+                }
+                None
+            }
+        }
+    }
+
+    // SplitMix64
+    fn next_random(&mut self) -> u64 {
+        self.random_state = self.random_state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.random_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::uci::parse_move;
+
+    fn book_bytes(board: &mut Board, moves: &[(&str, u16)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        // An entry of another position in front, which has to be skipped
+        BookEntry { key: 0, move_data: 0, weight: 100, learn: 0 }.write(&mut bytes);
+
+        for (text, weight) in moves.iter() {
+            let m = parse_move(board, text).unwrap();
+            BookEntry { key: polyglot_key(board).unwrap(), move_data: encode_move(&m), weight: *weight, learn: 0 }.write(&mut bytes);
+        }
+        return bytes;
+    }
+
+    #[test]
+    fn opening_book_pick() {
+        let mut board = Board::create_populated();
+        let mut book = OpeningBook::from_bytes(&book_bytes(&mut board, &[("e2e4", 10), ("d2d4", 30), ("c2c4", 0)]));
+        assert_eq!(book.len(), 4);
+
+        let moves = book.probe(&mut board);
+        assert_eq!(moves.iter().map(|(m, weight)| (m.coordinate_notation(), *weight)).collect::<Vec<(String, u16)>>(),
+                   vec!(("e2e4".to_string(), 10), ("d2d4".to_string(), 30), ("c2c4".to_string(), 0)));
+
+        assert_eq!(book.pick(&mut board, BookSelection::Best).unwrap().coordinate_notation(), "d2d4");
+
+        // Moves are picked in proportion to their weights, never with a weight of 0
+        book.set_seed(1);
+        let mut counts = [0; 2];
+        for _ in 0..400 {
+            match book.pick(&mut board, BookSelection::WeightedRandom).unwrap().coordinate_notation().as_str() {
+                "e2e4" => counts[0] += 1,
+                "d2d4" => counts[1] += 1,
+                m => panic!("Unexpected book move {}", m),
+            }
+        }
+        assert!(counts[0] > 50 && counts[1] > 2 * counts[0]);
+
+        // Out of book
+        let m = parse_move(&mut board, "e2e4").unwrap();
+        board.apply_move(m);
+        assert!(book.probe(&mut board).is_empty());
+        assert_eq!(book.pick(&mut board, BookSelection::Best), None);
+
+        // Positions with fairy pieces have no Polyglot key and are never in the book
+        let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBAR w KQkq - 0 1").unwrap();
+        assert_eq!(polyglot_key(&board), None);
+        assert!(book.probe(&mut board).is_empty());
+    }
+}
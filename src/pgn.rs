@@ -0,0 +1,307 @@
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::san::*;
+use super::variant::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    // Game still in progress or abandoned, "*"
+    Unknown,
+}
+
+impl GameResult {
+    pub fn from_token(token: &str) -> Option<GameResult> {
+        match token {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None
+        }
+    }
+
+    // Result of a game that ended with `outcome`, or is still going on without one
+    pub fn from_outcome(outcome: Option<Outcome>) -> GameResult {
+        match outcome {
+            Some(Outcome::Win(Color::White)) => GameResult::WhiteWins,
+            Some(Outcome::Win(Color::Black)) => GameResult::BlackWins,
+            Some(Outcome::Draw) => GameResult::Draw,
+            None => GameResult::Unknown,
+        }
+    }
+
+    pub fn token(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+}
+
+// A game of a PGN file, with the moves still in SAN
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    // The position the game starts from, the standard one unless there is a FEN tag
+    pub fn start_board(&self) -> Result<Board, String> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen),
+            None => Ok(Board::create_populated())
+        }
+    }
+
+    // Game of `moves` played from `board`, written in SAN. The FEN tag is added if the game
+    // doesn't start from the standard position.
+    pub fn from_moves(mut tags: Vec<(String, String)>, board: &Board, moves: &[Move], result: GameResult) -> PgnGame {
+        let mut board = board.clone();
+        if !board.semantic_eq(&Board::create_populated()) {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), board.to_fen()));
+        }
+
+        let mut san_moves = Vec::with_capacity(moves.len());
+        for m in moves.iter() {
+            san_moves.push(to_san(&mut board, m));
+            board.apply_move(*m);
+        }
+
+        return PgnGame { tags, moves: san_moves, result };
+    }
+
+    // The game in PGN, with the Result tag and the movetext wrapped at 80 columns
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in self.tags.iter().filter(|(name, _)| name != "Result") {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+        }
+        pgn.push_str(&format!("[Result \"{}\"]\n\n", self.result.token()));
+
+        // Games set up with Black to move start with "1..."
+        let black_first = self.start_board().is_ok_and(|board| board.side == Color::Black);
+        let mut tokens = Vec::new();
+        for (idx, san) in self.moves.iter().enumerate() {
+            let ply = idx + black_first as usize;
+            if ply.is_multiple_of(2) {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            } else if idx == 0 {
+                tokens.push("1...".to_string());
+            }
+            tokens.push(san.clone());
+        }
+        tokens.push(self.result.token().to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+
+        return pgn;
+    }
+
+    // Replay the game and return the moves. Fails on the first move that isn't legal.
+    pub fn replay(&self) -> Result<Vec<Move>, String> {
+        let mut board = self.start_board()?;
+        let mut moves = Vec::with_capacity(self.moves.len());
+
+        for text in self.moves.iter() {
+            let m = parse_san(&mut board, text).ok_or(format!("Illegal move '{}' after '{}'", text, self.moves[..moves.len()].join(" ")))?;
+            board.apply_move(m);
+            moves.push(m);
+        }
+
+        return Ok(moves);
+    }
+}
+
+// Split movetext into tokens, dropping comments, variations, NAGs and move numbers
+fn movetext_tokens(movetext: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut comment = false;
+    let mut variation_depth = 0;
+
+    for c in movetext.chars() {
+        if comment {
+            comment = c != '}';
+            continue;
+        }
+
+        match c {
+            '{' => comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => {}
+            _ if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c),
+        }
+
+        // Comments and variations end tokens as well
+        if (c == '{' || c == '(') && !token.is_empty() {
+            tokens.push(std::mem::take(&mut token));
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    return tokens.into_iter()
+        .filter(|token| !token.starts_with('$'))
+        .map(|token| {
+            // Move numbers, possibly attached to the move as in "1.e4" or "12...Nf6"
+            let start = token.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(token.len());
+            if token[..start].contains('.') || start == token.len() && !token.contains('-') {
+                token[start..].to_string()
+            } else {
+                token
+            }
+        })
+        .filter(|token| !token.is_empty())
+        .collect();
+}
+
+// Parse all games of a PGN file
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, String> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+
+    let mut finish_game = |tags: &mut Vec<(String, String)>, movetext: &mut String| {
+        if tags.is_empty() && movetext.trim().is_empty() {
+            return;
+        }
+
+        let mut moves = movetext_tokens(movetext);
+        let result = match moves.last().and_then(|token| GameResult::from_token(token)) {
+            Some(result) => {
+                moves.pop();
+                result
+            }
+            None => GameResult::Unknown
+        };
+
+        games.push(PgnGame { tags: std::mem::take(tags), moves, result });
+        movetext.clear();
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        // Escaped lines and comments until the end of the line
+        if line.starts_with('%') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            // A tag after movetext starts the next game
+            if !movetext.trim().is_empty() {
+                finish_game(&mut tags, &mut movetext);
+            }
+
+            let inner = line.trim_start_matches('[').trim_end_matches(']');
+            let (name, value) = inner.split_once(' ').ok_or(format!("Invalid tag '{}'", line))?;
+            let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+            tags.push((name.to_string(), value));
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    finish_game(&mut tags, &mut movetext);
+
+    return Ok(games);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {Main line} Nc6 (2... d6 3. d4) 3. Bb5 $1 a6 4.Ba4 Nf6
+5. O-O 1-0
+
+[Event "Test"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[Result "1/2-1/2"]
+
+1. e4 Kd7 1/2-1/2
+"#;
+
+    #[test]
+    fn pgn_parse() {
+        let games = parse_pgn(PGN).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].result, GameResult::WhiteWins);
+        assert_eq!(games[0].moves, vec!("e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"));
+        let moves = games[0].replay().unwrap();
+        assert_eq!(moves.last().unwrap().coordinate_notation(), "e1g1");
+
+        assert_eq!(games[1].result, GameResult::Draw);
+        assert_eq!(games[1].replay().unwrap().len(), 2);
+
+        let games = parse_pgn("1. e4 e5 2. Ke3 *").unwrap();
+        assert_eq!(games[0].result, GameResult::Unknown);
+        assert!(games[0].replay().is_err());
+    }
+
+    #[test]
+    fn pgn_write() {
+        let games = parse_pgn(PGN).unwrap();
+        for game in games.iter() {
+            let board = game.start_board().unwrap();
+            let tags = game.tags.iter().filter(|(name, _)| name != "FEN").cloned().collect();
+            let written = PgnGame::from_moves(tags, &board, &game.replay().unwrap(), game.result);
+            assert_eq!(written.moves, game.moves);
+
+            let parsed = parse_pgn(&written.to_pgn()).unwrap();
+            assert_eq!(parsed.len(), 1);
+            assert_eq!(parsed[0].moves, game.moves);
+            assert_eq!(parsed[0].result, game.result);
+            assert_eq!(parsed[0].tag("FEN"), game.tag("FEN"));
+        }
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+        let mut after = board.clone();
+        let moves: Vec<Move> = ["e8d7", "e2e4"].iter().map(|text| {
+            let m = crate::uci::parse_move(&mut after, text).unwrap();
+            after.apply_move(m);
+            m
+        }).collect();
+        let game = PgnGame::from_moves(vec!(("White".to_string(), "A \"B\"".to_string())), &board, &moves, GameResult::Unknown);
+        assert_eq!(game.to_pgn(), "[White \"A \\\"B\\\"\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]\n[Result \"*\"]\n\n1... Kd7 2. e4 *\n");
+    }
+}
@@ -0,0 +1,128 @@
+use super::core::*;
+
+// Declarative description of how the kinds of pieces move and what they are worth. Move
+// generation, attack detection, FEN and the evaluation read it, so a new kind of piece only needs
+// an entry in PIECE_DEFINITIONS and a PieceKind. A board can also be given definitions of its own,
+// see Board::definitions, to prototype variants without recompiling.
+
+pub type Direction = (i8, i8);
+
+pub const STRAIGHT_DIRECTIONS: [Direction; 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+pub const DIAGONAL_DIRECTIONS: [Direction; 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+pub const KNIGHT_DIRECTIONS: [Direction; 8] = [(-2, -1), (-1, -2), (1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1)];
+
+// Sets of directions a piece moves in, either one step at a time as a leaper or any number of
+// steps as a slider
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    Straight,
+    Diagonal,
+    Knight,
+}
+
+pub const PATTERNS: [Pattern; 3] = [Pattern::Straight, Pattern::Diagonal, Pattern::Knight];
+
+impl Pattern {
+    pub fn directions(&self) -> &'static [Direction] {
+        match self {
+            Pattern::Straight => &STRAIGHT_DIRECTIONS,
+            Pattern::Diagonal => &DIAGONAL_DIRECTIONS,
+            Pattern::Knight => &KNIGHT_DIRECTIONS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PieceDefinition {
+    // FEN letter of the black piece, White's is the upper case one
+    pub token: char,
+    // Material value in pawns
+    pub value: f32,
+    pub sliders: &'static [Pattern],
+    pub leapers: &'static [Pattern],
+    // Bonus in pawns per square from White's side of the board, mirrored for Black, see
+    // square_value(). Indexed by Square::index().
+    pub square_table: Option<&'static [f32; 64]>,
+}
+
+impl PieceDefinition {
+    // Bonus of a `color` piece on `square` from the perspective of `color`. The tables are made for
+    // the standard board and don't apply to others.
+    pub fn square_value(&self, square: Square, color: Color, geometry: &Geometry) -> f32 {
+        match self.square_table {
+            Some(table) if *geometry == Geometry::STANDARD => {
+                let rank = if color == Color::White { square.rank() } else { 7 - square.rank() };
+                table[Square::at(square.file(), rank).index()]
+            }
+            _ => 0.0
+        }
+    }
+}
+
+// Compound pieces are worth more in the centre, where they reach the most squares
+const CENTER_TABLE: [f32; 64] = [
+    -0.3, -0.2, -0.1, -0.1, -0.1, -0.1, -0.2, -0.3,
+    -0.2, -0.1,  0.0,  0.0,  0.0,  0.0, -0.1, -0.2,
+    -0.1,  0.0,  0.1,  0.1,  0.1,  0.1,  0.0, -0.1,
+    -0.1,  0.0,  0.1,  0.2,  0.2,  0.1,  0.0, -0.1,
+    -0.1,  0.0,  0.1,  0.2,  0.2,  0.1,  0.0, -0.1,
+    -0.1,  0.0,  0.1,  0.1,  0.1,  0.1,  0.0, -0.1,
+    -0.2, -0.1,  0.0,  0.0,  0.0,  0.0, -0.1, -0.2,
+    -0.3, -0.2, -0.1, -0.1, -0.1, -0.1, -0.2, -0.3,
+];
+
+// Definitions of all kinds of pieces, indexed by PieceKind
+pub type PieceDefinitions = [PieceDefinition; PIECE_KIND_COUNT];
+
+// Pawns move by rules of their own and kings castle besides their steps
+pub const PIECE_DEFINITIONS: PieceDefinitions = [
+    PieceDefinition { token: 'p', value: 1.0, sliders: &[], leapers: &[], square_table: None },
+    PieceDefinition { token: 'n', value: 3.0, sliders: &[], leapers: &[Pattern::Knight], square_table: None },
+    PieceDefinition { token: 'b', value: 3.0, sliders: &[Pattern::Diagonal], leapers: &[], square_table: None },
+    PieceDefinition { token: 'r', value: 5.0, sliders: &[Pattern::Straight], leapers: &[], square_table: None },
+    PieceDefinition { token: 'q', value: 9.0, sliders: &[Pattern::Straight, Pattern::Diagonal], leapers: &[], square_table: None },
+    PieceDefinition { token: 'k', value: 200.0, sliders: &[], leapers: &[Pattern::Straight, Pattern::Diagonal], square_table: None },
+    // Archbishop: bishop and knight
+    PieceDefinition { token: 'a', value: 7.0, sliders: &[Pattern::Diagonal], leapers: &[Pattern::Knight], square_table: Some(&CENTER_TABLE) },
+    // Chancellor: rook and knight
+    PieceDefinition { token: 'c', value: 8.0, sliders: &[Pattern::Straight], leapers: &[Pattern::Knight], square_table: Some(&CENTER_TABLE) },
+    // Amazon: queen and knight
+    PieceDefinition { token: 'm', value: 12.0, sliders: &[Pattern::Straight, Pattern::Diagonal], leapers: &[Pattern::Knight], square_table: Some(&CENTER_TABLE) },
+    // Dummy: an immobile blocker, used by tests
+    PieceDefinition { token: 'd', value: 0.0, sliders: &[], leapers: &[], square_table: None },
+];
+
+// Kinds of pieces that slide and that leap in each of the PATTERNS according to a table of
+// definitions, as a bit per PieceKind. Attack detection looks them up instead of going through the
+// whole table for every square.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PatternKinds {
+    pub sliders: [u16; PATTERNS.len()],
+    pub leapers: [u16; PATTERNS.len()],
+}
+
+impl PatternKinds {
+    pub fn create(definitions: &PieceDefinitions) -> PatternKinds {
+        let mut kinds = PatternKinds { sliders: [0; PATTERNS.len()], leapers: [0; PATTERNS.len()] };
+        for (kind, definition) in definitions.iter().enumerate() {
+            for pattern in definition.sliders {
+                kinds.sliders[*pattern as usize] |= 1 << kind;
+            }
+            for pattern in definition.leapers {
+                kinds.leapers[*pattern as usize] |= 1 << kind;
+            }
+        }
+        return kinds;
+    }
+}
+
+// The kind whose FEN letter in `definitions` is `token`, case insensitive
+pub fn kind_from_token(definitions: &PieceDefinitions, token: char) -> Option<PieceKind> {
+    PIECE_KINDS.iter().find(|kind| definitions[**kind as usize].token == token.to_ascii_lowercase()).copied()
+}
+
+// FEN letter of `piece` in `definitions`, upper case for White
+pub fn piece_token(definitions: &PieceDefinitions, piece: Piece) -> char {
+    let token = definitions[piece.kind as usize].token;
+    if piece.color == Color::White { token.to_ascii_uppercase() } else { token }
+}
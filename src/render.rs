@@ -0,0 +1,272 @@
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::move_generation::*;
+use super::piece_definition::*;
+
+// ANSI background colors of the squares, see RenderOptions::colors
+const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const LAST_MOVE_SQUARE: &str = "\x1b[48;5;143m";
+const CHECK_SQUARE: &str = "\x1b[48;5;167m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+const RESET: &str = "\x1b[0m";
+
+// Glyphs of the standard pieces, indexed by PieceKind. Fairy pieces keep their letters.
+const WHITE_GLYPHS: [char; 6] = ['♙', '♘', '♗', '♖', '♕', '♔'];
+const BLACK_GLYPHS: [char; 6] = ['♟', '♞', '♝', '♜', '♛', '♚'];
+
+// How render() draws a board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderOptions {
+    // Chess glyphs instead of FEN letters
+    pub unicode: bool,
+    // Rank numbers on the left and file letters below the board
+    pub coordinates: bool,
+    // Draw the board from Black's side
+    pub flipped: bool,
+    // Color the squares with ANSI escape codes
+    pub colors: bool,
+    // Move whose squares are highlighted, needs `colors`
+    pub last_move: Option<Move>,
+    // Highlight the king of the side to move when it's in check, needs `colors`
+    pub highlight_check: bool,
+}
+
+impl RenderOptions {
+    // Plain letters with coordinates, from White's side, as Board's Display implementation uses
+    pub fn create() -> RenderOptions {
+        RenderOptions { unicode: false, coordinates: true, flipped: false, colors: false, last_move: None, highlight_check: false }
+    }
+}
+
+// Unicode chess glyph of `piece`, None for fairy pieces
+pub fn piece_glyph(piece: Piece) -> Option<char> {
+    let glyphs = match piece.color {
+        Color::White => &WHITE_GLYPHS,
+        Color::Black => &BLACK_GLYPHS,
+    };
+    glyphs.get(piece.kind as usize).copied()
+}
+
+// The token a square is drawn with, '.' if it's empty. Letters are those of the board's definitions.
+fn square_token(board: &Board, piece: Option<Piece>, unicode: bool) -> char {
+    match piece {
+        Some(piece) if unicode => piece_glyph(piece).unwrap_or(piece_token(board.definitions(), piece)),
+        Some(piece) => piece_token(board.definitions(), piece),
+        None => '.'
+    }
+}
+
+// Draw `board` as text, one line per rank. Without colors each square takes two characters, a
+// space and the piece, so that parse_diagram() can read the result back.
+pub fn render(board: &Board, options: &RenderOptions) -> String {
+    let geometry = board.geometry;
+    let files: Vec<i8> = if options.flipped { (0..geometry.files).rev().collect() } else { (0..geometry.files).collect() };
+    let ranks: Vec<i8> = if options.flipped { (0..geometry.ranks).collect() } else { (0..geometry.ranks).rev().collect() };
+
+    let check_square = if options.highlight_check && is_check(board, board.side) { board.king_square(board.side) } else { None };
+
+    let mut lines = Vec::new();
+    for rank in ranks.iter() {
+        let mut line = String::new();
+        if options.coordinates {
+            line.push_str(&format!("{:>2} ", rank + 1));
+        }
+
+        for file in files.iter() {
+            let square = Square::at(*file, *rank);
+            let piece = board.piece_at(square);
+            let token = square_token(board, piece, options.unicode);
+
+            if options.colors {
+                let background = if Some(square) == check_square {
+                    CHECK_SQUARE
+                } else if options.last_move.is_some_and(|m| m.from == square || m.to == square) {
+                    LAST_MOVE_SQUARE
+                } else if (file + rank) % 2 == 0 {
+                    DARK_SQUARE
+                } else {
+                    LIGHT_SQUARE
+                };
+                let foreground = match piece {
+                    Some(piece) if piece.color == Color::White => WHITE_PIECE,
+                    _ => BLACK_PIECE,
+                };
+                let token = if piece.is_some() { token } else { ' ' };
+                line.push_str(&format!("{}{} {} {}", background, foreground, token, RESET));
+            } else {
+                line.push(' ');
+                line.push(token);
+            }
+        }
+        lines.push(line);
+    }
+
+    if options.coordinates {
+        let mut line = "   ".to_string();
+        for file in files.iter() {
+            let letter = (b'a' + *file as u8) as char;
+            if options.colors {
+                line.push_str(&format!(" {} ", letter));
+            } else {
+                line.push(' ');
+                line.push(letter);
+            }
+        }
+        lines.push(line);
+    }
+
+    return lines.join("\n");
+}
+
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", render(self, &RenderOptions::create()))
+    }
+}
+
+// Read a board drawn by render() without colors, in letters or glyphs, with or without coordinates
+// and from either side. Empty squares are '.'. The position gets White to move and no castling
+// rights.
+pub fn parse_diagram(text: &str) -> Result<Board, String> {
+    let mut rows: Vec<(Option<i8>, Vec<char>)> = Vec::new();
+    let mut file_letters: Vec<char> = Vec::new();
+
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+
+        // The file letters below the board, in order from either side
+        let letters: Vec<char> = tokens.iter().filter_map(|token| token.chars().next()).collect();
+        if tokens.iter().all(|token| token.len() == 1) && letters.iter().all(|letter| ('a'..='j').contains(letter)) &&
+            letters.windows(2).all(|pair| (pair[0] as i8 - pair[1] as i8).abs() == 1) && letters.len() > 1 {
+            file_letters = letters;
+            continue;
+        }
+
+        let (label, squares) = match tokens[0].parse::<i8>() {
+            Ok(rank) => (Some(rank), &tokens[1..]),
+            Err(_) => (None, &tokens[..]),
+        };
+
+        let mut row = Vec::new();
+        for square in squares {
+            let mut chars = square.chars();
+            match (chars.next(), chars.next()) {
+                (Some(token), None) => row.push(token),
+                _ => return Err(format!("Invalid square '{}' in '{}'", square, line.trim())),
+            }
+        }
+        rows.push((label, row));
+    }
+
+    // Rank 1 at the top and file 'a' on the right mean the board is drawn from Black's side
+    let flipped = match (rows.first().and_then(|row| row.0), rows.last().and_then(|row| row.0)) {
+        (Some(first), Some(last)) => first < last,
+        _ => file_letters.first().is_some_and(|letter| *letter != 'a'),
+    };
+    if flipped {
+        rows.reverse();
+        for (_, row) in rows.iter_mut() {
+            row.reverse();
+        }
+    }
+
+    let mut fen_ranks = Vec::new();
+    for (_, row) in rows.iter() {
+        let mut fen_rank = String::new();
+        let mut empty = 0;
+        for token in row.iter() {
+            if *token == '.' {
+                empty += 1;
+                continue;
+            }
+            if empty > 0 {
+                fen_rank.push_str(&empty.to_string());
+                empty = 0;
+            }
+
+            let letter = match WHITE_GLYPHS.iter().position(|glyph| glyph == token) {
+                Some(kind) => PIECE_KINDS[kind].token().to_ascii_uppercase(),
+                None => match BLACK_GLYPHS.iter().position(|glyph| glyph == token) {
+                    Some(kind) => PIECE_KINDS[kind].token(),
+                    None => *token,
+                },
+            };
+            fen_rank.push(letter);
+        }
+        if empty > 0 {
+            fen_rank.push_str(&empty.to_string());
+        }
+        fen_ranks.push(fen_rank);
+    }
+
+    return Board::from_fen(&format!("{} w - - 0 1", fen_ranks.join("/")));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn render_board() {
+        let board = Board::create_populated();
+        assert_eq!(board.to_string(), " 8  r n b q k b n r
+ 7  p p p p p p p p
+ 6  . . . . . . . .
+ 5  . . . . . . . .
+ 4  . . . . . . . .
+ 3  . . . . . . . .
+ 2  P P P P P P P P
+ 1  R N B Q K B N R
+    a b c d e f g h");
+
+        let options = RenderOptions { unicode: true, coordinates: false, flipped: true, ..RenderOptions::create() };
+        let lines: Vec<String> = render(&board, &options).lines().map(|line| line.to_string()).collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0], " ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖");
+        assert_eq!(lines[7], " ♜ ♞ ♝ ♚ ♛ ♝ ♞ ♜");
+
+        // The last move and the king in check get their own colors
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1").unwrap();
+        let m = crate::uci::parse_move(&mut board, "e1e2").unwrap();
+        board.apply_move(m);
+        let options = RenderOptions { colors: true, last_move: Some(m), highlight_check: true, ..RenderOptions::create() };
+        let text = render(&board, &options);
+        assert!(text.contains(&format!("{}{} k {}", CHECK_SQUARE, BLACK_PIECE, RESET)));
+        assert!(text.contains(&format!("{}{} R {}", LAST_MOVE_SQUARE, WHITE_PIECE, RESET)));
+        assert_eq!(text.matches(LAST_MOVE_SQUARE).count(), 2);
+
+        // Pieces are drawn with the letters of the board's definitions
+        let board = Board::from_fen_with_definitions("4k3/8/8/8/3E4/8/8/4K3 w - - 0 1", centaur_definitions()).unwrap();
+        assert_eq!(render(&board, &RenderOptions { coordinates: false, ..RenderOptions::create() }).lines().nth(4), Some(" . . . E . . . ."));
+        let options = RenderOptions { unicode: true, coordinates: false, ..RenderOptions::create() };
+        assert_eq!(render(&board, &options).lines().nth(4), Some(" . . . E . . . ."));
+    }
+
+    #[test]
+    fn render_parse_diagram() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1").unwrap();
+        for unicode in [false, true] {
+            for coordinates in [false, true] {
+                // Without coordinates there is no telling the sides apart
+                for flipped in [false, coordinates] {
+                    let options = RenderOptions { unicode, coordinates, flipped, ..RenderOptions::create() };
+                    let diagram = render(&board, &options);
+                    assert!(parse_diagram(&diagram).unwrap().semantic_eq(&board), "{}", diagram);
+                }
+            }
+        }
+
+        let board = Board::from_fen("rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w - - 0 1").unwrap();
+        assert_eq!(parse_diagram(&board.to_string()).unwrap().to_fen(), board.to_fen());
+
+        assert!(parse_diagram(" . . k\n . K x").is_err());
+        assert!(parse_diagram(" . . k\n . K").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::move_generation::*;
+
+// Find the legal move written in Standard Algebraic Notation, e.g. "Nf3", "exd5", "e8=Q+" or
+// "O-O". Check and annotation suffixes are ignored. Returns None for illegal or ambiguous moves.
+pub fn parse_san(board: &mut Board, text: &str) -> Option<Move> {
+    let text = text.trim_end_matches(['+', '#', '!', '?']);
+
+    let castle = match text {
+        "O-O" | "0-0" => Some(Castle::KingSide),
+        "O-O-O" | "0-0-0" => Some(Castle::QueenSide),
+        _ => None
+    };
+    if castle.is_some() {
+        return generate_moves(board).into_iter().find(|m| m.castle == castle);
+    }
+
+    // Drops are written as in coordinate notation, e.g. "N@f3"
+    if text.contains('@') {
+        return generate_moves(board).into_iter().find(|m| m.is_drop && m.coordinate_notation() == text);
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+
+    // Promotion, with or without '='
+    let mut promotion = None;
+    if let Some(last) = chars.last() {
+        if last.is_ascii_uppercase() {
+            promotion = Some(PieceKind::from_token(*last)?);
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+    }
+
+    let piece_kind = match chars.first() {
+        Some(c) if c.is_ascii_uppercase() => {
+            let piece_kind = PieceKind::from_token(*c)?;
+            chars.remove(0);
+            piece_kind
+        }
+        _ => PieceKind::Pawn
+    };
+
+    // The destination is the last file letter and the rank after it, which has two digits on
+    // boards with more than nine ranks
+    let to_idx = chars.iter().rposition(|c| ('a'..='j').contains(c))?;
+    let to = Square::from_coordinate(&chars[to_idx..].iter().collect::<String>())?;
+
+    // What's left is disambiguation and the capture sign
+    let mut from_file = None;
+    let mut from_rank_digits = String::new();
+    for c in chars[..to_idx].iter() {
+        match c {
+            'a'..='j' => from_file = Some(*c as i8 - 'a' as i8),
+            '0'..='9' => from_rank_digits.push(*c),
+            'x' | ':' => {}
+            _ => return None
+        }
+    }
+    let from_rank = match from_rank_digits.as_str() {
+        "" => None,
+        digits => Some(digits.parse::<i8>().ok()? - 1),
+    };
+
+    let mut matches = generate_moves(board).into_iter().filter(|m| {
+        m.piece_kind == piece_kind && m.to == to && m.promotion == promotion && m.castle.is_none() &&
+            from_file.is_none_or(|file| m.from.file() == file) &&
+            from_rank.is_none_or(|rank| m.from.rank() == rank)
+    });
+
+    let m = matches.next()?;
+    if matches.next().is_some() {
+        return None;
+    }
+    return Some(m);
+}
+
+// Write the legal move `m` in Standard Algebraic Notation, e.g. "Nbd2", "exd5", "e8=Q+" or
+// "O-O#". Inverse of parse_san().
+pub fn to_san(board: &mut Board, m: &Move) -> String {
+    let mut san = match m.castle {
+        Some(Castle::KingSide) => "O-O".to_string(),
+        Some(Castle::QueenSide) => "O-O-O".to_string(),
+        None if m.is_drop => m.coordinate_notation(),
+        None => {
+            let mut san = String::new();
+            if m.piece_kind == PieceKind::Pawn {
+                if m.capture.is_some() {
+                    san.push(m.from.coordinate().chars().next().unwrap());
+                }
+            } else {
+                san.push(m.piece_kind.token().to_ascii_uppercase());
+
+                // Name the file, the rank or both if other pieces of the kind can go there
+                let others: Vec<Move> = generate_moves(board).into_iter()
+                    .filter(|other| other.piece_kind == m.piece_kind && other.to == m.to && other.from != m.from &&
+                        other.castle.is_none() && !other.is_drop)
+                    .collect();
+                if !others.is_empty() {
+                    let from = m.from.coordinate();
+                    if others.iter().all(|other| other.from.file() != m.from.file()) {
+                        san.push_str(&from[..1]);
+                    } else if others.iter().all(|other| other.from.rank() != m.from.rank()) {
+                        san.push_str(&from[1..]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+
+            if m.capture.is_some() {
+                san.push('x');
+            }
+            san.push_str(&m.to.coordinate());
+            if let Some(promotion) = m.promotion {
+                san.push('=');
+                san.push(promotion.token().to_ascii_uppercase());
+            }
+            san
+        }
+    };
+
+    board.apply_move(*m);
+    if is_check(board, board.side) {
+        san.push(if generate_moves(board).is_empty() { '#' } else { '+' });
+    }
+    board.revert_move(*m);
+
+    return san;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn san(fen: &str, text: &str) -> Option<String> {
+        let mut board = Board::from_fen(fen).unwrap();
+        parse_san(&mut board, text).map(|m| m.coordinate_notation())
+    }
+
+    #[test]
+    fn san_parse() {
+        assert_eq!(san(START_FEN, "e4"), Some("e2e4".to_string()));
+        assert_eq!(san(START_FEN, "Nf3"), Some("g1f3".to_string()));
+        assert_eq!(san(START_FEN, "Nf3!?"), Some("g1f3".to_string()));
+        assert_eq!(san(START_FEN, "Ke2"), None);
+        assert_eq!(san(START_FEN, "e5"), None);
+
+        // Captures and disambiguation
+        let fen = "4k3/8/8/3p4/4P3/8/8/RN2K2R w K - 0 1";
+        assert_eq!(san(fen, "exd5"), Some("e4d5".to_string()));
+        assert_eq!(san(fen, "O-O"), Some("e1g1".to_string()));
+        assert_eq!(san(fen, "O-O-O"), None);
+        assert_eq!(san("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "Ra4"), None);
+        assert_eq!(san("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "R1a4"), Some("a1a4".to_string()));
+        assert_eq!(san("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "R7a4"), Some("a7a4".to_string()));
+        assert_eq!(san("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "Rad1"), Some("a1d1".to_string()));
+
+        // Promotions
+        assert_eq!(san("8/1P2k3/8/8/8/8/8/4K3 w - - 0 1", "b8=Q+"), Some("b7b8q".to_string()));
+        assert_eq!(san("8/1P2k3/8/8/8/8/8/4K3 w - - 0 1", "b8N"), Some("b7b8n".to_string()));
+        assert_eq!(san("8/1P2k3/8/8/8/8/8/4K3 w - - 0 1", "b8"), None);
+
+        // Wider boards
+        let fen = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+        assert_eq!(san(fen, "j4"), Some("j2j4".to_string()));
+        assert_eq!(san(fen, "Ad3"), Some("c1d3".to_string()));
+        assert_eq!(san(fen, "Ci3"), Some("h1i3".to_string()));
+    }
+
+    #[test]
+    fn san_format() {
+        let format = |fen: &str, coordinate: &str| {
+            let mut board = Board::from_fen(fen).unwrap();
+            let m = generate_moves(&mut board).into_iter().find(|m| m.coordinate_notation() == coordinate).unwrap();
+            to_san(&mut board, &m)
+        };
+
+        assert_eq!(format(START_FEN, "e2e4"), "e4");
+        assert_eq!(format(START_FEN, "g1f3"), "Nf3");
+        assert_eq!(format("4k3/8/8/3p4/4P3/8/8/RN2K2R w K - 0 1", "e4d5"), "exd5");
+        assert_eq!(format("4k3/8/8/3p4/4P3/8/8/RN2K2R w K - 0 1", "e1g1"), "O-O");
+        assert_eq!(format("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "a1a4"), "R1a4");
+        assert_eq!(format("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", "a1d1"), "Rad1");
+        assert_eq!(format("4k3/8/8/2N5/8/8/8/2N1N2K w - - 0 1", "c1d3"), "Nc1d3");
+        assert_eq!(format("8/1P2k3/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), "b8=Q");
+        assert_eq!(format("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+        assert_eq!(format("4k3/8/8/8/8/8/8/4K3[N] w - - 0 1", "N@f6"), "N@f6+");
+
+        // Every move reads back as itself
+        for fen in [START_FEN, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"] {
+            let mut board = Board::from_fen(fen).unwrap();
+            for m in generate_moves(&mut board) {
+                let text = to_san(&mut board, &m);
+                assert_eq!(parse_san(&mut board, &text), Some(m));
+            }
+        }
+    }
+}
@@ -0,0 +1,1386 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::move_generation::*;
+use super::evaluation::*;
+use super::tablebase::*;
+use super::transposition_table::*;
+use super::variant::*;
+use super::{Line, MoveUnmove};
+
+// Evaluation in pawns
+pub type Score = f32;
+
+// Score of a side that is checkmated at the root. Mates further away from the root are scored
+// closer to zero, so that the search prefers the fastest mate.
+pub const MATE_SCORE: f32 = 10000.0;
+
+// Evaluations beyond this are mates
+pub const MATE_THRESHOLD: f32 = MATE_SCORE - 1000.0;
+
+// Evaluation of a position that the tablebase knows to be won at the root. Tablebase wins further
+// from the root are scored lower, all of them below mates.
+pub const TABLEBASE_WIN_SCORE: f32 = MATE_THRESHOLD - MAX_SEARCH_DEPTH as f32;
+
+const INFINITY: f32 = f32::MAX;
+
+// Moves until mate for an evaluation from the perspective of the side to move, negative when that
+// side gets mated, e.g. 1 for a mate in one. None for evaluations that aren't mates.
+pub fn mate_distance(evaluation: Score) -> Option<i32> {
+    if evaluation > MATE_THRESHOLD {
+        Some(((MATE_SCORE - evaluation) as i32 + 1) / 2)
+    } else if evaluation < -MATE_THRESHOLD {
+        Some(-((MATE_SCORE + evaluation) as i32 / 2))
+    } else {
+        None
+    }
+}
+
+// Width of the windows used by the null-window searches of Principal Variation Search. Material
+// values are whole numbers, so any width below 1.0 separates "better" from "not better".
+const NULL_WINDOW: f32 = 0.01;
+
+// Once an aspiration window has been widened beyond this, the root is searched with a full window
+const ASPIRATION_WINDOW_LIMIT: f32 = 64.0;
+
+// Depth that searches without a depth limit stop at
+pub const MAX_SEARCH_DEPTH: u32 = 64;
+
+// Number of nodes between two checks of the time and node limits
+const LIMIT_CHECK_INTERVAL: u64 = 1024;
+
+// Depth by which the null move is searched shallower than a normal move
+const NULL_MOVE_REDUCTION: u32 = 2;
+
+// Late move reductions only apply from this remaining depth on, and only to moves after this many
+// moves have been searched at full depth
+const LATE_MOVE_REDUCTION_MIN_DEPTH: u32 = 3;
+const LATE_MOVE_REDUCTION_MIN_MOVE_INDEX: usize = 3;
+
+// Reverse futility pruning applies up to this remaining depth, with a margin per remaining ply
+const REVERSE_FUTILITY_MAX_DEPTH: u32 = 3;
+const REVERSE_FUTILITY_MARGIN: f32 = 2.0;
+
+// Futility pruning applies up to this remaining depth, with a margin per remaining ply
+const FUTILITY_MAX_DEPTH: u32 = 2;
+const FUTILITY_MARGIN: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchOptions {
+    // Search the first move of a node with the full window and all others with a null window,
+    // re-searching with the full window only if a move turns out to be better than the first.
+    pub principal_variation_search: bool,
+
+    // Half-width of the window around the previous iteration's score that the root is searched
+    // with. The window is doubled on the failing side until the score falls inside of it.
+    // None searches every iteration with a full window.
+    pub aspiration_window: Option<f32>,
+
+    // Let the opponent move twice in a row at reduced depth. If that still fails high, the node
+    // is cut off. Skipped when in check and when the side to move only has pawns left, where
+    // zugzwang makes passing the turn unsound.
+    pub null_move_pruning: bool,
+
+    // Search quiet moves late in the move order at reduced depth, and at full depth only if they
+    // turn out to be better than expected
+    pub late_move_reductions: bool,
+
+    // Cut off nodes close to the leaves whose static evaluation exceeds beta by a safe margin
+    pub reverse_futility_pruning: bool,
+
+    // Skip quiet moves close to the leaves if the static evaluation is too far below alpha for
+    // them to catch up
+    pub futility_pruning: bool,
+
+    // Search moves that give check one ply deeper
+    pub check_extension: bool,
+
+    // Search the only legal move of a position one ply deeper
+    pub single_reply_extension: bool,
+
+    // Search pawn moves to the seventh rank one ply deeper
+    pub pawn_push_extension: bool,
+
+    // Maximum number of extensions along a single path from the root, so that forcing sequences
+    // can't make the search explode
+    pub max_extensions: u32,
+
+    // Number of entries of the transposition table, 0 disables it
+    pub transposition_table_size: usize,
+
+    // Number of threads searching the root at the same time (Lazy SMP). Helper threads search
+    // with varied depths and move orders and only contribute through the shared transposition
+    // table, the result is always the main thread's.
+    pub threads: usize,
+
+    // Number of best root moves to find, each with its own principal variation. The root is
+    // searched once per line, excluding the moves of the lines found before.
+    pub multi_pv: usize,
+}
+
+impl SearchOptions {
+    pub fn create() -> SearchOptions {
+        SearchOptions {
+            principal_variation_search: true,
+            aspiration_window: Some(1.5),
+            null_move_pruning: true,
+            late_move_reductions: true,
+            reverse_futility_pruning: true,
+            futility_pruning: true,
+            check_extension: true,
+            single_reply_extension: true,
+            pawn_push_extension: true,
+            max_extensions: 4,
+            transposition_table_size: 1 << 16,
+            threads: 1,
+            multi_pv: 1,
+        }
+    }
+
+    // Options for a plain full-width alpha-beta search, only iterative deepening and move ordering
+    // remain
+    pub fn plain() -> SearchOptions {
+        SearchOptions {
+            principal_variation_search: false,
+            aspiration_window: None,
+            null_move_pruning: false,
+            late_move_reductions: false,
+            reverse_futility_pruning: false,
+            futility_pruning: false,
+            check_extension: false,
+            single_reply_extension: false,
+            pawn_push_extension: false,
+            max_extensions: 0,
+            transposition_table_size: 0,
+            threads: 1,
+            multi_pv: 1,
+        }
+    }
+}
+
+// Limits of a search. Without any limit, the search only ends when it is stopped or reaches
+// MAX_SEARCH_DEPTH.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub move_time: Option<Duration>,
+    pub nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn infinite() -> SearchLimits {
+        SearchLimits { depth: None, move_time: None, nodes: None }
+    }
+
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits { depth: Some(depth), move_time: None, nodes: None }
+    }
+
+    pub fn move_time(move_time: Duration) -> SearchLimits {
+        SearchLimits { depth: None, move_time: Some(move_time), nodes: None }
+    }
+
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits { depth: None, move_time: None, nodes: Some(nodes) }
+    }
+}
+
+// Intermediate result of a search, sent for each line after each completed iteration
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: u32,
+    // 1 for the best line, 2 for the second best and so on
+    pub multi_pv: usize,
+    // From the perspective of the side to move
+    pub evaluation: Score,
+    pub line: Line,
+    pub node_count: u64,
+    pub duration: Duration,
+}
+
+impl SearchInfo {
+    // The `info` line a UCI engine reports this as, with castling moves written for Chess960 if
+    // `chess960` is set
+    pub fn to_uci_string(&self, chess960: bool) -> String {
+        let score = match mate_distance(self.evaluation) {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", (self.evaluation * 100.0).round() as i32),
+        };
+
+        let pv = self.line.moves.iter().map(|m| m.uci_notation(chess960)).collect::<Vec<String>>().join(" ");
+
+        format!("info depth {} multipv {} score {} nodes {} time {} pv {}",
+                self.depth, self.multi_pv, score, self.node_count, self.duration.as_millis(), pv)
+    }
+}
+
+// Negamax alpha-beta search with iterative deepening, move ordering and the enhancements selected
+// in SearchOptions.
+pub struct SearchEvaluator {
+    statistics: DynamicEvaluatorStatistics,
+    best_line: Line,
+    max_depth: u32,
+    options: SearchOptions,
+    transposition_table: Arc<TranspositionTable>,
+
+    // Set to abort the search, the result of the iteration in progress is discarded
+    stop: Arc<AtomicBool>,
+
+    // Whether get_stop_flag() has handed out the stop flag. Its owner then decides when the search
+    // stops, otherwise each search clears the flag the limits of the previous one raised.
+    stop_flag_shared: bool,
+
+    // Set while pondering, i.e. searching on the opponent's time. The time limit doesn't apply
+    // until it is cleared, after which the time spent so far counts against it.
+    ponder: Arc<AtomicBool>,
+
+    // 0 for the main thread, helper threads of Lazy SMP count up from 1
+    thread_index: usize,
+
+    limits: SearchLimits,
+    deadline: Option<Instant>,
+    search_start: Instant,
+
+    // Node count of the statistics when the search started, the node limit applies to the nodes
+    // searched since
+    search_start_nodes: u64,
+
+    // Depth of the iteration in progress
+    iteration_depth: u32,
+
+    // Receives a SearchInfo after each completed iteration of the main thread
+    info_sender: Option<Sender<SearchInfo>>,
+
+    // Lines of the last completed iteration, best first, with evaluations from the perspective of
+    // the side to move
+    principal_variations: Vec<(Score, Line)>,
+    root_side: Color,
+
+    // Root moves skipped because an earlier line of the same iteration already starts with them
+    excluded_root_moves: Vec<Move>,
+
+    // Restriction of the root moves set with set_root_moves()
+    search_moves: Option<Vec<Move>>,
+    excluded_moves: Vec<Move>,
+
+    // Endgame tablebase probed below the root
+    tablebase: Option<Arc<dyn Tablebase>>,
+
+    // Root moves that keep the best outcome according to the tablebase
+    tablebase_moves: Option<Vec<Move>>,
+}
+
+// Key to sort moves by, higher keys are searched first: The move of the previous iteration's
+// principal variation, the best move stored in the transposition table, then captures (most
+// valuable victim first, least valuable attacker first), then promotions and finally quiet moves.
+fn move_order_key(m: &Move, pv_move: Option<Move>, table_move: Option<MoveKey>) -> f32 {
+    if pv_move == Some(*m) {
+        return INFINITY;
+    }
+    if let Some(table_move) = table_move {
+        if table_move.matches(m) {
+            return INFINITY / 2.0;
+        }
+    }
+
+    let mut key = 0.0;
+    if let Some(capture) = m.capture {
+        key += 1000.0 + capture.piece().kind.value() * 10.0 - m.piece_kind.value();
+    }
+    if let Some(promotion) = m.promotion {
+        key += 500.0 + promotion.value();
+    }
+    return key;
+}
+
+pub fn order_moves(moves: &mut [Move], pv_move: Option<Move>, table_move: Option<MoveKey>) {
+    moves.sort_by(|a, b| move_order_key(b, pv_move, table_move).partial_cmp(&move_order_key(a, pv_move, table_move)).unwrap());
+}
+
+// Evaluation of a tablebase outcome `ply` plies from the root and the bound it gives
+fn tablebase_evaluation(wdl: Wdl, ply: u32) -> (f32, Bound) {
+    match wdl {
+        Wdl::Win => (TABLEBASE_WIN_SCORE - ply as f32, Bound::Lower),
+        Wdl::Loss => (-(TABLEBASE_WIN_SCORE - ply as f32), Bound::Upper),
+        // The 50-move rule turns cursed wins and blessed losses into draws
+        _ => (0.0, Bound::Exact),
+    }
+}
+
+// Mate evaluations are stored in the transposition table relative to the node instead of the
+// root, so that they remain valid when the position is reached at a different ply
+fn evaluation_to_table(evaluation: f32, ply: u32) -> f32 {
+    if evaluation > MATE_THRESHOLD {
+        evaluation + ply as f32
+    } else if evaluation < -MATE_THRESHOLD {
+        evaluation - ply as f32
+    } else {
+        evaluation
+    }
+}
+
+fn evaluation_from_table(evaluation: f32, ply: u32) -> f32 {
+    if evaluation > MATE_THRESHOLD {
+        evaluation - ply as f32
+    } else if evaluation < -MATE_THRESHOLD {
+        evaluation + ply as f32
+    } else {
+        evaluation
+    }
+}
+
+impl SearchEvaluator {
+    pub fn create_with_options(max_depth: u32, options: SearchOptions) -> SearchEvaluator {
+        SearchEvaluator {
+            statistics: DynamicEvaluatorStatistics::create(),
+            best_line: Line::empty(),
+            max_depth,
+            options,
+            transposition_table: Arc::new(TranspositionTable::create(options.transposition_table_size)),
+            stop: Arc::new(AtomicBool::new(false)),
+            stop_flag_shared: false,
+            ponder: Arc::new(AtomicBool::new(false)),
+            thread_index: 0,
+            limits: SearchLimits::depth(max_depth),
+            deadline: None,
+            search_start: Instant::now(),
+            search_start_nodes: 0,
+            iteration_depth: 0,
+            info_sender: None,
+            principal_variations: Vec::new(),
+            root_side: Color::White,
+            excluded_root_moves: Vec::new(),
+            search_moves: None,
+            excluded_moves: Vec::new(),
+            tablebase: None,
+            tablebase_moves: None,
+        }
+    }
+
+    // A helper thread for Lazy SMP that shares the transposition table with this evaluator
+    fn create_helper(&self, thread_index: usize, stop: Arc<AtomicBool>) -> SearchEvaluator {
+        // Only the main thread's lines are reported, so helpers don't need to find more than one
+        let mut options = self.options;
+        options.multi_pv = 1;
+
+        SearchEvaluator {
+            statistics: DynamicEvaluatorStatistics::create(),
+            best_line: Line::empty(),
+            max_depth: self.max_depth,
+            options,
+            transposition_table: self.transposition_table.clone(),
+            stop,
+            stop_flag_shared: true,
+            ponder: self.ponder.clone(),
+            thread_index,
+            limits: self.limits,
+            deadline: None,
+            search_start: Instant::now(),
+            search_start_nodes: 0,
+            iteration_depth: 0,
+            info_sender: None,
+            principal_variations: Vec::new(),
+            root_side: Color::White,
+            excluded_root_moves: Vec::new(),
+            search_moves: self.search_moves.clone(),
+            excluded_moves: self.excluded_moves.clone(),
+            tablebase: self.tablebase.clone(),
+            tablebase_moves: None,
+        }
+    }
+
+    // Replace the depth limit the evaluator was created with by `limits`
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+        self.max_depth = limits.depth.unwrap_or(MAX_SEARCH_DEPTH);
+    }
+
+    // Only search the root moves in `search_moves`, if given, and none of `excluded_moves`, like
+    // UCI's `go searchmoves`. Moves are matched by their squares, promotion and dropped piece.
+    // Everything below the root is searched in full. If no legal root move is left the restriction
+    // is ignored.
+    pub fn set_root_moves(&mut self, search_moves: Option<Vec<Move>>, excluded_moves: Vec<Move>) {
+        self.search_moves = search_moves;
+        self.excluded_moves = excluded_moves;
+    }
+
+    // Probe `tablebase` in positions with few enough pieces. At the root, only the moves that keep
+    // the best outcome within the 50-move rule, counted from the board's halfmove clock, are
+    // searched. Below the root, the outcomes bound the evaluation.
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<dyn Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    fn is_root_restricted(&self) -> bool {
+        self.search_moves.is_some() || !self.excluded_moves.is_empty() || !self.excluded_root_moves.is_empty() ||
+            self.tablebase_moves.is_some()
+    }
+
+    // Remove the root moves excluded by set_root_moves() and by earlier MultiPV lines
+    fn restrict_root_moves(&self, moves: &mut Vec<Move>) {
+        let is_allowed = |m: &Move| {
+            let key = MoveKey::of(m);
+            let whitelisted = match &self.search_moves {
+                Some(search_moves) => search_moves.iter().any(|s| key.matches(s)),
+                None => true
+            };
+            whitelisted && !self.excluded_moves.iter().any(|e| key.matches(e))
+        };
+
+        if moves.iter().any(is_allowed) {
+            moves.retain(is_allowed);
+        }
+
+        if let Some(tablebase_moves) = &self.tablebase_moves {
+            if moves.iter().any(|m| tablebase_moves.contains(m)) {
+                moves.retain(|m| tablebase_moves.contains(m));
+            }
+        }
+
+        moves.retain(|m| !self.excluded_root_moves.contains(m));
+    }
+
+    pub fn set_info_sender(&mut self, info_sender: Sender<SearchInfo>) {
+        self.info_sender = Some(info_sender);
+    }
+
+    // Flag that aborts the search when set. Once handed out, it is no longer reset by the
+    // evaluator.
+    pub fn get_stop_flag(&mut self) -> Arc<AtomicBool> {
+        self.stop_flag_shared = true;
+        self.stop.clone()
+    }
+
+    // Flag that marks the search as pondering while set, see SearchEvaluator::ponder. Clearing it
+    // turns the search into a normal search with the limits given to set_limits().
+    pub fn get_ponder_flag(&self) -> Arc<AtomicBool> {
+        self.ponder.clone()
+    }
+
+    // Whether the search has to be aborted. The first iteration is always completed, so that there
+    // is a move to play.
+    fn is_stopped(&self) -> bool {
+        self.iteration_depth > 1 && self.stop.load(Ordering::Relaxed)
+    }
+
+    // Raise the stop flag once the time or node limit has been exceeded
+    fn check_limits(&mut self) {
+        if !self.statistics.node_count.is_multiple_of(LIMIT_CHECK_INTERVAL) {
+            return;
+        }
+
+        if let Some(nodes) = self.limits.nodes {
+            if self.statistics.node_count - self.search_start_nodes >= nodes {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline && !self.ponder.load(Ordering::Relaxed) {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn get_options(&self) -> SearchOptions {
+        self.options
+    }
+
+    // The lines of the last search as (first move, evaluation from White's perspective, line),
+    // best first. There are up to `multi_pv` of them.
+    pub fn get_principal_variations(&self) -> Vec<(Move, Score, Line)> {
+        self.principal_variations.iter()
+            .filter(|(_, line)| !line.moves.is_empty())
+            .map(|(evaluation, line)| (line.moves[0], evaluation * self.root_side.evaluation_sign(), line.clone()))
+            .collect()
+    }
+
+    // Search like evaluate() and return the best lines, see get_principal_variations()
+    pub fn search_multi_pv(&mut self, board: &mut Board) -> Vec<(Move, Score, Line)> {
+        self.evaluate(board);
+        self.get_principal_variations()
+    }
+
+    // Static evaluation from the perspective of the side to move
+    fn static_evaluation(&self, board: &Board) -> f32 {
+        static_evaluation(board) * board.side.evaluation_sign()
+    }
+
+    // Evaluation of a position without legal moves from the perspective of the side to move.
+    // Stalemate is a draw. Positions without a king to move are evaluated statically, as the other
+    // evaluators do. Variants decide for themselves.
+    fn terminal_evaluation(&self, board: &Board, ply: u32) -> f32 {
+        if board.variant != Variant::Standard {
+            return match board.variant.terminal_outcome(board) {
+                Outcome::Win(color) if color == board.side => MATE_SCORE - ply as f32,
+                Outcome::Win(_) => -(MATE_SCORE - ply as f32),
+                Outcome::Draw => 0.0,
+            };
+        }
+        if is_check(board, board.side) {
+            return -(MATE_SCORE - ply as f32);
+        }
+        if board.king_square(board.side).is_some() {
+            return 0.0;
+        }
+        return self.static_evaluation(board);
+    }
+
+    // Search the move that has just been applied to `board` to `depth`. All but the first move of a
+    // node are searched with a null window first if Principal Variation Search is enabled.
+    fn search_move(&mut self, board: &mut Board, alpha: f32, beta: f32, depth: u32, ply: u32, extensions: u32, pv: &[Move], first: bool) -> (f32, Line) {
+        if first || !self.options.principal_variation_search {
+            let (evaluation, line) = self.alpha_beta(board, -beta, -alpha, depth, ply, extensions, pv, true);
+            return (-evaluation, line);
+        }
+
+        let (evaluation, line) = self.alpha_beta(board, -alpha - NULL_WINDOW, -alpha, depth, ply, extensions, pv, true);
+        if -evaluation > alpha && -evaluation < beta {
+            self.statistics.pvs_re_searches += 1;
+            let (evaluation, line) = self.alpha_beta(board, -beta, -alpha, depth, ply, extensions, pv, true);
+            return (-evaluation, line);
+        }
+
+        return (-evaluation, line);
+    }
+
+    // Returns the evaluation from the perspective of the side to move and the line leading to it.
+    // `pv` is the remainder of the previous iteration's principal variation if the node lies on it,
+    // otherwise it is empty. `extensions` is the number of extensions on the path from the root.
+    // `allow_null_move` is false directly after a null move, so that two null moves never follow
+    // each other.
+    fn alpha_beta(&mut self, board: &mut Board, mut alpha: f32, beta: f32, depth: u32, ply: u32, extensions: u32, pv: &[Move], allow_null_move: bool) -> (f32, Line) {
+        self.statistics.node_count += 1;
+
+        if self.thread_index == 0 {
+            self.check_limits();
+        }
+        if self.is_stopped() {
+            return (0.0, Line::empty());
+        }
+
+        if depth == 0 {
+            // Games the variant has decided, e.g. by blowing up a king, are over at the horizon too
+            if board.variant.outcome(board).is_some() {
+                return (self.terminal_evaluation(board, ply), Line::empty());
+            }
+            return (self.static_evaluation(board), Line::empty());
+        }
+
+        // Nodes searched with a null window only need to prove a bound, so selective pruning and
+        // transposition table cutoffs are restricted to them. Compare against twice the width to
+        // be safe from rounding.
+        let is_pv_node = beta - alpha > 2.0 * NULL_WINDOW;
+        let original_alpha = alpha;
+
+        let hash = board.hash();
+        let mut table_move = None;
+        if let Some(entry) = self.transposition_table.probe(hash) {
+            table_move = entry.best_move;
+
+            if !is_pv_node && entry.depth >= depth {
+                let evaluation = evaluation_from_table(entry.evaluation, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => evaluation >= beta,
+                    Bound::Upper => evaluation <= alpha,
+                };
+                if cutoff {
+                    return (evaluation, Line::empty());
+                }
+            }
+        }
+
+        // Tablebase draws are exact. Wins and losses are bounds, as the search may still find a
+        // mate. If they don't cut the node off, they narrow its evaluation down.
+        let mut tablebase_bound = None;
+        if ply > 0 {
+            if let Some(tablebase) = &self.tablebase {
+                if can_probe(tablebase.as_ref(), board) {
+                    if let Some(wdl) = tablebase.probe_wdl(board) {
+                        self.statistics.tablebase_hits += 1;
+
+                        let (evaluation, bound) = tablebase_evaluation(wdl, ply);
+                        let cutoff = match bound {
+                            Bound::Exact => true,
+                            Bound::Lower => evaluation >= beta,
+                            Bound::Upper => evaluation <= alpha,
+                        };
+                        if cutoff {
+                            return (evaluation, Line::empty());
+                        }
+
+                        if bound == Bound::Lower {
+                            alpha = alpha.max(evaluation);
+                        }
+                        tablebase_bound = Some((evaluation, bound));
+                    }
+                }
+            }
+        }
+
+        let in_check = is_check(board, board.side);
+        let static_evaluation = self.static_evaluation(board);
+
+        if self.options.reverse_futility_pruning && !is_pv_node && !in_check && depth <= REVERSE_FUTILITY_MAX_DEPTH &&
+            static_evaluation - REVERSE_FUTILITY_MARGIN * depth as f32 >= beta {
+            return (static_evaluation, Line::empty());
+        }
+
+        if self.options.null_move_pruning && allow_null_move && !is_pv_node && !in_check && depth > NULL_MOVE_REDUCTION &&
+            static_evaluation >= beta && board.has_non_pawn_material(board.side) {
+            let en_passant = board.apply_null_move();
+            let (evaluation, _) = self.alpha_beta(board, -beta, -beta + NULL_WINDOW, depth - 1 - NULL_MOVE_REDUCTION, ply + 1, extensions, &[], false);
+            board.revert_null_move(en_passant);
+
+            if -evaluation >= beta {
+                return (beta, Line::empty());
+            }
+        }
+
+        let mut moves = generate_moves(board);
+        if moves.is_empty() {
+            return (self.terminal_evaluation(board, ply), Line::empty());
+        }
+
+        // A single reply is judged by the legal moves, not by those left after the root restriction
+        let legal_move_count = moves.len();
+        if ply == 0 && self.is_root_restricted() {
+            self.restrict_root_moves(&mut moves);
+        }
+
+        let pv_move = pv.first().copied();
+        order_moves(&mut moves, pv_move, table_move);
+
+        if ply == 0 && self.thread_index > 0 && moves.len() > 2 {
+            // Helper threads search the root moves after the first one in different orders
+            let rotation = self.thread_index % (moves.len() - 1);
+            moves[1..].rotate_left(rotation);
+        }
+
+        let futile = self.options.futility_pruning && !is_pv_node && !in_check && depth <= FUTILITY_MAX_DEPTH &&
+            static_evaluation + FUTILITY_MARGIN * depth as f32 <= alpha;
+
+        let mut best_evaluation = -INFINITY;
+        let mut best_line = Line::empty();
+
+        let side = board.side;
+
+        for (idx, m) in moves.iter().enumerate() {
+            let child_pv = if pv_move == Some(*m) { &pv[1..] } else { &[] };
+            let is_quiet = m.capture.is_none() && m.promotion.is_none();
+
+            let mut move_unmove = MoveUnmove::apply_move(board, m);
+            let gives_check = is_check(board, board.side);
+            let extension = self.extension(m, side, &board.geometry, gives_check, legal_move_count, extensions);
+
+            if futile && idx > 0 && is_quiet && !gives_check && extension == 0 {
+                move_unmove.revert_move(board);
+                continue;
+            }
+
+            // A reduced search that doesn't beat alpha confirms that the move is as bad as its
+            // position in the move order suggests, otherwise the move is searched normally
+            let mut reduced_result = None;
+            if self.options.late_move_reductions && depth >= LATE_MOVE_REDUCTION_MIN_DEPTH && idx >= LATE_MOVE_REDUCTION_MIN_MOVE_INDEX &&
+                is_quiet && !in_check && !gives_check && extension == 0 {
+                let (evaluation, line) = self.alpha_beta(board, -alpha - NULL_WINDOW, -alpha, depth - 2, ply + 1, extensions, child_pv, true);
+                if -evaluation <= alpha {
+                    reduced_result = Some((-evaluation, line));
+                }
+            }
+
+            let (evaluation, line) = match reduced_result {
+                Some(result) => result,
+                None => self.search_move(board, alpha, beta, depth - 1 + extension, ply + 1, extensions + extension, child_pv, idx == 0)
+            };
+            move_unmove.revert_move(board);
+
+            if evaluation > best_evaluation {
+                best_evaluation = evaluation;
+                best_line = line;
+                best_line.push_front(m);
+            }
+
+            if evaluation > alpha {
+                alpha = evaluation;
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        match tablebase_bound {
+            Some((evaluation, Bound::Lower)) => best_evaluation = best_evaluation.max(evaluation),
+            Some((evaluation, Bound::Upper)) => best_evaluation = best_evaluation.min(evaluation),
+            _ => {}
+        }
+
+        // With root moves excluded, the root's evaluation isn't the position's
+        let is_partial_root = ply == 0 && self.is_root_restricted();
+
+        if !self.is_stopped() && !is_partial_root {
+            let bound = if best_evaluation <= original_alpha {
+                Bound::Upper
+            } else if best_evaluation >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+
+            self.transposition_table.store(hash, &TranspositionEntry {
+                depth,
+                evaluation: evaluation_to_table(best_evaluation, ply),
+                bound,
+                best_move: best_line.moves.first().map(MoveKey::of),
+            });
+        }
+
+        return (best_evaluation, best_line);
+    }
+
+    // Number of plies to search move `m` of `side` deeper, either 0 or 1. `move_count` is the
+    // number of legal moves in the position the move is made in.
+    fn extension(&mut self, m: &Move, side: Color, geometry: &Geometry, gives_check: bool, move_count: usize, extensions: u32) -> u32 {
+        if extensions >= self.options.max_extensions {
+            return 0;
+        }
+
+        if self.options.check_extension && gives_check {
+            self.statistics.check_extensions += 1;
+            return 1;
+        }
+
+        if self.options.single_reply_extension && move_count == 1 {
+            self.statistics.single_reply_extensions += 1;
+            return 1;
+        }
+
+        if self.options.pawn_push_extension && m.piece_kind == PieceKind::Pawn && m.to.rank() == geometry.seventh_rank(side) {
+            self.statistics.pawn_push_extensions += 1;
+            return 1;
+        }
+
+        return 0;
+    }
+
+    // Search the root with a window around `previous_evaluation`, widening it on fail-high and
+    // fail-low until the evaluation falls inside of it
+    fn aspiration_search(&mut self, board: &mut Board, depth: u32, previous_evaluation: f32, window: f32, pv: &[Move]) -> (f32, Line) {
+        let mut alpha_delta = window;
+        let mut beta_delta = window;
+
+        loop {
+            let alpha = if alpha_delta > ASPIRATION_WINDOW_LIMIT { -INFINITY } else { previous_evaluation - alpha_delta };
+            let beta = if beta_delta > ASPIRATION_WINDOW_LIMIT { INFINITY } else { previous_evaluation + beta_delta };
+
+            let (evaluation, line) = self.alpha_beta(board, alpha, beta, depth, 0, 0, pv, true);
+
+            if self.is_stopped() {
+                return (evaluation, line);
+            }
+
+            if evaluation <= alpha {
+                alpha_delta *= 2.0;
+            } else if evaluation >= beta {
+                beta_delta *= 2.0;
+            } else {
+                return (evaluation, line);
+            }
+
+            self.statistics.aspiration_re_searches += 1;
+        }
+    }
+
+    // Search to increasing depths up to max_depth and return the evaluation from the perspective
+    // of the side to move. Helper threads of odd index search one ply deeper than the others.
+    fn iterative_deepening(&mut self, board: &mut Board) -> f32 {
+        let max_depth = self.max_depth + (self.thread_index % 2) as u32;
+        // The previous position's tablebase moves mustn't restrict this one's
+        self.tablebase_moves = None;
+        let mut root_moves = generate_moves(board);
+        self.restrict_root_moves(&mut root_moves);
+
+        if let Some(tablebase) = self.tablebase.clone() {
+            if can_probe(tablebase.as_ref(), board) {
+                self.tablebase_moves = filter_root_moves(tablebase.as_ref(), board, &root_moves);
+                self.restrict_root_moves(&mut root_moves);
+            }
+        }
+        let multi_pv = self.options.multi_pv.min(root_moves.len()).max(1);
+        let mut evaluation = self.static_evaluation(board);
+
+        self.root_side = board.side;
+        self.principal_variations.clear();
+
+        for depth in 1..=max_depth {
+            self.iteration_depth = depth;
+
+            let mut principal_variations: Vec<(Score, Line)> = Vec::new();
+            self.excluded_root_moves.clear();
+
+            for pv_index in 0..multi_pv {
+                let (previous_evaluation, previous_line) = match self.principal_variations.get(pv_index) {
+                    Some((previous_evaluation, previous_line)) => (*previous_evaluation, previous_line.moves.clone()),
+                    None => (evaluation, Vec::new())
+                };
+
+                let (line_evaluation, line) = match self.options.aspiration_window {
+                    Some(window) if depth > 1 => self.aspiration_search(board, depth, previous_evaluation, window, &previous_line),
+                    _ => self.alpha_beta(board, -INFINITY, INFINITY, depth, 0, 0, &previous_line, true)
+                };
+
+                if self.is_stopped() {
+                    break;
+                }
+
+                let root_move = line.moves.first().copied();
+                principal_variations.push((line_evaluation, line));
+
+                match root_move {
+                    Some(root_move) => self.excluded_root_moves.push(root_move),
+                    None => break
+                }
+            }
+
+            self.excluded_root_moves.clear();
+
+            if self.is_stopped() {
+                break;
+            }
+
+            principal_variations.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+            evaluation = principal_variations[0].0;
+            self.best_line = principal_variations[0].1.clone();
+            self.principal_variations = principal_variations;
+
+            if let Some(info_sender) = &self.info_sender {
+                for (pv_index, (line_evaluation, line)) in self.principal_variations.iter().enumerate() {
+                    // The receiver may have lost interest, that's not a reason to stop searching
+                    let _ = info_sender.send(SearchInfo {
+                        depth,
+                        multi_pv: pv_index + 1,
+                        evaluation: *line_evaluation,
+                        line: line.clone(),
+                        node_count: self.statistics.node_count - self.search_start_nodes,
+                        duration: self.search_start.elapsed(),
+                    });
+                }
+            }
+
+            if self.stop.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+
+        return evaluation;
+    }
+
+    // Search with helper threads that work on their own copies of the board and share the
+    // transposition table. The helpers are stopped as soon as the main thread is done.
+    fn lazy_smp(&mut self, board: &mut Board) -> f32 {
+        let helper_stop = Arc::new(AtomicBool::new(false));
+        let mut helpers: Vec<SearchEvaluator> = (1..self.options.threads)
+            .map(|thread_index| self.create_helper(thread_index, helper_stop.clone()))
+            .collect();
+
+        let evaluation = std::thread::scope(|scope| {
+            for helper in helpers.iter_mut() {
+                let mut helper_board = board.clone();
+                scope.spawn(move || {
+                    helper.iterative_deepening(&mut helper_board);
+                });
+            }
+
+            let evaluation = self.iterative_deepening(board);
+            helper_stop.store(true, Ordering::Relaxed);
+            evaluation
+        });
+
+        for helper in helpers.iter() {
+            self.statistics.accumulate(&helper.statistics);
+        }
+
+        return evaluation;
+    }
+}
+
+impl DynamicEvaluator for SearchEvaluator {
+    fn create(max_depth: u32) -> SearchEvaluator {
+        SearchEvaluator::create_with_options(max_depth, SearchOptions::create())
+    }
+
+    fn evaluate(&mut self, board: &mut Board) -> f32 {
+        self.best_line.moves.clear();
+
+        let stopwatch = std::time::Instant::now();
+        self.search_start = stopwatch;
+        self.search_start_nodes = self.statistics.node_count;
+        if !self.stop_flag_shared {
+            self.stop.store(false, Ordering::Relaxed);
+        }
+        self.deadline = self.limits.move_time.map(|move_time| stopwatch + move_time);
+
+        let evaluation = if self.options.threads > 1 {
+            self.lazy_smp(board)
+        } else {
+            self.iterative_deepening(board)
+        };
+        self.statistics.duration += stopwatch.elapsed();
+
+        return evaluation * board.side.evaluation_sign();
+    }
+
+    fn get_best_line(&self) -> &Line {
+        &self.best_line
+    }
+
+    fn get_statistics(&self) -> DynamicEvaluatorStatistics {
+        self.statistics
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn search_basic() {
+        // Without extensions, the search sees exactly three plies
+        // A white pawn that can capture a black pawn
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 1),
+            PieceKind::Pawn.colored(Color::Black).at(1, 2)]);
+        let mut evaluator = SearchEvaluator::create_with_options(3, SearchOptions::plain());
+        assert_eq!(evaluator.evaluate(&mut board), 1.0);
+
+        // A black pawn that can capture a white pawn
+        let mut board = Board::create_empty();
+        board.side = Color::Black;
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 2),
+            PieceKind::Pawn.colored(Color::Black).at(1, 3)]);
+        let mut evaluator = SearchEvaluator::create_with_options(3, SearchOptions::plain());
+        assert_eq!(evaluator.evaluate(&mut board), -1.0);
+
+        // A white pawn that will be captured by a black pawn after it moves
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 4),
+            PieceKind::Pawn.colored(Color::Black).at(1, 6)]);
+        let mut evaluator = SearchEvaluator::create_with_options(3, SearchOptions::plain());
+        assert_eq!(evaluator.evaluate(&mut board), -1.0);
+    }
+
+    #[test]
+    fn search_stalemate_is_draw() {
+        // Black to move has no legal moves but isn't in check
+        let mut board = Board::create_empty();
+        board.side = Color::Black;
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(5, 6),
+            PieceKind::Queen.colored(Color::White).at(6, 5),
+            PieceKind::King.colored(Color::Black).at(7, 7)]);
+
+        let mut evaluator = SearchEvaluator::create(2);
+        assert_eq!(evaluator.evaluate(&mut board), 0.0);
+    }
+
+    #[test]
+    fn search_mate_in_one() {
+        // Back rank mate
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(6, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::King.colored(Color::Black).at(7, 7),
+            PieceKind::Pawn.colored(Color::Black).at(6, 6),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let mut evaluator = SearchEvaluator::create(2);
+        assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 1.0);
+        assert_eq!(evaluator.get_best_line().moves[0], TestMove::from_to(&board, Square::at(0, 0), Square::at(0, 7)));
+    }
+
+    #[test]
+    fn search_enhancements_keep_evaluation() {
+        // Principal Variation Search and aspiration windows only change how much of the tree is
+        // searched, never the result
+        let mut board = Board::create_populated();
+
+        let mut options = SearchOptions::plain();
+        options.principal_variation_search = true;
+        options.aspiration_window = Some(1.5);
+
+        let mut alpha_beta = AlphaBetaEvaluator::create(3);
+        let mut plain = SearchEvaluator::create_with_options(3, SearchOptions::plain());
+        let mut enhanced = SearchEvaluator::create_with_options(3, options);
+
+        let evaluation = alpha_beta.evaluate(&mut board);
+        assert_eq!(plain.evaluate(&mut board), evaluation);
+        assert_eq!(enhanced.evaluate(&mut board), evaluation);
+
+        assert_eq!(plain.get_statistics().pvs_re_searches, 0);
+        assert_eq!(plain.get_statistics().aspiration_re_searches, 0);
+    }
+
+    #[test]
+    fn selective_search_visits_fewer_nodes() {
+        let mut board = Board::create_populated();
+
+        let mut plain = SearchEvaluator::create_with_options(4, SearchOptions::plain());
+        let mut selective = SearchEvaluator::create_with_options(4, SearchOptions::create());
+        plain.evaluate(&mut board);
+        selective.evaluate(&mut board);
+
+        assert!(selective.get_statistics().node_count < plain.get_statistics().node_count);
+    }
+
+    #[test]
+    fn selective_search_finds_mate() {
+        // Each pruning technique on its own must not hide the back rank mate
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(6, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::Knight.colored(Color::White).at(3, 3),
+            PieceKind::King.colored(Color::Black).at(7, 7),
+            PieceKind::Pawn.colored(Color::Black).at(6, 6),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let mut options = Vec::new();
+        for idx in 0..4 {
+            let mut o = SearchOptions::plain();
+            o.null_move_pruning = idx == 0;
+            o.late_move_reductions = idx == 1;
+            o.reverse_futility_pruning = idx == 2;
+            o.futility_pruning = idx == 3;
+            options.push(o);
+        }
+        options.push(SearchOptions::create());
+
+        for o in options.iter() {
+            let mut evaluator = SearchEvaluator::create_with_options(4, *o);
+            assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 1.0);
+        }
+    }
+
+    #[test]
+    fn check_extension() {
+        // At depth 1 the mate is only found if the check is searched one ply deeper
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(6, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::King.colored(Color::Black).at(7, 7),
+            PieceKind::Pawn.colored(Color::Black).at(6, 6),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let mut options = SearchOptions::plain();
+        options.check_extension = true;
+        options.max_extensions = 1;
+        let mut evaluator = SearchEvaluator::create_with_options(1, options);
+        assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 1.0);
+        assert!(evaluator.get_statistics().check_extensions > 0);
+
+        // Without budget, there are no extensions
+        options.max_extensions = 0;
+        let mut evaluator = SearchEvaluator::create_with_options(1, options);
+        assert_eq!(evaluator.evaluate(&mut board), 3.0);
+        assert_eq!(evaluator.get_statistics().check_extensions, 0);
+    }
+
+    #[test]
+    fn pawn_push_extension() {
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 5),
+            PieceKind::Pawn.colored(Color::White).at(3, 1),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let mut options = SearchOptions::plain();
+        options.pawn_push_extension = true;
+        options.max_extensions = 1;
+        let mut evaluator = SearchEvaluator::create_with_options(1, options);
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_statistics().pawn_push_extensions, 1);
+
+        // On a 6x6 board, the push to the fifth rank is the one before promotion
+        let mut board = Board::create_empty_with_geometry(Geometry::LOS_ALAMOS);
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 3),
+            PieceKind::Pawn.colored(Color::White).at(3, 1)]);
+
+        let mut evaluator = SearchEvaluator::create_with_options(1, options);
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_statistics().pawn_push_extensions, 1);
+    }
+
+    #[test]
+    fn single_reply_extension() {
+        // The white pawn only has one move
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 4),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let mut options = SearchOptions::plain();
+        options.single_reply_extension = true;
+        options.max_extensions = 1;
+        let mut evaluator = SearchEvaluator::create_with_options(1, options);
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_statistics().single_reply_extensions, 1);
+        assert_eq!(evaluator.get_best_line().moves.len(), 2);
+    }
+
+    #[test]
+    fn lazy_smp_search() {
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(6, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::Knight.colored(Color::White).at(3, 3),
+            PieceKind::King.colored(Color::Black).at(7, 7),
+            PieceKind::Pawn.colored(Color::Black).at(6, 6),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let mut options = SearchOptions::create();
+        options.threads = 4;
+        let mut evaluator = SearchEvaluator::create_with_options(4, options);
+        assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 1.0);
+        assert_eq!(evaluator.get_best_line().moves[0], TestMove::from_to(&board, Square::at(0, 0), Square::at(0, 7)));
+
+        // The board is left as it was
+        let mut expected_board = Board::create_empty();
+        expected_board.add_pieces(&[
+            PieceKind::King.colored(Color::White).at(6, 0),
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::Knight.colored(Color::White).at(3, 3),
+            PieceKind::King.colored(Color::Black).at(7, 7),
+            PieceKind::Pawn.colored(Color::Black).at(6, 6),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+        assert!(board.semantic_eq(&expected_board));
+    }
+
+    #[test]
+    fn transposition_table_mate_evaluations() {
+        // Mates are stored relative to the node and restored relative to the root
+        assert_eq!(evaluation_from_table(evaluation_to_table(MATE_SCORE - 5.0, 3), 1), MATE_SCORE - 3.0);
+        assert_eq!(evaluation_from_table(evaluation_to_table(-(MATE_SCORE - 5.0), 3), 1), -(MATE_SCORE - 3.0));
+        assert_eq!(evaluation_from_table(evaluation_to_table(4.0, 3), 1), 4.0);
+    }
+
+    #[test]
+    fn search_limits() {
+        let mut board = Board::create_populated();
+
+        // Without any limit, the search ends once it has been stopped
+        let mut evaluator = SearchEvaluator::create(0);
+        evaluator.set_limits(SearchLimits::infinite());
+        evaluator.get_stop_flag().store(true, Ordering::Relaxed);
+        evaluator.evaluate(&mut board);
+        assert!(!evaluator.get_best_line().moves.is_empty());
+
+        // The node limit is checked periodically
+        let mut evaluator = SearchEvaluator::create(0);
+        evaluator.set_limits(SearchLimits::nodes(5000));
+        evaluator.evaluate(&mut board);
+        assert!(evaluator.get_statistics().node_count < 5000 + LIMIT_CHECK_INTERVAL * 2);
+        assert!(!evaluator.get_best_line().moves.is_empty());
+
+        // Each search starts with fresh limits
+        let mut evaluator = SearchEvaluator::create(0);
+        evaluator.set_limits(SearchLimits::nodes(5000));
+        evaluator.evaluate(&mut board);
+        let first_nodes = evaluator.get_statistics().node_count;
+        evaluator.evaluate(&mut board);
+        assert!(evaluator.get_statistics().node_count - first_nodes >= 5000);
+    }
+
+    #[test]
+    fn search_limits_reused_evaluator() {
+        // A time limit that stopped the first search doesn't stop the next ones early
+        let mut board = Board::create_populated();
+        let move_time = Duration::from_millis(100);
+        let mut evaluator = SearchEvaluator::create(0);
+        evaluator.set_limits(SearchLimits::move_time(move_time));
+
+        for _ in 0..2 {
+            let start = Instant::now();
+            evaluator.evaluate(&mut board);
+            assert!(start.elapsed() >= move_time);
+            assert!(evaluator.get_best_line().moves.len() > 1);
+        }
+    }
+
+    #[test]
+    fn multi_pv() {
+        let mut board = Board::create_populated();
+
+        let mut single = SearchEvaluator::create(3);
+        let evaluation = single.evaluate(&mut board);
+
+        let mut options = SearchOptions::create();
+        options.multi_pv = 3;
+        let mut evaluator = SearchEvaluator::create_with_options(3, options);
+        let lines = evaluator.search_multi_pv(&mut board);
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].1, evaluation);
+        assert!(lines[0].1 >= lines[1].1 && lines[1].1 >= lines[2].1);
+        assert!(lines[0].0 != lines[1].0 && lines[1].0 != lines[2].0 && lines[0].0 != lines[2].0);
+        for (m, _, line) in lines.iter() {
+            assert_eq!(*m, line.moves[0]);
+        }
+    }
+
+    #[test]
+    fn multi_pv_more_lines_than_moves() {
+        // The white pawn has two moves
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::Pawn.colored(Color::White).at(0, 1),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let mut options = SearchOptions::create();
+        options.multi_pv = 5;
+        let mut evaluator = SearchEvaluator::create_with_options(2, options);
+        assert_eq!(evaluator.search_multi_pv(&mut board).len(), 2);
+    }
+
+    #[test]
+    fn search_info_to_uci_string() {
+        let mut board = Board::create_populated();
+        let line = Line::from_moves(vec!(
+            TestMove::from_to_en_passant(&board, Square::at(4, 1), Square::at(4, 3), Square::at(4, 2))));
+        board.apply_move(line.moves[0]);
+
+        let mut info = SearchInfo {
+            depth: 5,
+            multi_pv: 2,
+            evaluation: -0.5,
+            line,
+            node_count: 1234,
+            duration: Duration::from_millis(56),
+        };
+        assert_eq!(info.to_uci_string(false), "info depth 5 multipv 2 score cp -50 nodes 1234 time 56 pv e2e4");
+
+        // Mate in 2 moves, i.e. 3 plies
+        info.evaluation = MATE_SCORE - 3.0;
+        assert_eq!(info.to_uci_string(false), "info depth 5 multipv 2 score mate 2 nodes 1234 time 56 pv e2e4");
+
+        // Mated in 1 move, i.e. 2 plies
+        info.evaluation = -(MATE_SCORE - 2.0);
+        assert_eq!(info.to_uci_string(false), "info depth 5 multipv 2 score mate -1 nodes 1234 time 56 pv e2e4");
+    }
+
+    #[test]
+    fn search_mate_distance() {
+        assert_eq!(mate_distance(MATE_SCORE - 1.0), Some(1));
+        assert_eq!(mate_distance(MATE_SCORE - 3.0), Some(2));
+        assert_eq!(mate_distance(-(MATE_SCORE - 2.0)), Some(-1));
+        assert_eq!(mate_distance(-(MATE_SCORE - 4.0)), Some(-2));
+        assert_eq!(mate_distance(TABLEBASE_WIN_SCORE), None);
+        assert_eq!(mate_distance(-0.5), None);
+    }
+
+    #[test]
+    fn search_moves() {
+        let mut board = Board::create_empty();
+        board.add_pieces(&[
+            PieceKind::Rook.colored(Color::White).at(0, 0),
+            PieceKind::Pawn.colored(Color::White).at(7, 1),
+            PieceKind::Queen.colored(Color::Black).at(0, 7),
+            PieceKind::Pawn.colored(Color::Black).at(7, 6)]);
+
+        let capture = TestMove::from_to_capture(&board, Square::at(0, 0), Square::at(0, 7), PieceKind::Queen.colored(Color::Black).at(0, 7));
+        let pawn_push = TestMove::from_to(&board, Square::at(7, 1), Square::at(7, 2));
+
+        let mut evaluator = SearchEvaluator::create(3);
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_best_line().moves[0], capture);
+
+        // Only the whitelisted move is searched at the root
+        let mut evaluator = SearchEvaluator::create(3);
+        evaluator.set_root_moves(Some(vec!(pawn_push)), Vec::new());
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_best_line().moves[0], pawn_push);
+
+        // The excluded move isn't searched at the root, but still below it
+        let mut evaluator = SearchEvaluator::create(3);
+        evaluator.set_root_moves(None, vec!(capture));
+        let evaluation = evaluator.evaluate(&mut board);
+        assert!(evaluator.get_best_line().moves[0] != capture);
+        assert!(evaluation < 0.0);
+
+        // A restriction without legal moves is ignored
+        let mut evaluator = SearchEvaluator::create(3);
+        evaluator.set_root_moves(Some(Vec::new()), Vec::new());
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_best_line().moves[0], capture);
+
+        // A single whitelisted move isn't a single reply and isn't extended
+        let mut board = Board::create_populated();
+        let mut evaluator = SearchEvaluator::create(2);
+        evaluator.set_root_moves(Some(vec!(TestMove::from_to(&board, Square::at(4, 1), Square::at(4, 3)))), Vec::new());
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_statistics().single_reply_extensions, 0);
+
+        // Drops to the same square are told apart by the dropped piece
+        let mut board = Board::from_fen("k7/8/1K6/8/8/8/8/8[PQ] w - - 0 1").unwrap();
+        let pawn_drop = Move::drop(&board, PieceKind::Pawn, Square::at(1, 6));
+        let queen_drop = Move::drop(&board, PieceKind::Queen, Square::at(1, 6));
+        let mut evaluator = SearchEvaluator::create(3);
+        evaluator.set_root_moves(Some(vec!(pawn_drop)), Vec::new());
+        evaluator.evaluate(&mut board);
+        assert_eq!(evaluator.get_best_line().moves[0], pawn_drop);
+
+        let mut evaluator = SearchEvaluator::create(3);
+        evaluator.set_root_moves(None, vec!(queen_drop));
+        evaluator.evaluate(&mut board);
+        assert!(evaluator.get_best_line().moves[0] != queen_drop);
+    }
+    #[test]
+    fn tablebase_search() {
+        let tablebase: Arc<dyn Tablebase> = Arc::new(MaterialTablebase { max_pieces: 3 });
+
+        // The rook is worth a tablebase win rather than its material
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let mut evaluator = SearchEvaluator::create(2);
+        evaluator.set_tablebase(Some(tablebase.clone()));
+        assert_eq!(evaluator.evaluate(&mut board), TABLEBASE_WIN_SCORE - 1.0);
+        assert!(evaluator.get_statistics().tablebase_hits > 0);
+
+        // Evaluations are from White's perspective
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        let mut evaluator = SearchEvaluator::create(2);
+        evaluator.set_tablebase(Some(tablebase.clone()));
+        assert_eq!(evaluator.evaluate(&mut board), TABLEBASE_WIN_SCORE - 1.0);
+
+        // Only the root move that keeps the win is searched
+        let mut board = Board::from_fen("q3k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let mut evaluator = SearchEvaluator::create(2);
+        evaluator.set_tablebase(Some(tablebase.clone()));
+        assert_eq!(evaluator.evaluate(&mut board), TABLEBASE_WIN_SCORE - 1.0);
+        assert_eq!(evaluator.get_best_line().moves[0].coordinate_notation(), "a1a8");
+
+        // Only taking the rook draws, and a position too large for the tablebase that follows
+        // searches all root moves again
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        let mut options = SearchOptions::create();
+        options.multi_pv = 3;
+        let mut evaluator = SearchEvaluator::create_with_options(2, options);
+        evaluator.set_tablebase(Some(tablebase));
+        assert_eq!(evaluator.search_multi_pv(&mut board).len(), 1);
+        let mut board = Board::from_fen("4k3/7p/8/8/8/8/4r2P/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluator.search_multi_pv(&mut board).len(), 3);
+    }
+}
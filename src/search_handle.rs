@@ -0,0 +1,199 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread::JoinHandle;
+
+use super::board::*;
+use super::evaluation::*;
+use super::move_::*;
+use super::search::*;
+use super::Line;
+
+// Final result of a search started with start_search()
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // From White's perspective, as returned by DynamicEvaluator::evaluate()
+    pub evaluation: f32,
+    pub line: Line,
+    // Best lines as returned by SearchEvaluator::get_principal_variations(), one per MultiPV line
+    pub principal_variations: Vec<(Move, Score, Line)>,
+    pub statistics: DynamicEvaluatorStatistics,
+}
+
+// A search running on a background thread
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    thread: JoinHandle<SearchResult>,
+    info_receiver: Receiver<SearchInfo>,
+}
+
+impl SearchHandle {
+    // Ask the search to finish. The best move of the last completed iteration becomes the result.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    // The opponent played the expected move: Continue the ponder search as a normal search. The
+    // time spent pondering counts against the time limit.
+    pub fn ponder_hit(&self) {
+        self.ponder.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.ponder.load(Ordering::Relaxed)
+    }
+
+    // Flags behind stop() and ponder_hit(), for controlling the search from another thread
+    pub fn get_stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn get_ponder_flag(&self) -> Arc<AtomicBool> {
+        self.ponder.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    // Intermediate results, one per completed iteration. The channel disconnects once the search
+    // has finished.
+    pub fn get_info_receiver(&self) -> &Receiver<SearchInfo> {
+        &self.info_receiver
+    }
+
+    // Block until the search has finished and return its result
+    pub fn wait(self) -> SearchResult {
+        self.thread.join().unwrap()
+    }
+}
+
+pub fn start_search(board: &Board, limits: SearchLimits) -> SearchHandle {
+    start_search_with_options(board, limits, SearchOptions::create())
+}
+
+pub fn start_search_with_options(board: &Board, limits: SearchLimits, options: SearchOptions) -> SearchHandle {
+    start_search_with_root_moves(board, limits, options, None, Vec::new())
+}
+
+// Search only some of the root moves, see SearchEvaluator::set_root_moves()
+pub fn start_search_with_root_moves(board: &Board, limits: SearchLimits, options: SearchOptions,
+                                    search_moves: Option<Vec<Move>>, excluded_moves: Vec<Move>) -> SearchHandle {
+    let mut evaluator = SearchEvaluator::create_with_options(0, options);
+    evaluator.set_limits(limits);
+    evaluator.set_root_moves(search_moves, excluded_moves);
+    start_search_with_evaluator(board, evaluator)
+}
+
+// Search on the opponent's time. `board` is the position after the expected reply, e.g. the second
+// move of the last principal variation. The search runs without time limit until ponder_hit()
+// or stop() is called.
+pub fn start_ponder_search(board: &Board, limits: SearchLimits, options: SearchOptions) -> SearchHandle {
+    let mut evaluator = SearchEvaluator::create_with_options(0, options);
+    evaluator.set_limits(limits);
+    evaluator.get_ponder_flag().store(true, Ordering::Relaxed);
+    start_search_with_evaluator(board, evaluator)
+}
+
+// Run a search with an evaluator that has already been set up on a background thread
+pub fn start_search_with_evaluator(board: &Board, mut evaluator: SearchEvaluator) -> SearchHandle {
+    let (info_sender, info_receiver) = channel();
+    evaluator.set_info_sender(info_sender);
+
+    let stop = evaluator.get_stop_flag();
+    let ponder = evaluator.get_ponder_flag();
+    let mut board = board.clone();
+
+    let thread = std::thread::spawn(move || {
+        let evaluation = evaluator.evaluate(&mut board);
+        let line = evaluator.get_best_line().clone();
+
+        SearchResult {
+            best_move: line.moves.first().copied(),
+            evaluation,
+            line,
+            principal_variations: evaluator.get_principal_variations(),
+            statistics: evaluator.get_statistics(),
+        }
+    });
+
+    SearchHandle { stop, ponder, thread, info_receiver }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn search_handle_depth_limit() {
+        let board = Board::create_populated();
+        let handle = start_search(&board, SearchLimits::depth(3));
+
+        let infos: Vec<SearchInfo> = handle.get_info_receiver().iter().collect();
+        assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<u32>>(), vec!(1, 2, 3));
+
+        let result = handle.wait();
+        assert_eq!(result.best_move, Some(infos[2].line.moves[0]));
+    }
+
+    #[test]
+    fn search_handle_stop() {
+        let board = Board::create_populated();
+        let handle = start_search(&board, SearchLimits::infinite());
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        handle.stop();
+
+        let result = handle.wait();
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn search_handle_move_time() {
+        let board = Board::create_populated();
+        let handle = start_search(&board, SearchLimits::move_time(Duration::from_millis(100)));
+
+        let result = handle.wait();
+        assert!(result.best_move.is_some());
+        assert!(result.statistics.duration < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn search_handle_ponder() {
+        let board = Board::create_populated();
+        let handle = start_ponder_search(&board, SearchLimits::move_time(Duration::from_millis(50)), SearchOptions::create());
+
+        // The time limit doesn't apply while pondering
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(!handle.is_finished());
+        assert!(handle.is_pondering());
+
+        // The time spent pondering already exceeds the limit
+        handle.ponder_hit();
+        assert!(!handle.is_pondering());
+
+        let result = handle.wait();
+        assert!(result.best_move.is_some());
+        assert!(result.statistics.duration < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn search_handle_multi_pv() {
+        let board = Board::create_populated();
+        let mut options = SearchOptions::create();
+        options.multi_pv = 2;
+        let handle = start_search_with_options(&board, SearchLimits::depth(2), options);
+
+        let infos: Vec<SearchInfo> = handle.get_info_receiver().iter().collect();
+        assert_eq!(infos.iter().map(|info| (info.depth, info.multi_pv)).collect::<Vec<(u32, usize)>>(),
+                   vec!((1, 1), (1, 2), (2, 1), (2, 2)));
+
+        let result = handle.wait();
+        assert_eq!(result.principal_variations.len(), 2);
+        assert_eq!(result.best_move, Some(result.principal_variations[0].0));
+    }
+}
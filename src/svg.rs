@@ -0,0 +1,185 @@
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::piece_definition::*;
+use super::render::*;
+use super::Line;
+
+const LIGHT_SQUARE_COLOR: &str = "#f0d9b5";
+const DARK_SQUARE_COLOR: &str = "#b58863";
+const HIGHLIGHT_COLOR: &str = "#cdd16a";
+const ARROW_COLOR: &str = "#15781b";
+const COORDINATE_COLOR: &str = "#555555";
+
+// How render_svg() draws a board
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    // Width and height of a square in pixels
+    pub square_size: u32,
+    // Draw the board from Black's side
+    pub flipped: bool,
+    // Rank numbers on the left and file letters below the board
+    pub coordinates: bool,
+    // Squares drawn in the highlight color, e.g. the last move
+    pub highlights: Vec<Square>,
+    // Arrows from the first to the second square, e.g. the best move
+    pub arrows: Vec<(Square, Square)>,
+}
+
+impl SvgOptions {
+    pub fn create() -> SvgOptions {
+        SvgOptions { square_size: 45, flipped: false, coordinates: true, highlights: Vec::new(), arrows: Vec::new() }
+    }
+
+    // Add an arrow for `m`, drops only highlight their square
+    pub fn add_move(&mut self, m: &Move) {
+        if m.is_drop {
+            self.highlights.push(m.to);
+        } else {
+            self.arrows.push((m.from, m.to));
+        }
+    }
+
+    // Add an arrow for each move of `line`
+    pub fn add_line(&mut self, line: &Line) {
+        for m in line.moves.iter() {
+            self.add_move(m);
+        }
+    }
+}
+
+// Top left corner of `square` in pixels
+fn square_position(square: Square, geometry: &Geometry, options: &SvgOptions, margin: f32) -> (f32, f32) {
+    let size = options.square_size as f32;
+    let (column, row) = if options.flipped {
+        (geometry.files - 1 - square.file(), square.rank())
+    } else {
+        (square.file(), geometry.ranks - 1 - square.rank())
+    };
+    (margin + column as f32 * size, row as f32 * size)
+}
+
+// Draw `board` as a standalone SVG document. Pieces are text glyphs, so that no images are
+// needed.
+pub fn render_svg(board: &Board, options: &SvgOptions) -> String {
+    let geometry = board.geometry;
+    let size = options.square_size as f32;
+    let margin = if options.coordinates { size / 2.0 } else { 0.0 };
+    let width = margin + geometry.files as f32 * size;
+    let height = geometry.ranks as f32 * size + margin;
+
+    let mut svg = Vec::new();
+    svg.push(format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+                     width, height, width, height));
+    svg.push(format!("<defs><marker id=\"arrowhead\" markerWidth=\"4\" markerHeight=\"4\" refX=\"2\" refY=\"2\" orient=\"auto\">\
+                      <path d=\"M0,0 L4,2 L0,4 z\" fill=\"{}\"/></marker></defs>", ARROW_COLOR));
+
+    for rank in 0..geometry.ranks {
+        for file in 0..geometry.files {
+            let square = Square::at(file, rank);
+            let (x, y) = square_position(square, &geometry, options, margin);
+            let color = if (file + rank) % 2 == 0 { DARK_SQUARE_COLOR } else { LIGHT_SQUARE_COLOR };
+            svg.push(format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", x, y, size, size, color));
+            if options.highlights.contains(&square) {
+                svg.push(format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.8\"/>",
+                                 x, y, size, size, HIGHLIGHT_COLOR));
+            }
+        }
+    }
+
+    if options.coordinates {
+        let font_size = size / 3.0;
+        for rank in 0..geometry.ranks {
+            let (_, y) = square_position(Square::at(0, rank), &geometry, options, margin);
+            svg.push(format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                             margin / 2.0, y + size / 2.0, font_size, COORDINATE_COLOR, rank + 1));
+        }
+        for file in 0..geometry.files {
+            let (x, _) = square_position(Square::at(file, 0), &geometry, options, margin);
+            svg.push(format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                             x + size / 2.0, height - margin / 2.0, font_size, COORDINATE_COLOR, (b'a' + file as u8) as char));
+        }
+    }
+
+    // Both colors use the solid glyphs, filled white or black with a dark outline. Fairy pieces are
+    // drawn as their letters.
+    for piece in board.pieces() {
+        let (x, y) = square_position(piece.square(), &geometry, options, margin);
+        let glyph = piece_glyph(piece.piece().kind.colored(Color::Black)).unwrap_or(piece_token(board.definitions(), piece.piece()));
+        let fill = if piece.piece().color == Color::White { "#ffffff" } else { "#000000" };
+        svg.push(format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\" stroke=\"#000000\" stroke-width=\"1\" \
+                          text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                         x + size / 2.0, y + size / 2.0, size * 0.8, fill, glyph));
+    }
+
+    for (from, to) in options.arrows.iter() {
+        let (from_x, from_y) = square_position(*from, &geometry, options, margin);
+        let (to_x, to_y) = square_position(*to, &geometry, options, margin);
+        svg.push(format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" stroke-opacity=\"0.8\" \
+                          stroke-linecap=\"round\" marker-end=\"url(#arrowhead)\"/>",
+                         from_x + size / 2.0, from_y + size / 2.0, to_x + size / 2.0, to_y + size / 2.0, ARROW_COLOR, size / 8.0));
+    }
+
+    svg.push("</svg>".to_string());
+    return svg.join("\n") + "\n";
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn svg_render() {
+        let board = Board::from_fen("k1/1K w - - 0 1").unwrap();
+
+        let mut options = SvgOptions { square_size: 10, highlights: vec!(Square::at(1, 0)), ..SvgOptions::create() };
+        options.arrows.push((Square::at(1, 0), Square::at(0, 0)));
+        assert_eq!(render_svg(&board, &options), r##"<svg xmlns="http://www.w3.org/2000/svg" width="25" height="25" viewBox="0 0 25 25">
+<defs><marker id="arrowhead" markerWidth="4" markerHeight="4" refX="2" refY="2" orient="auto"><path d="M0,0 L4,2 L0,4 z" fill="#15781b"/></marker></defs>
+<rect x="5" y="10" width="10" height="10" fill="#b58863"/>
+<rect x="15" y="10" width="10" height="10" fill="#f0d9b5"/>
+<rect x="15" y="10" width="10" height="10" fill="#cdd16a" fill-opacity="0.8"/>
+<rect x="5" y="0" width="10" height="10" fill="#f0d9b5"/>
+<rect x="15" y="0" width="10" height="10" fill="#b58863"/>
+<text x="2.5" y="15" font-size="3.3333333" fill="#555555" text-anchor="middle" dominant-baseline="central">1</text>
+<text x="2.5" y="5" font-size="3.3333333" fill="#555555" text-anchor="middle" dominant-baseline="central">2</text>
+<text x="10" y="22.5" font-size="3.3333333" fill="#555555" text-anchor="middle" dominant-baseline="central">a</text>
+<text x="20" y="22.5" font-size="3.3333333" fill="#555555" text-anchor="middle" dominant-baseline="central">b</text>
+<text x="10" y="5" font-size="8" fill="#000000" stroke="#000000" stroke-width="1" text-anchor="middle" dominant-baseline="central">♚</text>
+<text x="20" y="15" font-size="8" fill="#ffffff" stroke="#000000" stroke-width="1" text-anchor="middle" dominant-baseline="central">♚</text>
+<line x1="20" y1="15" x2="10" y2="15" stroke="#15781b" stroke-width="1.25" stroke-opacity="0.8" stroke-linecap="round" marker-end="url(#arrowhead)"/>
+</svg>
+"##);
+
+        // Flipped, the a-file is on the right and the first rank at the top
+        let options = SvgOptions { square_size: 10, flipped: true, coordinates: false, ..SvgOptions::create() };
+        let svg = render_svg(&board, &options);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20\" height=\"20\" "));
+        assert!(svg.contains("<text x=\"5\" y=\"5\" font-size=\"8\" fill=\"#ffffff\""));
+        assert!(svg.contains("<text x=\"15\" y=\"15\" font-size=\"8\" fill=\"#000000\""));
+
+        // Fairy pieces are drawn with the letters of the board's definitions
+        let board = Board::from_fen_with_definitions("4k3/8/8/8/3E4/8/8/4K3 w - - 0 1", centaur_definitions()).unwrap();
+        assert!(render_svg(&board, &SvgOptions::create()).contains(">E</text>"));
+    }
+
+    #[test]
+    fn svg_line_arrows() {
+        let mut board = Board::create_populated();
+        let mut line = Line::empty();
+        for text in ["e2e4", "e7e5"] {
+            let m = crate::uci::parse_move(&mut board, text).unwrap();
+            board.apply_move(m);
+            line.moves.push(m);
+        }
+
+        let mut options = SvgOptions::create();
+        options.add_line(&line);
+        assert_eq!(options.arrows, vec!((Square::at(4, 1), Square::at(4, 3)), (Square::at(4, 6), Square::at(4, 4))));
+        let svg = render_svg(&board, &options);
+        assert_eq!(svg.matches("<line ").count(), 2);
+        assert_eq!(svg.matches("<text ").count(), 32 + 16);
+        assert!(svg.contains("<line x1=\"225\" y1=\"292.5\" x2=\"225\" y2=\"202.5\""));
+    }
+}
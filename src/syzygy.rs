@@ -0,0 +1,1156 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::board::*;
+use super::core::*;
+use super::move_generation::*;
+use super::tablebase::*;
+use crate::MoveUnmove;
+
+// Reader of Syzygy endgame tablebases: .rtbw files with the win/draw/loss outcome of each position
+// and .rtbz files with the distance to zeroing. The file format is only documented by its
+// reference probing code, which this follows closely.
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Tables exist for up to 7 pieces, kings included
+const MAX_TABLE_PIECES: usize = 7;
+
+// Flags of the first byte of a file
+const FILE_SPLIT: u8 = 1;
+const FILE_HAS_PAWNS: u8 = 2;
+
+// Flags of the compressed data of a table
+const FLAG_SIDE_TO_MOVE: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// Symbol of the right child of a Huffman symbol that is a leaf
+const LEAF_SYMBOL: usize = 0xFFF;
+
+// Pieces in the order they appear in table names, e.g. KRPvKR
+const NAME_ORDER: [PieceKind; 6] = [PieceKind::King, PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight, PieceKind::Pawn];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+// Pieces are identified by codes 1 (pawn) to 6 (king) in the files, plus 8 for black
+fn piece_code(piece: Piece) -> u8 {
+    piece.kind as u8 + 1 + if piece.color == Color::Black { 8 } else { 0 }
+}
+
+// Distance of a square from the a1-h8 diagonal, positive above it
+fn off_diagonal(square: usize) -> i32 {
+    (square >> 3) as i32 - (square & 7) as i32
+}
+
+fn read_u16_le(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([byte_at(bytes, offset), byte_at(bytes, offset + 1)])
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([byte_at(bytes, offset), byte_at(bytes, offset + 1), byte_at(bytes, offset + 2), byte_at(bytes, offset + 3)])
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([byte_at(bytes, offset), byte_at(bytes, offset + 1), byte_at(bytes, offset + 2), byte_at(bytes, offset + 3)])
+}
+
+fn read_u64_be(bytes: &[u8], offset: usize) -> u64 {
+    (read_u32_be(bytes, offset) as u64) << 32 | read_u32_be(bytes, offset + 4) as u64
+}
+
+// Reads past the end of a truncated file give zeros rather than a panic
+fn byte_at(bytes: &[u8], offset: usize) -> u8 {
+    bytes.get(offset).copied().unwrap_or(0)
+}
+
+// Tables that map piece placements to indices, shared by all tablebase files
+struct Indexing {
+    // Squares a2-h7 to 0..47, the pawn with the highest value leads
+    map_pawns: [usize; 64],
+    // Squares below the a1-h8 diagonal to 0..27
+    map_b1h1h7: [usize; 64],
+    // Squares of the a1-d1-d4 triangle to 0..9, diagonal squares last
+    map_a1d1d4: [usize; 64],
+    // The 462 placements of two kings with the first one in the a1-d1-d4 triangle
+    map_kk: [[usize; 64]; 10],
+    // binomial[k][n] is the number of ways to choose k of n elements
+    binomial: [[u64; 64]; 6],
+    lead_pawn_index: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Indexing {
+    fn create() -> Indexing {
+        let mut indexing = Indexing {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_index: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                indexing.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        // a1, b1, c1, d1, b2, c2, d2, c3, d3, d4
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in [0, 1, 2, 3, 9, 10, 11, 18, 19, 27].iter() {
+            if off_diagonal(*square) < 0 {
+                indexing.map_a1d1d4[*square] = code;
+                code += 1;
+            } else if off_diagonal(*square) == 0 {
+                diagonal.push(*square);
+            }
+        }
+        for square in diagonal.iter() {
+            indexing.map_a1d1d4[*square] = code;
+            code += 1;
+        }
+
+        // Kings next to each other are illegal. If the first king is on the diagonal, the second
+        // one is mirrored below it, placements with both on the diagonal come last.
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            // b1 is the only square mapped to 0 on purpose
+            for first in 0..=27 {
+                if indexing.map_a1d1d4[first] != idx || (idx == 0 && first != 1) {
+                    continue;
+                }
+
+                for second in 0..64 {
+                    let distance = ((first & 7) as i32 - (second & 7) as i32).abs().max(((first >> 3) as i32 - (second >> 3) as i32).abs());
+                    if distance <= 1 || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                        continue;
+                    }
+
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        indexing.map_kk[idx][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal.iter() {
+            indexing.map_kk[*idx][*second] = code;
+            code += 1;
+        }
+
+        indexing.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                indexing.binomial[k][n] = if k > 0 { indexing.binomial[k - 1][n - 1] } else { 0 } +
+                    if k < n { indexing.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // The leading pawn is the one closest to the edge and, on the same file, to the first rank
+        let mut available_squares = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..=6 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        indexing.map_pawns[square] = available_squares;
+                        indexing.map_pawns[square ^ 7] = available_squares - 1;
+                        available_squares = available_squares.saturating_sub(2);
+                    }
+                    indexing.lead_pawn_index[lead_pawns][square] = idx;
+                    idx += indexing.binomial[lead_pawns - 1][indexing.map_pawns[square]];
+                }
+                indexing.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+
+        return indexing;
+    }
+}
+
+fn indexing() -> &'static Indexing {
+    static INDEXING: OnceLock<Indexing> = OnceLock::new();
+    INDEXING.get_or_init(Indexing::create)
+}
+
+// The material of a table, described by its name, e.g. KRvK. The first side is called white.
+#[derive(Clone, Debug, PartialEq)]
+struct Material {
+    name: String,
+    piece_count: usize,
+    has_pawns: bool,
+    // Whether a side has a piece other than the king of which there is only one
+    has_unique_pieces: bool,
+    // Pawns of the leading side and of the other side
+    pawn_count: [usize; 2],
+    // Both sides have the same pieces
+    symmetric: bool,
+}
+
+impl Material {
+    fn parse(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let mut counts = [[0; 6]; 2];
+        for (side, pieces) in [white, black].iter().enumerate() {
+            if !pieces.starts_with('K') || pieces.len() > MAX_TABLE_PIECES {
+                return None;
+            }
+            for c in pieces.chars() {
+                counts[side][PieceKind::from_token(c).filter(|kind| *kind <= PieceKind::King && c.is_ascii_uppercase())? as usize] += 1;
+            }
+            if counts[side][PieceKind::King as usize] != 1 {
+                return None;
+            }
+        }
+
+        let piece_count = white.len() + black.len();
+        if piece_count > MAX_TABLE_PIECES {
+            return None;
+        }
+
+        let pawns = [counts[0][PieceKind::Pawn as usize], counts[1][PieceKind::Pawn as usize]];
+        let has_unique_pieces = counts.iter().any(|side| side[..PieceKind::King as usize].contains(&1));
+
+        // The side with fewer pawns leads, as that compresses better
+        let white_leads = pawns[1] == 0 || (pawns[0] > 0 && pawns[1] >= pawns[0]);
+
+        Some(Material {
+            name: name.to_string(),
+            piece_count,
+            has_pawns: pawns[0] + pawns[1] > 0,
+            has_unique_pieces,
+            pawn_count: if white_leads { [pawns[0], pawns[1]] } else { [pawns[1], pawns[0]] },
+            symmetric: white == black,
+        })
+    }
+
+    // Name of the pieces of `color` on `board`, e.g. KRP
+    fn side_name(board: &Board, color: Color) -> String {
+        let pieces = board.pieces();
+        let mut name = String::new();
+        for kind in NAME_ORDER.iter() {
+            for piece in pieces.iter().filter(|piece| piece.piece() == kind.colored(color)) {
+                name.push(piece.piece().kind.token().to_ascii_uppercase());
+            }
+        }
+        return name;
+    }
+}
+
+// Huffman-compressed values of one table, i.e. one side to move and, with pawns, one file of the
+// leading pawn. Offsets point into the bytes of the file.
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    block_size: usize,
+    // Every `span` values there is an entry in the sparse index
+    span: usize,
+    block_count: usize,
+    min_symbol_length: usize,
+    // Lowest symbol of each length, u16 each
+    lowest_symbol: usize,
+    // Left and right child of each symbol, 12 bits each
+    tree: usize,
+    // Number of values minus one of each block, u16 each
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    // base64[l] is the lowest symbol of length min_symbol_length + l, left-aligned
+    base64: Vec<u64>,
+    // Number of values minus one a symbol expands to
+    symbol_length: Vec<u8>,
+    pieces: [u8; MAX_TABLE_PIECES],
+    group_index: [u64; MAX_TABLE_PIECES + 1],
+    // Sizes of the groups of pieces encoded together, 0 terminated
+    group_length: [usize; MAX_TABLE_PIECES + 1],
+    // Offsets of the DTZ value maps of the four WDL outcomes
+    map_index: [usize; 4],
+}
+
+impl PairsData {
+    // Split the pieces into groups and compute the factor of each group's index. `order` gives
+    // the position of the leading group and of the other side's pawns in the encoding.
+    fn set_groups(&mut self, material: &Material, order: [usize; 2], file: usize) {
+        let indexing = indexing();
+
+        let mut n = 0;
+        let mut first_length: i32 = if material.has_pawns { 0 } else if material.has_unique_pieces { 3 } else { 2 };
+        self.group_length[0] = 1;
+        for i in 1..material.piece_count {
+            first_length -= 1;
+            if first_length > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_length[n] += 1;
+            } else {
+                n += 1;
+                self.group_length[n] = 1;
+            }
+        }
+        n += 1;
+        self.group_length[n] = 0;
+
+        let both_have_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut next = if both_have_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_length[0] - if both_have_pawns { self.group_length[1] } else { 0 };
+        let mut idx: u64 = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                self.group_index[0] = idx;
+                idx *= if material.has_pawns {
+                    indexing.lead_pawns_size[self.group_length[0]][file]
+                } else if material.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                self.group_index[1] = idx;
+                idx *= indexing.binomial[self.group_length[1]][48 - self.group_length[0]];
+            } else {
+                self.group_index[next] = idx;
+                idx *= indexing.binomial[self.group_length[next]][free_squares];
+                free_squares -= self.group_length[next];
+                next += 1;
+            }
+            k += 1;
+        }
+
+        self.group_index[n] = idx;
+    }
+
+    // Read the header of the compressed data at `offset`, returns the offset after it
+    fn set_sizes(&mut self, bytes: &[u8], mut offset: usize) -> Option<usize> {
+        self.flags = *bytes.get(offset)?;
+
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            // All positions have the same value, which is stored instead of the symbol length
+            self.min_symbol_length = *bytes.get(offset + 1)? as usize;
+            return Some(offset + 2);
+        }
+
+        let size_index = self.group_length.iter().position(|length| *length == 0)?;
+        let table_size = self.group_index[size_index];
+
+        self.block_size = 1 << *bytes.get(offset + 1)?;
+        self.span = 1 << *bytes.get(offset + 2)?;
+        self.sparse_index_size = table_size.div_ceil(self.span as u64) as usize;
+        let padding = *bytes.get(offset + 3)? as usize;
+        self.block_count = read_u32_le(bytes, offset + 4) as usize;
+        // Padded so that the sparse index never points beyond it
+        self.block_length_size = self.block_count + padding;
+        let max_symbol_length = *bytes.get(offset + 8)? as usize;
+        self.min_symbol_length = *bytes.get(offset + 9)? as usize;
+        if max_symbol_length < self.min_symbol_length || max_symbol_length > 64 || self.min_symbol_length == 0 {
+            return None;
+        }
+        offset += 10;
+
+        // Longer codes of the canonical Huffman code have lower values, so the lowest symbol of
+        // each length, left-aligned to 64 bits, tells the length of the code at the start of a
+        // buffer
+        self.lowest_symbol = offset;
+        let lengths = max_symbol_length - self.min_symbol_length + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            self.base64[i] = self.base64[i + 1]
+                .wrapping_add(read_u16_le(bytes, self.lowest_symbol + 2 * i) as u64)
+                .wrapping_sub(read_u16_le(bytes, self.lowest_symbol + 2 * (i + 1)) as u64) / 2;
+        }
+        for i in 0..lengths {
+            self.base64[i] = self.base64[i].checked_shl((64 - i - self.min_symbol_length) as u32).unwrap_or(0);
+        }
+        offset += 2 * lengths;
+
+        // Recursive pairing: symbols are either values or pairs of other symbols
+        let symbols = read_u16_le(bytes, offset) as usize;
+        offset += 2;
+        self.tree = offset;
+        if bytes.len() < self.tree + 3 * symbols {
+            return None;
+        }
+
+        self.symbol_length = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                self.symbol_length[symbol] = self.expand_symbol_length(bytes, symbol, &mut visited)?;
+            }
+        }
+
+        return Some(offset + 3 * symbols + (symbols & 1));
+    }
+
+    // Number of values minus one that `symbol` expands to
+    fn expand_symbol_length(&mut self, bytes: &[u8], symbol: usize, visited: &mut Vec<bool>) -> Option<u8> {
+        visited[symbol] = true;
+
+        let right = self.right_child(bytes, symbol);
+        if right == LEAF_SYMBOL {
+            return Some(0);
+        }
+        let left = self.left_child(bytes, symbol);
+        if left >= visited.len() || right >= visited.len() {
+            return None;
+        }
+
+        for child in [left, right].iter() {
+            if !visited[*child] {
+                self.symbol_length[*child] = self.expand_symbol_length(bytes, *child, visited)?;
+            }
+        }
+
+        return Some(self.symbol_length[left].wrapping_add(self.symbol_length[right]).wrapping_add(1));
+    }
+
+    // The value stored for a leaf symbol
+    fn left_child(&self, bytes: &[u8], symbol: usize) -> usize {
+        let offset = self.tree + 3 * symbol;
+        ((byte_at(bytes, offset + 1) as usize & 0xF) << 8) | byte_at(bytes, offset) as usize
+    }
+
+    fn right_child(&self, bytes: &[u8], symbol: usize) -> usize {
+        let offset = self.tree + 3 * symbol;
+        ((byte_at(bytes, offset + 2) as usize) << 4) | (byte_at(bytes, offset + 1) as usize >> 4)
+    }
+
+    fn block_length_at(&self, bytes: &[u8], block: usize) -> i64 {
+        read_u16_le(bytes, self.block_length + 2 * block) as i64
+    }
+
+    // The value with index `idx`
+    fn decompress(&self, bytes: &[u8], idx: u64) -> i32 {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return self.min_symbol_length as i32;
+        }
+
+        // The sparse index has the block and offset of the value in the middle of each span,
+        // from there walk to the block that contains idx
+        let sparse_entry = self.sparse_index + 6 * (idx / self.span as u64) as usize;
+        let mut block = read_u32_le(bytes, sparse_entry) as usize;
+        let mut offset = read_u16_le(bytes, sparse_entry + 4) as i64;
+        offset += (idx % self.span as u64) as i64 - (self.span / 2) as i64;
+
+        while offset < 0 {
+            block = block.wrapping_sub(1);
+            offset += self.block_length_at(bytes, block) + 1;
+        }
+        while offset > self.block_length_at(bytes, block) {
+            offset -= self.block_length_at(bytes, block) + 1;
+            block += 1;
+        }
+
+        // Decode the symbols of the block until the one that contains the value
+        let mut pointer = self.data + block * self.block_size;
+        let mut buffer = read_u64_be(bytes, pointer);
+        pointer += 8;
+        let mut buffer_size = 64;
+
+        let mut symbol;
+        loop {
+            let mut length = 0;
+            while buffer < self.base64[length] {
+                length += 1;
+            }
+
+            symbol = ((buffer - self.base64[length]) >> (64 - length - self.min_symbol_length)) as u16;
+            symbol = symbol.wrapping_add(read_u16_le(bytes, self.lowest_symbol + 2 * length));
+            let symbol_length = *self.symbol_length.get(symbol as usize).unwrap_or(&0) as i64;
+
+            if offset < symbol_length + 1 {
+                break;
+            }
+
+            offset -= symbol_length + 1;
+            length += self.min_symbol_length;
+            buffer = buffer.checked_shl(length as u32).unwrap_or(0);
+            buffer_size -= length;
+
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (read_u32_be(bytes, pointer) as u64) << (64 - buffer_size);
+                pointer += 4;
+            }
+        }
+
+        // Expand the pairs down to the value
+        let mut symbol = symbol as usize;
+        while *self.symbol_length.get(symbol).unwrap_or(&0) != 0 {
+            let left = self.left_child(bytes, symbol);
+            let left_length = *self.symbol_length.get(left).unwrap_or(&0) as i64;
+            if offset < left_length + 1 {
+                symbol = left;
+            } else {
+                offset -= left_length + 1;
+                symbol = self.right_child(bytes, symbol);
+            }
+        }
+
+        return self.left_child(bytes, symbol) as i32;
+    }
+}
+
+// Result of looking up a position in a table
+enum Lookup {
+    Value(i32),
+    // DTZ tables only store one side to move, and the position has the other one
+    OtherSide,
+}
+
+// A .rtbw or .rtbz file in memory with its decoded headers
+struct TableFile {
+    kind: TableKind,
+    bytes: Vec<u8>,
+    // Offset of the DTZ value maps
+    map: usize,
+    // Per side to move and file of the leading pawn, only the first file without pawns
+    pairs: Vec<Vec<PairsData>>,
+}
+
+impl TableFile {
+    fn load(path: &Path, kind: TableKind, material: &Material) -> Option<TableFile> {
+        let bytes = std::fs::read(path).ok()?;
+        let magic = if kind == TableKind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.len() < 5 || bytes[..4] != magic {
+            return None;
+        }
+
+        let mut table = TableFile { kind, bytes, map: 0, pairs: Vec::new() };
+        table.set(material)?;
+        return Some(table);
+    }
+
+    // Decode the headers of the file
+    fn set(&mut self, material: &Material) -> Option<()> {
+        let bytes = &self.bytes;
+        let flags = bytes[4];
+        if (flags & FILE_HAS_PAWNS != 0) != material.has_pawns || (flags & FILE_SPLIT != 0) == material.symmetric {
+            return None;
+        }
+        let mut offset = 5;
+
+        let sides = if self.kind == TableKind::Wdl && !material.symmetric { 2 } else { 1 };
+        let files = if material.has_pawns { 4 } else { 1 };
+        let both_have_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        self.pairs = vec![vec![PairsData::default(); files]; sides];
+
+        for file in 0..files {
+            let order_byte = *bytes.get(offset)? as usize;
+            let pawn_order_byte = if both_have_pawns { *bytes.get(offset + 1)? as usize } else { 0xFF };
+            let order = [[order_byte & 0xF, pawn_order_byte & 0xF], [order_byte >> 4, pawn_order_byte >> 4]];
+            offset += 1 + both_have_pawns as usize;
+
+            for k in 0..material.piece_count {
+                let pieces = *bytes.get(offset)?;
+                for side in 0..sides {
+                    self.pairs[side][file].pieces[k] = if side == 1 { pieces >> 4 } else { pieces & 0xF };
+                }
+                offset += 1;
+            }
+
+            for (side, order) in order.iter().enumerate().take(sides) {
+                self.pairs[side][file].set_groups(material, *order, file);
+            }
+        }
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                offset = self.pairs[side][file].set_sizes(bytes, offset)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            self.map = offset;
+            for file in 0..files {
+                let flags = self.pairs[0][file].flags;
+                if flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+
+                if flags & FLAG_WIDE != 0 {
+                    offset += offset & 1;
+                    for i in 0..4 {
+                        self.pairs[0][file].map_index[i] = (offset - self.map) / 2 + 1;
+                        offset += 2 * read_u16_le(bytes, offset) as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        self.pairs[0][file].map_index[i] = offset - self.map + 1;
+                        offset += *bytes.get(offset)? as usize + 1;
+                    }
+                }
+            }
+            offset += offset & 1;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                self.pairs[side][file].sparse_index = offset;
+                offset += 6 * self.pairs[side][file].sparse_index_size;
+            }
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                self.pairs[side][file].block_length = offset;
+                offset += 2 * self.pairs[side][file].block_length_size;
+            }
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                // Blocks are aligned to 64 bytes
+                offset = (offset + 0x3F) & !0x3F;
+                self.pairs[side][file].data = offset;
+                offset += self.pairs[side][file].block_count * self.pairs[side][file].block_size;
+            }
+        }
+
+        if offset > bytes.len() {
+            return None;
+        }
+        return Some(());
+    }
+
+    // Look up `board` with the colors swapped if `flip`. `wdl` is the outcome of the position,
+    // which DTZ tables need to interpret their values.
+    fn lookup(&self, material: &Material, board: &Board, flip: bool, wdl: Wdl) -> Lookup {
+        let indexing = indexing();
+
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let side = (flip as usize) ^ (board.side == Color::Black) as usize;
+
+        let mut pieces = board.pieces();
+        pieces.sort_by_key(|piece| piece.square().index());
+
+        let mut squares = [0usize; MAX_TABLE_PIECES];
+        let mut codes = [0u8; MAX_TABLE_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+
+        // Tables with pawns are split by the file of the leading pawn, which is mirrored to the
+        // queen side
+        let lead_code = self.pairs[0][0].pieces[0] ^ flip_color;
+        if material.has_pawns {
+            for piece in pieces.iter().filter(|piece| piece_code(piece.piece()) == lead_code) {
+                squares[size] = piece.square().index() ^ flip_squares;
+                size += 1;
+            }
+            lead_pawns = size;
+
+            let mut lead = 0;
+            for i in 1..lead_pawns {
+                if indexing.map_pawns[squares[i]] > indexing.map_pawns[squares[lead]] {
+                    lead = i;
+                }
+            }
+            squares.swap(0, lead);
+            file = (squares[0] & 7).min(7 - (squares[0] & 7));
+        }
+
+        if self.kind == TableKind::Dtz {
+            let flags = self.pairs[0][file].flags;
+            if (flags & FLAG_SIDE_TO_MOVE) as usize != side && (material.has_pawns || !material.symmetric) {
+                return Lookup::OtherSide;
+            }
+        }
+
+        for piece in pieces.iter() {
+            if material.has_pawns && piece_code(piece.piece()) == lead_code {
+                continue;
+            }
+            squares[size] = piece.square().index() ^ flip_squares;
+            codes[size] = piece_code(piece.piece()) ^ flip_color;
+            size += 1;
+        }
+
+        let pairs = &self.pairs[if self.kind == TableKind::Wdl { side } else { 0 }][file];
+
+        // Bring the pieces into the order of the table
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if pairs.pieces[i] == codes[j] {
+                    codes.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // Mirror the leading piece to the a-d files
+        if squares[0] & 7 > 3 {
+            for square in squares[..size].iter_mut() {
+                *square ^= 7;
+            }
+        }
+
+        let mut idx;
+        if material.has_pawns {
+            idx = indexing.lead_pawn_index[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|square| indexing.map_pawns[*square]);
+            for (i, square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += indexing.binomial[i][indexing.map_pawns[*square]];
+            }
+        } else {
+            // Without pawns the leading piece is also mirrored to the first ranks, and the first
+            // piece of the leading group off the a1-h8 diagonal below it
+            if squares[0] >> 3 > 3 {
+                for square in squares[..size].iter_mut() {
+                    *square ^= 56;
+                }
+            }
+
+            for i in 0..pairs.group_length[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in squares[i..size].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            idx = if material.has_unique_pieces {
+                // The three leading pieces are encoded together
+                let adjust1 = (squares[1] > squares[0]) as usize;
+                let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+
+                let rank = |square: usize| square >> 3;
+                if off_diagonal(squares[0]) != 0 {
+                    ((indexing.map_a1d1d4[squares[0]] * 63 + squares[1] - adjust1) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[1]) != 0 {
+                    ((6 * 63 + rank(squares[0]) * 28 + indexing.map_b1h1h7[squares[1]]) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[2]) != 0 {
+                    (6 * 63 * 62 + 4 * 28 * 62 + rank(squares[0]) * 7 * 28 + (rank(squares[1]) - adjust1) * 28 +
+                        indexing.map_b1h1h7[squares[2]]) as u64
+                } else {
+                    (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(squares[0]) * 7 * 6 + (rank(squares[1]) - adjust1) * 6 +
+                        rank(squares[2]) - adjust2) as u64
+                }
+            } else {
+                indexing.map_kk[indexing.map_a1d1d4[squares[0]]][squares[1]] as u64
+            };
+        }
+
+        // The remaining groups are encoded by their squares in ascending order, skipping the
+        // squares taken by earlier groups
+        idx *= pairs.group_index[0];
+        let mut group_start = pairs.group_length[0];
+        let mut remaining_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut next = 1;
+        while pairs.group_length[next] != 0 {
+            let group_end = group_start + pairs.group_length[next];
+            squares[group_start..group_end].sort();
+
+            let mut n = 0;
+            for i in 0..pairs.group_length[next] {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|other| square > **other).count();
+                n += indexing.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+
+            remaining_pawns = false;
+            idx += n * pairs.group_index[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        let value = pairs.decompress(&self.bytes, idx);
+        return Lookup::Value(match self.kind {
+            TableKind::Wdl => value - 2,
+            TableKind::Dtz => self.map_dtz(file, value, wdl),
+        });
+    }
+
+    // DTZ values are stored by frequency per outcome, and in moves rather than plies where that
+    // makes no difference
+    fn map_dtz(&self, file: usize, value: i32, wdl: Wdl) -> i32 {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+
+        let pairs = &self.pairs[0][file];
+        let mut value = value as usize;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let map_index = pairs.map_index[WDL_MAP[(wdl.value() + 2) as usize]];
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16_le(&self.bytes, self.map + 2 * (map_index + value)) as usize
+            } else {
+                byte_at(&self.bytes, self.map + map_index + value) as usize
+            };
+        }
+
+        let in_moves = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if in_moves {
+            value *= 2;
+        }
+
+        return value as i32 + 1;
+    }
+}
+
+// A table found on disk, loaded on first use
+struct TableEntry {
+    material: Material,
+    wdl_path: PathBuf,
+    dtz_path: PathBuf,
+    wdl: OnceLock<Option<TableFile>>,
+    dtz: OnceLock<Option<TableFile>>,
+}
+
+impl TableEntry {
+    fn table(&self, kind: TableKind) -> Option<&TableFile> {
+        match kind {
+            TableKind::Wdl => self.wdl.get_or_init(|| TableFile::load(&self.wdl_path, kind, &self.material)).as_ref(),
+            TableKind::Dtz => self.dtz.get_or_init(|| TableFile::load(&self.dtz_path, kind, &self.material)).as_ref(),
+        }
+    }
+}
+
+// Syzygy tablebase files in one or more directories
+pub struct SyzygyTablebase {
+    tables: HashMap<String, TableEntry>,
+    max_pieces: usize,
+}
+
+impl SyzygyTablebase {
+    // Find the tables in `paths`, directories separated like in the PATH environment variable.
+    // Files are only read when they are probed for the first time.
+    pub fn open(paths: &str) -> std::io::Result<SyzygyTablebase> {
+        let mut tables = HashMap::new();
+        let mut max_pieces = 0;
+
+        for directory in std::env::split_paths(paths) {
+            for file in std::fs::read_dir(&directory)? {
+                let path = file?.path();
+                if path.extension() != Some(OsStr::new("rtbw")) {
+                    continue;
+                }
+
+                let material = match path.file_stem().and_then(|stem| stem.to_str()).and_then(Material::parse) {
+                    Some(material) => material,
+                    None => continue
+                };
+                max_pieces = max_pieces.max(material.piece_count);
+
+                tables.entry(material.name.clone()).or_insert(TableEntry {
+                    material,
+                    wdl_path: path.clone(),
+                    dtz_path: path.with_extension("rtbz"),
+                    wdl: OnceLock::new(),
+                    dtz: OnceLock::new(),
+                });
+            }
+        }
+
+        return Ok(SyzygyTablebase { tables, max_pieces });
+    }
+
+    // Number of tables found
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    // The table with the material of `board` and whether the colors have to be swapped to look
+    // the position up in it. Tables are named with the stronger side first, and the symmetric
+    // ones only store white to move.
+    fn find(&self, board: &Board) -> Option<(&TableEntry, bool)> {
+        let white = Material::side_name(board, Color::White);
+        let black = Material::side_name(board, Color::Black);
+
+        if let Some(entry) = self.tables.get(&format!("{}v{}", white, black)) {
+            return Some((entry, entry.material.symmetric && board.side == Color::Black));
+        }
+        return self.tables.get(&format!("{}v{}", black, white)).map(|entry| (entry, true));
+    }
+
+    fn probe_table(&self, board: &Board, kind: TableKind, wdl: Wdl) -> Option<Lookup> {
+        // Two kings, no table needed
+        if board.piece_count() == 2 {
+            return Some(Lookup::Value(0));
+        }
+
+        let (entry, flip) = self.find(board)?;
+        let table = entry.table(kind)?;
+        return Some(table.lookup(&entry.material, board, flip, wdl));
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Option<Wdl> {
+        match self.probe_table(board, TableKind::Wdl, Wdl::Draw)? {
+            Lookup::Value(value) => Wdl::from_value(value),
+            Lookup::OtherSide => None
+        }
+    }
+
+    // The outcome of the position, and whether a capture, or with `zeroing_moves` a pawn move, is
+    // the best move. Tables store arbitrary values where the side to move wins by capturing, so
+    // the captures have to be tried. In that case DTZ tables can't be trusted either.
+    fn search(&self, board: &mut Board, zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = generate_moves(board);
+        let mut best = Wdl::Loss;
+        let mut move_count = 0;
+
+        for m in moves.iter() {
+            if m.capture.is_none() && (!zeroing_moves || m.piece_kind != PieceKind::Pawn) {
+                continue;
+            }
+            move_count += 1;
+
+            let mut move_unmove = MoveUnmove::apply_move(board, m);
+            let result = self.search(board, false);
+            move_unmove.revert_move(board);
+
+            let value = result?.0.negate();
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // With only zeroing moves, the table isn't needed and its value may be wrong
+        let only_zeroing_moves = move_count > 0 && move_count == moves.len();
+        let value = if only_zeroing_moves { best } else { self.probe_wdl_table(board)? };
+
+        if best >= value {
+            return Some((best, best > Wdl::Draw || only_zeroing_moves));
+        }
+        return Some((value, false));
+    }
+
+    fn probe_dtz_impl(&self, board: &mut Board) -> Option<i32> {
+        let (wdl, zeroing_is_best) = self.search(board, true)?;
+
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing_is_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        if let Lookup::Value(dtz) = self.probe_table(board, TableKind::Dtz, wdl)? {
+            let fifty_move_rule = if wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss { 100 } else { 0 };
+            return Some((dtz + fifty_move_rule) * wdl.value().signum());
+        }
+
+        // The table stores the other side to move, so find the best DTZ one ply deeper
+        let mut min_dtz = i32::MAX;
+        for m in generate_moves(board).iter() {
+            let zeroing = m.is_zeroing();
+
+            let mut move_unmove = MoveUnmove::apply_move(board, m);
+            // Zeroing moves reset the count, so take the DTZ before them
+            let dtz = if zeroing {
+                self.search(board, false).map(|(wdl, _)| -dtz_before_zeroing(wdl))
+            } else {
+                self.probe_dtz_impl(board).map(|dtz| -dtz)
+            };
+            let mates = dtz == Some(1) && is_check(board, board.side) && generate_moves(board).is_empty();
+            move_unmove.revert_move(board);
+
+            let mut dtz = dtz?;
+            if mates {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.value().signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        // Without legal moves the side to move is mated
+        return Some(if min_dtz == i32::MAX { -1 } else { min_dtz });
+    }
+}
+
+impl Tablebase for SyzygyTablebase {
+    fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn probe_wdl(&self, board: &mut Board) -> Option<Wdl> {
+        if !can_probe(self, board) {
+            return None;
+        }
+        return self.search(board, false).map(|(wdl, _)| wdl);
+    }
+
+    fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+        if !can_probe(self, board) {
+            return None;
+        }
+        return self.probe_dtz_impl(board);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::evaluation::*;
+    use crate::search::*;
+
+    #[test]
+    fn syzygy_indexing() {
+        let indexing = indexing();
+
+        // 462 placements of the kings, 0 to 461
+        assert_eq!(indexing.map_kk.iter().flat_map(|row| row.iter()).max(), Some(&461));
+        assert_eq!(indexing.map_a1d1d4[1], 0);
+        assert_eq!(indexing.map_a1d1d4[27], 9);
+        assert_eq!(indexing.map_b1h1h7[1], 0);
+        assert_eq!(indexing.map_b1h1h7[55], 27);
+
+        assert_eq!(indexing.binomial[2][5], 10);
+        assert_eq!(indexing.binomial[5][63], 7028847);
+
+        // a2 and h2 lead, then a3 and h3
+        assert_eq!(indexing.map_pawns[8], 47);
+        assert_eq!(indexing.map_pawns[15], 46);
+        assert_eq!(indexing.map_pawns[16], 45);
+        assert_eq!(indexing.map_pawns[55], 36);
+        assert_eq!(indexing.map_pawns[52], 0);
+        assert_eq!(indexing.lead_pawns_size[1], [6, 6, 6, 6]);
+    }
+
+    #[test]
+    fn syzygy_material() {
+        let material = Material::parse("KRPvKR").unwrap();
+        assert_eq!(material.piece_count, 5);
+        assert!(material.has_pawns && material.has_unique_pieces && !material.symmetric);
+        assert_eq!(material.pawn_count, [1, 0]);
+
+        let material = Material::parse("KPvKPP").unwrap();
+        assert_eq!(material.pawn_count, [1, 2]);
+        let material = Material::parse("KPPvKP").unwrap();
+        assert_eq!(material.pawn_count, [1, 2]);
+
+        let material = Material::parse("KNNvK").unwrap();
+        assert!(!material.has_unique_pieces);
+        assert!(Material::parse("KRvKR").unwrap().symmetric);
+
+        assert_eq!(Material::parse("KXvK"), None);
+        assert_eq!(Material::parse("RvK"), None);
+        assert_eq!(Material::parse("KQQQQQQvK"), None);
+
+        let board = Board::from_fen("8/8/8/8/8/2k5/1p6/KRN5 w - - 0 1").unwrap();
+        assert_eq!(Material::side_name(&board, Color::White), "KRN");
+        assert_eq!(Material::side_name(&board, Color::Black), "KP");
+    }
+
+    // The tables in tests/syzygy are the 3-piece endgames with a queen, a rook or a pawn, the
+    // minor-piece ones that underpromotions lead to, and the WDL table of KQvKR. They were written
+    // in the Syzygy format locally rather than downloaded; the files of the same names from
+    // https://tablebase.lichess.ovh/tables/standard/3-4-5/ can replace them as they are.
+    const TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+
+    #[test]
+    fn syzygy_probe() {
+        let tablebase = SyzygyTablebase::open(TABLES).unwrap();
+        assert_eq!(tablebase.max_pieces(), 4);
+
+        let probe = |fen: &str| {
+            let mut board = Board::from_fen(fen).unwrap();
+            (tablebase.probe_wdl(&mut board), tablebase.probe_dtz(&mut board))
+        };
+
+        assert_eq!(probe("8/8/8/8/8/8/8/K1k5 w - - 0 1"), (Some(Wdl::Draw), Some(0)));
+        assert_eq!(probe("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").0, Some(Wdl::Win));
+        assert_eq!(probe("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").0, Some(Wdl::Loss));
+        assert_eq!(probe("3qk3/8/8/8/8/8/8/4K3 w - - 0 1").0, Some(Wdl::Loss));
+        // The queen can be taken
+        assert_eq!(probe("8/8/8/8/8/2k5/8/Kq6 w - - 0 1"), (Some(Wdl::Draw), Some(0)));
+        // Mate in one
+        assert_eq!(probe("k7/8/1K6/8/8/8/8/7R w - - 0 1"), (Some(Wdl::Win), Some(1)));
+        assert_eq!(probe("R1k5/8/2K5/8/8/8/8/8 b - - 0 1"), (Some(Wdl::Loss), Some(-1)));
+        // Without zeroing moves, DTZ counts the plies to mate: mate in 6 and in 2 with the rook
+        // against 2 more moves to be mated
+        assert_eq!(probe("8/8/8/8/8/2k5/8/KQ6 w - - 0 1"), (Some(Wdl::Win), Some(11)));
+        assert_eq!(probe("7k/8/5K2/8/8/8/8/R7 w - - 0 1"), (Some(Wdl::Win), Some(3)));
+        assert_eq!(probe("7k/8/5K2/8/8/8/8/R7 b - - 0 1"), (Some(Wdl::Loss), Some(-4)));
+        // KPK depends on the king
+        assert_eq!(probe("8/8/8/8/8/k7/P7/K7 w - - 0 1").0, Some(Wdl::Draw));
+        assert_eq!(probe("8/8/4k3/8/4K3/8/4P3/8 w - - 0 1").0, Some(Wdl::Win));
+
+        // KQvKR is won unless the rook skewers the queen behind the king
+        assert_eq!(probe("8/8/8/8/8/2k5/6r1/3QK3 w - - 0 1").0, Some(Wdl::Win));
+        assert_eq!(probe("3Q4/8/8/8/3K4/8/8/k5r1 b - - 0 1").0, Some(Wdl::Win));
+        assert_eq!(probe("3Q4/8/8/8/3K4/8/8/k5r1 w - - 0 1").0, Some(Wdl::Win));
+    }
+
+    #[test]
+    fn syzygy_search() {
+        let syzygy = Arc::new(SyzygyTablebase::open(TABLES).unwrap());
+        let tablebase: Arc<dyn Tablebase> = syzygy.clone();
+
+        // The search finds the mate in 2 of syzygy_probe() on its own
+        let mut board = Board::from_fen("7k/8/5K2/8/8/8/8/R7 w - - 0 1").unwrap();
+        let mut evaluator = SearchEvaluator::create(3);
+        assert_eq!(mate_distance(evaluator.evaluate(&mut board)), Some(2));
+
+        let mut board = Board::from_fen("8/8/8/8/8/2k5/8/KQ6 w - - 0 1").unwrap();
+        let move_count = generate_moves(&mut board).len();
+
+        // Only the root moves that keep the queen are searched, and the positions after them
+        // are probed
+        let mut options = SearchOptions::create();
+        options.multi_pv = move_count;
+        let mut evaluator = SearchEvaluator::create_with_options(1, options);
+        evaluator.set_tablebase(Some(tablebase.clone()));
+        let winning_moves: Vec<String> = evaluator.search_multi_pv(&mut board).iter().map(|(m, _, _)| m.coordinate_notation()).collect();
+        assert!(winning_moves.len() < move_count);
+        assert!(!winning_moves.contains(&"b1c2".to_string()));
+        assert!(evaluator.get_statistics().tablebase_hits > 0);
+
+        // Close to the 50-move rule, only the moves that mate soonest are left
+        board.halfmove_clock = 95;
+        let mut evaluator = SearchEvaluator::create_with_options(1, options);
+        evaluator.set_tablebase(Some(tablebase));
+        let fastest_moves = evaluator.search_multi_pv(&mut board);
+        assert!(fastest_moves.len() < winning_moves.len());
+        for (m, _, _) in fastest_moves.iter() {
+            let mut move_unmove = MoveUnmove::apply_move(&mut board, m);
+            assert_eq!(syzygy.probe_dtz(&mut board), Some(-10));
+            move_unmove.revert_move(&mut board);
+        }
+    }
+}
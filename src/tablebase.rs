@@ -0,0 +1,178 @@
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::move_generation::*;
+use super::variant::*;
+use crate::MoveUnmove;
+
+// Outcome of a position for the side to move with perfect play. Cursed wins and blessed losses
+// are wins and losses that the 50-move rule turns into draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    // Inverse of value()
+    pub fn from_value(value: i32) -> Option<Wdl> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None
+        }
+    }
+
+    // -2 for a loss up to 2 for a win, as stored in Syzygy tables
+    pub fn value(self) -> i32 {
+        self as i32 - 2
+    }
+
+    // The outcome for the other side
+    pub fn negate(self) -> Wdl {
+        Wdl::from_value(-self.value()).unwrap()
+    }
+}
+
+// Endgame tablebase with perfect information about positions with few pieces, see syzygy.rs
+pub trait Tablebase: Send + Sync {
+    // Largest number of pieces, kings included, of the positions in the tablebase
+    fn max_pieces(&self) -> usize;
+
+    // Outcome for the side to move, None if the position isn't in the tablebase
+    fn probe_wdl(&self, board: &mut Board) -> Option<Wdl>;
+
+    // Distance to zeroing, i.e. to the next capture or pawn move of the winning side, in plies.
+    // Positive if the side to move wins, negative if it loses and 0 for draws. Wins and losses
+    // beyond the 50-move rule count 100 plies more.
+    fn probe_dtz(&self, board: &mut Board) -> Option<i32>;
+}
+
+// Whether the position can be in `tablebase`. Tablebases don't know about castling or variants.
+pub fn can_probe(tablebase: &dyn Tablebase, board: &Board) -> bool {
+    board.variant == Variant::Standard && board.geometry == Geometry::STANDARD && board.castle_rights == BoardCastleRights::none() &&
+        board.piece_count() <= tablebase.max_pieces()
+}
+
+// DTZ of a position in which a capture or pawn move with outcome `wdl` is made
+pub fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+// Rank of a root move with distance to zeroing `dtz` counted from the root, higher is better.
+// Wins that can be converted before the 50-move rule strikes rank equally, as do losses that
+// can't be saved by it. The others rank by how close the 50-move draw is.
+pub fn root_rank(dtz: i32, halfmove_clock: u32) -> i32 {
+    let halfmove_clock = halfmove_clock as i32;
+    if dtz > 0 {
+        if dtz + halfmove_clock <= 99 { 1000 } else { 1000 - (dtz + halfmove_clock) }
+    } else if dtz < 0 {
+        if -dtz * 2 + halfmove_clock < 100 { -1000 } else { -1000 + (-dtz + halfmove_clock) }
+    } else {
+        0
+    }
+}
+
+// Rank each of the root `moves` with the tablebase's DTZ and the board's halfmove clock, see
+// root_rank(). None if a probe fails.
+pub fn rank_root_moves(tablebase: &dyn Tablebase, board: &mut Board, moves: &[Move]) -> Option<Vec<i32>> {
+    let halfmove_clock = board.halfmove_clock;
+    let mut ranks = Vec::with_capacity(moves.len());
+
+    for m in moves.iter() {
+        let mut move_unmove = MoveUnmove::apply_move(board, m);
+
+        let dtz = if m.is_zeroing() {
+            tablebase.probe_wdl(board).map(|wdl| dtz_before_zeroing(wdl.negate()))
+        } else {
+            // One ply further from zeroing than the position after the move
+            tablebase.probe_dtz(board).map(|dtz| -dtz - dtz.signum())
+        };
+
+        // A mate is as good as it gets
+        let mates = dtz == Some(2) && is_check(board, board.side) && generate_moves(board).is_empty();
+        move_unmove.revert_move(board);
+
+        let dtz = if mates { 1 } else { dtz? };
+        ranks.push(root_rank(dtz, halfmove_clock));
+    }
+
+    return Some(ranks);
+}
+
+// The root `moves` with the best tablebase rank: the ones that keep a win within the 50-move rule,
+// or else the ones that hold the draw, etc. None if a probe fails.
+pub fn filter_root_moves(tablebase: &dyn Tablebase, board: &mut Board, moves: &[Move]) -> Option<Vec<Move>> {
+    let ranks = rank_root_moves(tablebase, board, moves)?;
+    let best_rank = *ranks.iter().max()?;
+
+    return Some(moves.iter().zip(ranks.iter())
+        .filter(|(_, rank)| **rank == best_rank)
+        .map(|(m, _)| *m)
+        .collect());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn wdl_values() {
+        for value in -2..=2 {
+            assert_eq!(Wdl::from_value(value).unwrap().value(), value);
+        }
+        assert_eq!(Wdl::from_value(3), None);
+        assert_eq!(Wdl::Win.negate(), Wdl::Loss);
+        assert_eq!(Wdl::CursedWin.negate(), Wdl::BlessedLoss);
+        assert_eq!(Wdl::Draw.negate(), Wdl::Draw);
+        assert!(Wdl::Win > Wdl::CursedWin && Wdl::BlessedLoss > Wdl::Loss);
+    }
+
+    #[test]
+    fn tablebase_root_rank() {
+        assert_eq!(root_rank(5, 0), 1000);
+        assert_eq!(root_rank(5, 94), 1000);
+        // The win can't be converted in time anymore
+        assert_eq!(root_rank(60, 60), 880);
+        assert_eq!(root_rank(0, 60), 0);
+        assert_eq!(root_rank(-10, 0), -1000);
+        // The 50-move rule may save the game
+        assert_eq!(root_rank(-60, 0), -940);
+        assert!(root_rank(-60, 50) > root_rank(-60, 0));
+    }
+
+    #[test]
+    fn tablebase_root_filter() {
+        let tablebase = MaterialTablebase { max_pieces: 4 };
+
+        // Only taking the queen wins, all other moves lose
+        let mut board = Board::from_fen("q3k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let moves = generate_moves(&mut board);
+        let ranks = rank_root_moves(&tablebase, &mut board, &moves).unwrap();
+        assert_eq!(ranks.iter().filter(|rank| **rank == 1000).count(), 1);
+        assert!(ranks.iter().all(|rank| *rank == 1000 || *rank == -1000));
+
+        let filtered = filter_root_moves(&tablebase, &mut board, &moves).unwrap();
+        assert_eq!(filtered.iter().map(|m| m.coordinate_notation()).collect::<Vec<String>>(), vec!("a1a8"));
+
+        // Too many pieces and castling rights are beyond tablebases
+        let mut board = Board::from_fen("q3k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert!(!can_probe(&tablebase, &board));
+        assert_eq!(tablebase.probe_wdl(&mut board), None);
+        let board = Board::from_fen("q3k3/p7/8/8/8/8/P7/R3K3 w - - 0 1").unwrap();
+        assert!(!can_probe(&tablebase, &board));
+    }
+}
@@ -1,6 +1,8 @@
 use super::core::*;
 use super::board::*;
 use super::move_::*;
+use super::piece_definition::*;
+use super::tablebase::*;
 
 pub struct TestMove {}
 
@@ -30,4 +32,46 @@ impl TestMove {
     pub fn from_to_capture(board: &Board, from: Square, to: Square, capture: PieceOnBoard) -> Move {
         Move::from_to_capture(board, board.piece_at(from).unwrap().kind, from, to, capture)
     }
-}
\ No newline at end of file
+}
+
+// The built-in definitions with the Archbishop turned into a Centaur, which steps like a king and
+// jumps like a knight, and written with another letter
+pub fn centaur_definitions() -> &'static PieceDefinitions {
+    let mut definitions = PIECE_DEFINITIONS;
+    definitions[PieceKind::Archbishop as usize] = PieceDefinition { token: 'e', value: 6.5, sliders: &[],
+        leapers: &[Pattern::Straight, Pattern::Diagonal, Pattern::Knight], square_table: None };
+    Box::leak(Box::new(definitions))
+}
+
+// Tablebase for tests that judges positions by material: the side with more wins
+pub struct MaterialTablebase {
+    pub max_pieces: usize,
+}
+
+impl MaterialTablebase {
+    fn material(board: &Board, color: Color) -> f32 {
+        board.pieces().iter()
+            .filter(|piece| piece.piece().color == color && piece.piece().kind != PieceKind::King)
+            .map(|piece| piece.piece().kind.value())
+            .sum()
+    }
+}
+
+impl Tablebase for MaterialTablebase {
+    fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn probe_wdl(&self, board: &mut Board) -> Option<Wdl> {
+        if !can_probe(self, board) {
+            return None;
+        }
+
+        let difference = MaterialTablebase::material(board, board.side) - MaterialTablebase::material(board, board.side.switch());
+        return Some(if difference > 0.0 { Wdl::Win } else if difference < 0.0 { Wdl::Loss } else { Wdl::Draw });
+    }
+
+    fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+        return self.probe_wdl(board).map(|wdl| 10 * wdl.value().signum());
+    }
+}
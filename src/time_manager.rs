@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+// Number of moves the remaining time is split into when the time control doesn't say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+// Time kept in reserve for communication with the GUI, so that the clock doesn't run out
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+// Shortest time a move is given, even with almost no time left
+const MIN_MOVE_TIME: Duration = Duration::from_millis(10);
+
+// The clock of the side to move, as given by UCI's `go wtime btime winc binc movestogo`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub remaining: Duration,
+    pub increment: Duration,
+    // Moves until the next time control, None for sudden death
+    pub moves_to_go: Option<u32>,
+}
+
+impl TimeControl {
+    pub fn create(remaining: Duration, increment: Duration, moves_to_go: Option<u32>) -> TimeControl {
+        TimeControl { remaining, increment, moves_to_go }
+    }
+
+    // Time to spend on the next move. It is measured from the start of the search, so that a
+    // ponder search which turns into a normal search has already used up some of it.
+    pub fn allocate(&self) -> Duration {
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let available = self.remaining.saturating_sub(MOVE_OVERHEAD);
+
+        let move_time = self.remaining / moves_to_go + self.increment * 3 / 4;
+        return move_time.min(available).max(MIN_MOVE_TIME);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn time_control_allocate() {
+        // Sudden death
+        let time_control = TimeControl::create(Duration::from_secs(60), Duration::from_secs(0), None);
+        assert_eq!(time_control.allocate(), Duration::from_secs(2));
+
+        // Increment
+        let time_control = TimeControl::create(Duration::from_secs(60), Duration::from_secs(4), None);
+        assert_eq!(time_control.allocate(), Duration::from_secs(5));
+
+        // Last move before the time control
+        let time_control = TimeControl::create(Duration::from_secs(10), Duration::from_secs(0), Some(1));
+        assert_eq!(time_control.allocate(), Duration::from_millis(9950));
+
+        // Almost out of time
+        let time_control = TimeControl::create(Duration::from_millis(20), Duration::from_secs(0), None);
+        assert_eq!(time_control.allocate(), Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::mpsc::channel;
+use std::sync::Mutex;
+
+use super::match_runner::*;
+use super::pgn::*;
+
+// Who plays whom in a tournament
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TournamentKind {
+    // Everyone plays everyone else
+    RoundRobin,
+    // The first participant plays everyone else, who don't play each other
+    Gauntlet,
+}
+
+// Creates a player in the thread that plays its games
+pub type PlayerFactory = Box<dyn Fn() -> Result<Box<dyn MatchPlayer>, String> + Send + Sync>;
+
+pub struct Participant {
+    pub name: String,
+    factory: PlayerFactory,
+}
+
+impl Participant {
+    pub fn create(name: &str, factory: PlayerFactory) -> Participant {
+        Participant { name: name.to_string(), factory }
+    }
+}
+
+// A game of the schedule. Participants are indices into the tournament's participants. `round`
+// counts the games of the schedule from 1 and becomes the game's Round tag.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pairing {
+    pub round: usize,
+    pub white: usize,
+    pub black: usize,
+    pub opening: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TournamentGame {
+    pub pairing: Pairing,
+    pub result: GameResult,
+}
+
+// Games between many participants from a list of openings, each opening played by each pair with
+// both colors. The games are played in parallel, each thread keeping its own players.
+pub struct Tournament {
+    participants: Vec<Participant>,
+    kind: TournamentKind,
+    openings: Vec<Opening>,
+    limits: MatchLimits,
+    adjudication: Adjudication,
+    rounds: usize,
+    concurrency: usize,
+    event: String,
+}
+
+impl Tournament {
+    pub fn create(participants: Vec<Participant>, kind: TournamentKind, openings: Vec<Opening>, limits: MatchLimits) -> Tournament {
+        let concurrency = std::thread::available_parallelism().map_or(1, |count| count.get());
+        Tournament {
+            participants,
+            kind,
+            openings,
+            limits,
+            adjudication: Adjudication::create(),
+            rounds: 1,
+            concurrency,
+            event: "Tournament".to_string(),
+        }
+    }
+
+    pub fn set_adjudication(&mut self, adjudication: Adjudication) {
+        self.adjudication = adjudication;
+    }
+
+    // How often the whole set of openings is played
+    pub fn set_rounds(&mut self, rounds: usize) {
+        self.rounds = rounds;
+    }
+
+    // Number of games played at the same time
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1);
+    }
+
+    // Event tag of the games
+    pub fn set_event(&mut self, event: &str) {
+        self.event = event.to_string();
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.participants.iter().map(|participant| participant.name.clone()).collect()
+    }
+
+    // All games of the tournament, in the order they are started
+    pub fn schedule(&self) -> Vec<Pairing> {
+        let count = self.participants.len();
+        let mut pairs = Vec::new();
+        for first in 0..count {
+            for second in (first + 1)..count {
+                if self.kind == TournamentKind::RoundRobin || first == 0 {
+                    pairs.push((first, second));
+                }
+            }
+        }
+
+        let mut schedule = Vec::new();
+        for _ in 0..self.rounds {
+            for opening in 0..self.openings.len().max(1) {
+                for (first, second) in pairs.iter() {
+                    for (white, black) in [(*first, *second), (*second, *first)] {
+                        schedule.push(Pairing { round: schedule.len() + 1, white, black, opening });
+                    }
+                }
+            }
+        }
+        return schedule;
+    }
+
+    // Games of an interrupted run of this tournament that were written to `pgn`. Unfinished games
+    // are left out so that they are played again.
+    pub fn completed_games(&self, pgn: &str) -> Result<Vec<TournamentGame>, String> {
+        let schedule = self.schedule();
+        let names = self.names();
+
+        let mut games = Vec::new();
+        for game in parse_pgn(pgn)? {
+            if game.result == GameResult::Unknown {
+                continue;
+            }
+            let pairing = game.tag("Round").and_then(|round| round.parse::<usize>().ok())
+                .and_then(|round| schedule.get(round.wrapping_sub(1)))
+                .filter(|pairing| game.tag("White") == Some(&names[pairing.white]) && game.tag("Black") == Some(&names[pairing.black]))
+                .ok_or(format!("Game {} isn't part of this tournament", game.tag("Round").unwrap_or("?")))?;
+            games.push(TournamentGame { pairing: *pairing, result: game.result });
+        }
+        return Ok(games);
+    }
+
+    // Play the games of the schedule that aren't in `completed`, appending each to `pgn` as it
+    // finishes and writing a line per game to `log`
+    pub fn run(&self, completed: &[TournamentGame], pgn: &mut dyn Write, log: &mut dyn Write) -> Result<TournamentReport, String> {
+        let schedule = self.schedule();
+        let mut games: Vec<TournamentGame> = completed.to_vec();
+        let pending: Vec<Pairing> = schedule.iter().filter(|pairing| !completed.iter().any(|game| game.pairing.round == pairing.round)).copied().collect();
+
+        let queue = Mutex::new(pending.into_iter());
+        let (sender, receiver) = channel();
+        let mut error = None;
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.concurrency {
+                let sender = sender.clone();
+                let queue = &queue;
+                scope.spawn(move || {
+                    let mut players: HashMap<usize, Box<dyn MatchPlayer>> = HashMap::new();
+                    loop {
+                        let pairing = match queue.lock().unwrap().next() {
+                            Some(pairing) => pairing,
+                            None => return,
+                        };
+                        let result = self.play(pairing, &mut players);
+                        if sender.send((pairing, result)).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            for (pairing, result) in receiver {
+                let record = match result {
+                    Ok(record) => record,
+                    Err(message) => {
+                        // Let the other games finish but start no new ones
+                        queue.lock().unwrap().by_ref().for_each(drop);
+                        if error.is_none() {
+                            error = Some(message);
+                        }
+                        continue;
+                    }
+                };
+
+                let mut game = record.to_pgn();
+                game.tags.insert(0, ("Event".to_string(), self.event.clone()));
+                game.tags.insert(1, ("Round".to_string(), pairing.round.to_string()));
+                if writeln!(pgn, "{}", game.to_pgn()).and_then(|_| pgn.flush()).is_err() {
+                    error = Some("Can't write the PGN".to_string());
+                }
+
+                games.push(TournamentGame { pairing, result: record.result });
+                let _ = writeln!(log, "Game {} of {}: {} - {} {} ({})", games.len(), schedule.len(), record.white, record.black,
+                                 record.result.token(), record.reason);
+            }
+        });
+
+        if let Some(message) = error {
+            return Err(message);
+        }
+        games.sort_by_key(|game| game.pairing.round);
+        return Ok(TournamentReport { names: self.names(), games });
+    }
+
+    // Play the game of `pairing` with the thread's `players`, starting them as needed
+    fn play(&self, pairing: Pairing, players: &mut HashMap<usize, Box<dyn MatchPlayer>>) -> Result<GameRecord, String> {
+        for participant in [pairing.white, pairing.black] {
+            if let Entry::Vacant(entry) = players.entry(participant) {
+                entry.insert((self.participants[participant].factory)()?);
+            }
+        }
+
+        let mut white = players.remove(&pairing.white).unwrap();
+        let mut black = players.remove(&pairing.black).unwrap();
+        let opening = self.openings.get(pairing.opening).cloned()
+            .unwrap_or(Opening { board: super::board::Board::create_populated(), moves: Vec::new() });
+        let record = play_game(white.as_mut(), black.as_mut(), &opening, &self.limits, &self.adjudication);
+        players.insert(pairing.white, white);
+        players.insert(pairing.black, black);
+
+        // Several participants may be the same engine with different settings
+        let mut record = record?;
+        record.white = self.participants[pairing.white].name.clone();
+        record.black = self.participants[pairing.black].name.clone();
+        return Ok(record);
+    }
+}
+
+// Games played in a tournament and the standings they make
+#[derive(Clone, Debug, PartialEq)]
+pub struct TournamentReport {
+    pub names: Vec<String>,
+    pub games: Vec<TournamentGame>,
+}
+
+// Virtual draws between each pair of opponents that keep the ratings finite, as in BayesElo
+const RATING_PRIOR: f32 = 2.0;
+
+impl TournamentReport {
+    // Points of `player` against `opponent` and the number of games they played
+    pub fn score(&self, player: usize, opponent: usize) -> (f32, u32) {
+        let mut points = 0.0;
+        let mut games = 0;
+        for game in self.games.iter() {
+            let pairing = game.pairing;
+            let white_points = match game.result {
+                GameResult::WhiteWins => 1.0,
+                GameResult::BlackWins => 0.0,
+                GameResult::Draw => 0.5,
+                GameResult::Unknown => continue,
+            };
+            if (pairing.white, pairing.black) == (player, opponent) {
+                points += white_points;
+                games += 1;
+            } else if (pairing.white, pairing.black) == (opponent, player) {
+                points += 1.0 - white_points;
+                games += 1;
+            }
+        }
+        return (points, games);
+    }
+
+    // Points and games of `player` against everyone
+    pub fn total(&self, player: usize) -> (f32, u32) {
+        (0..self.names.len()).filter(|opponent| *opponent != player)
+            .map(|opponent| self.score(player, opponent))
+            .fold((0.0, 0), |(points, games), (more_points, more_games)| (points + more_points, games + more_games))
+    }
+
+    // Elo ratings with an average of 0. They maximize the likelihood of the results under the
+    // Bradley-Terry model, counting draws as half a win and adding RATING_PRIOR virtual draws
+    // between opponents, by minorization-maximization.
+    pub fn ratings(&self) -> Vec<f32> {
+        let count = self.names.len();
+        let mut points = vec![vec![0.0; count]; count];
+        let mut games = vec![vec![0.0; count]; count];
+        for player in 0..count {
+            for opponent in 0..count {
+                let (score, played) = if player == opponent { (0.0, 0) } else { self.score(player, opponent) };
+                if played > 0 {
+                    points[player][opponent] = score + RATING_PRIOR / 2.0;
+                    games[player][opponent] = played as f32 + RATING_PRIOR;
+                }
+            }
+        }
+
+        let mut strengths = vec![1.0f32; count];
+        for _ in 0..1000 {
+            let mut next = strengths.clone();
+            for player in 0..count {
+                let won: f32 = points[player].iter().sum();
+                let expected: f32 = (0..count).map(|opponent| games[player][opponent] / (strengths[player] + strengths[opponent])).sum();
+                if expected > 0.0 {
+                    next[player] = won / expected;
+                }
+            }
+
+            // The scale is arbitrary, keep the geometric mean at 1
+            let mean = (next.iter().map(|strength| strength.ln()).sum::<f32>() / count as f32).exp();
+            let change = next.iter().zip(strengths.iter()).map(|(a, b)| (a / mean - b).abs()).fold(0.0, f32::max);
+            strengths = next.iter().map(|strength| strength / mean).collect();
+            if change < 1e-6 {
+                break;
+            }
+        }
+
+        return strengths.iter().map(|strength| 400.0 * strength.log10()).collect();
+    }
+
+    // Table of the points each participant (row) scored against each other one (column), ordered
+    // by rating
+    pub fn crosstable(&self) -> String {
+        let ratings = self.ratings();
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        order.sort_by(|a, b| ratings[*b].total_cmp(&ratings[*a]));
+
+        let width = self.names.iter().map(|name| name.len()).max().unwrap_or(0).max(4);
+        let mut lines = Vec::new();
+        let mut header = format!("{:>3} {:<width$} {:>6} {:>9}", "#", "Name", "Elo", "Points");
+        for rank in 1..=order.len() {
+            header.push_str(&format!(" {:>7}", rank));
+        }
+        lines.push(header);
+
+        for (rank, player) in order.iter().enumerate() {
+            let (points, games) = self.total(*player);
+            let mut line = format!("{:>3} {:<width$} {:>6.0} {:>9}", rank + 1, self.names[*player], ratings[*player], format!("{}/{}", points, games));
+            for opponent in order.iter() {
+                let (points, games) = self.score(*player, *opponent);
+                let cell = if opponent == player { "-".to_string() } else if games == 0 { String::new() } else { format!("{}/{}", points, games) };
+                line.push_str(&format!(" {:>7}", cell));
+            }
+            lines.push(line);
+        }
+        return lines.join("\n");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::SearchOptions;
+
+    fn participant(name: &str) -> Participant {
+        Participant::create(name, Box::new(|| Ok(Box::new(InProcessPlayer::create("mess", InProcessEngine::Search(SearchOptions::create()))) as Box<dyn MatchPlayer>)))
+    }
+
+    fn game(white: usize, black: usize, result: GameResult) -> TournamentGame {
+        TournamentGame { pairing: Pairing { round: 0, white, black, opening: 0 }, result }
+    }
+
+    #[test]
+    fn tournament_standings() {
+        let names = vec!("A".to_string(), "B".to_string(), "C".to_string());
+        let report = TournamentReport {
+            names,
+            games: vec!(
+                game(0, 1, GameResult::WhiteWins), game(1, 0, GameResult::Draw),
+                game(0, 2, GameResult::WhiteWins), game(2, 0, GameResult::BlackWins),
+                game(1, 2, GameResult::Draw), game(2, 1, GameResult::Draw),
+            ),
+        };
+        assert_eq!(report.score(0, 1), (1.5, 2));
+        assert_eq!(report.score(1, 0), (0.5, 2));
+        assert_eq!(report.total(0), (3.5, 4));
+
+        let ratings = report.ratings();
+        assert!(ratings[0] > 50.0 && ratings[0] < 400.0, "{:?}", ratings);
+        assert!(ratings[1] < 0.0 && ratings[2] < ratings[1], "{:?}", ratings);
+        assert!(ratings.iter().sum::<f32>().abs() < 1e-2, "{:?}", ratings);
+
+        let crosstable = report.crosstable();
+        let lines: Vec<&str> = crosstable.lines().collect();
+        assert_eq!(lines[0], "  # Name    Elo    Points       1       2       3");
+        assert!(lines[1].starts_with("  1 A   "));
+        assert!(lines[1].ends_with("  3.5/4       -   1.5/2     2/2"));
+    }
+
+    #[test]
+    fn tournament_run() {
+        let openings = openings_from_epd("4k3/8/8/8/8/8/8/R3K3 w - -").unwrap();
+        let participants = vec!(participant("A"), participant("B"), participant("C"));
+        let mut tournament = Tournament::create(participants, TournamentKind::RoundRobin, openings.clone(), MatchLimits::Depth(1));
+        tournament.set_adjudication(Adjudication { max_plies: 10, ..Adjudication::create() });
+        tournament.set_concurrency(2);
+
+        let schedule = tournament.schedule();
+        assert_eq!(schedule.len(), 6);
+        assert_eq!(schedule[1], Pairing { round: 2, white: 1, black: 0, opening: 0 });
+
+        let mut pgn = Vec::new();
+        let report = tournament.run(&[], &mut pgn, &mut Vec::new()).unwrap();
+        assert_eq!(report.games.len(), 6);
+        assert_eq!(report.games.iter().map(|game| game.pairing).collect::<Vec<Pairing>>(), schedule);
+        let pgn = String::from_utf8(pgn).unwrap();
+        assert_eq!(pgn.matches("[Event \"Tournament\"]").count(), 6);
+        assert!(pgn.contains("[White \"C\"]\n[Black \"B\"]"));
+
+        // Resuming from the first two games plays only the other four
+        let first_games = &pgn[..pgn.match_indices("[Event").nth(2).unwrap().0];
+        let completed = tournament.completed_games(first_games).unwrap();
+        assert_eq!(completed.len(), 2);
+        let mut more = Vec::new();
+        let resumed = tournament.run(&completed, &mut more, &mut Vec::new()).unwrap();
+        assert_eq!(String::from_utf8(more).unwrap().matches("[Event").count(), 4);
+        assert_eq!(resumed, report);
+
+        // A gauntlet only pairs the first participant with the others
+        let participants = vec!(participant("A"), participant("B"), participant("C"));
+        let gauntlet = Tournament::create(participants, TournamentKind::Gauntlet, openings, MatchLimits::Depth(1));
+        assert!(gauntlet.schedule().iter().all(|pairing| pairing.white == 0 || pairing.black == 0));
+        assert_eq!(gauntlet.schedule().len(), 4);
+        assert!(gauntlet.completed_games(&pgn).is_err());
+    }
+}
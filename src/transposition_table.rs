@@ -0,0 +1,253 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::core::*;
+use super::move_::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    // The evaluation is exact
+    Exact,
+    // The evaluation failed high, the real evaluation is at least as good
+    Lower,
+    // The evaluation failed low, the real evaluation is at most as good
+    Upper,
+}
+
+// The parts of a Move that identify it among the moves of a position. Drops have equal squares,
+// so they are told apart by the kind of piece dropped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveKey {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceKind>,
+    pub drop: Option<PieceKind>,
+}
+
+impl MoveKey {
+    pub fn of(m: &Move) -> MoveKey {
+        MoveKey { from: m.from, to: m.to, promotion: m.promotion, drop: MoveKey::dropped_kind(m) }
+    }
+
+    pub fn matches(&self, m: &Move) -> bool {
+        self.from == m.from && self.to == m.to && self.promotion == m.promotion && self.drop == MoveKey::dropped_kind(m)
+    }
+
+    fn dropped_kind(m: &Move) -> Option<PieceKind> {
+        if m.is_drop { Some(m.piece_kind) } else { None }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TranspositionEntry {
+    pub depth: u32,
+    pub evaluation: f32,
+    pub bound: Bound,
+    pub best_move: Option<MoveKey>,
+}
+
+// Piece kinds are stored as their index in PIECE_KINDS, None for kinds that can't be stored
+fn pack_kind(kind: PieceKind) -> Option<u64> {
+    PIECE_KINDS.iter().position(|k| *k == kind).map(|index| index as u64)
+}
+
+// Squares take 8 bits, the file in the lower and the rank in the upper four, to fit boards up to
+// Geometry::LARGEST
+fn pack_square(square: Square) -> u64 {
+    return (square.file() as u64) | (square.rank() as u64) << 4;
+}
+
+fn unpack_square(data: u64) -> Square {
+    return Square::at((data & 0xf) as i8, ((data >> 4) & 0xf) as i8);
+}
+
+// Layout of an entry packed into 64 bits:
+// bits 0-31 evaluation, 32-39 depth, 40-41 bound, 42-49 from square, 50-57 to square,
+// 58-61 promotion (0 for none), 62 whether there is a best move, 63 whether it is a drop. The
+// from square of a drop is its to square, so bits 42-49 hold the kind of the dropped piece instead.
+// A best move with a piece kind that can't be stored is left out.
+impl TranspositionEntry {
+    fn pack(&self) -> u64 {
+        let mut data = self.evaluation.to_bits() as u64;
+        data |= (self.depth.min(255) as u64) << 32;
+        data |= (match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        }) << 40;
+
+        if let Some(best_move) = self.best_move {
+            let (from, is_drop) = match best_move.drop {
+                Some(kind) => (pack_kind(kind), 1),
+                None => (Some(pack_square(best_move.from)), 0),
+            };
+            let promotion = match best_move.promotion {
+                Some(promotion) => pack_kind(promotion).map(|promotion| promotion + 1),
+                None => Some(0),
+            };
+            if let (Some(from), Some(promotion)) = (from, promotion) {
+                data |= from << 42 | pack_square(best_move.to) << 50 | promotion << 58 | 1 << 62 | is_drop << 63;
+            }
+        }
+
+        return data;
+    }
+
+    fn unpack(data: u64) -> TranspositionEntry {
+        let bound = match (data >> 40) & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+
+        let mut best_move = None;
+        if (data >> 62) & 1 == 1 {
+            let promotion_index = ((data >> 58) & 0xf) as usize;
+            let to = unpack_square(data >> 50);
+            let drop = if (data >> 63) & 1 == 1 { Some(PIECE_KINDS[((data >> 42) & 0xff) as usize]) } else { None };
+            best_move = Some(MoveKey {
+                from: if drop.is_some() { to } else { unpack_square(data >> 42) },
+                to,
+                promotion: if promotion_index == 0 { None } else { Some(PIECE_KINDS[promotion_index - 1]) },
+                drop,
+            });
+        }
+
+        TranspositionEntry {
+            depth: ((data >> 32) & 0xff) as u32,
+            evaluation: f32::from_bits(data as u32),
+            bound,
+            best_move,
+        }
+    }
+}
+
+// Hash table of search results, shared between search threads without locking. Each slot holds the
+// packed entry and the position hash XORed with it. A slot torn by two threads writing at the
+// same time fails the XOR check and reads as empty.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    // A table of `size` entries, a size of 0 disables the table
+    pub fn create(size: usize) -> TranspositionTable {
+        let mut slots = Vec::with_capacity(size);
+        for _ in 0..size {
+            slots.push([AtomicU64::new(0), AtomicU64::new(0)]);
+        }
+        TranspositionTable { slots }
+    }
+
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TranspositionEntry> {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        let slot = &self.slots[(hash % self.slots.len() as u64) as usize];
+        let checked_hash = slot[0].load(Ordering::Relaxed);
+        let data = slot[1].load(Ordering::Relaxed);
+
+        if checked_hash ^ data != hash || (checked_hash == 0 && data == 0) {
+            return None;
+        }
+
+        return Some(TranspositionEntry::unpack(data));
+    }
+
+    pub fn store(&self, hash: u64, entry: &TranspositionEntry) {
+        if self.slots.is_empty() {
+            return;
+        }
+
+        let slot = &self.slots[(hash % self.slots.len() as u64) as usize];
+        let data = entry.pack();
+        slot[0].store(hash ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transposition_table_store_and_probe() {
+        let table = TranspositionTable::create(1024);
+        let entry = TranspositionEntry {
+            depth: 7,
+            evaluation: -3.5,
+            bound: Bound::Lower,
+            best_move: Some(MoveKey { from: Square::at(1, 6), to: Square::at(2, 7), promotion: Some(PieceKind::Rook), drop: None }),
+        };
+
+        assert_eq!(table.probe(12345), None);
+        table.store(12345, &entry);
+        assert_eq!(table.probe(12345), Some(entry));
+
+        // A different position mapping to the same slot doesn't match
+        assert_eq!(table.probe(12345 + 1024), None);
+
+        let entry = TranspositionEntry { depth: 2, evaluation: 1.0, bound: Bound::Exact, best_move: None };
+        table.store(12345 + 1024, &entry);
+        assert_eq!(table.probe(12345 + 1024), Some(entry));
+        assert_eq!(table.probe(12345), None);
+
+        // Squares of wider boards and fairy promotions fit as well
+        let entry = TranspositionEntry {
+            depth: 3,
+            evaluation: 0.5,
+            bound: Bound::Upper,
+            best_move: Some(MoveKey { from: Square::at(9, 6), to: Square::at(8, 7), promotion: Some(PieceKind::Chancellor), drop: None }),
+        };
+        table.store(12345, &entry);
+        assert_eq!(table.probe(12345), Some(entry));
+
+        // Drops keep the dropped piece
+        let entry = TranspositionEntry {
+            depth: 4,
+            evaluation: 2.0,
+            bound: Bound::Exact,
+            best_move: Some(MoveKey { from: Square::at(1, 6), to: Square::at(1, 6), promotion: None, drop: Some(PieceKind::Queen) }),
+        };
+        table.store(12345, &entry);
+        assert_eq!(table.probe(12345), Some(entry));
+
+        // Every kind a pawn may promote to, including those of fairy pieces
+        for kind in PIECE_KINDS.iter() {
+            let entry = TranspositionEntry {
+                depth: 1,
+                evaluation: 0.0,
+                bound: Bound::Exact,
+                best_move: Some(MoveKey { from: Square::at(9, 6), to: Square::at(9, 7), promotion: Some(*kind), drop: None }),
+            };
+            table.store(12345, &entry);
+            assert_eq!(table.probe(12345), Some(entry));
+        }
+
+        // A kind that can't be stored leaves out the best move
+        let best_move = Some(MoveKey { from: Square::at(0, 6), to: Square::at(0, 7), promotion: Some(PieceKind::Dummy), drop: None });
+        table.store(12345, &TranspositionEntry { depth: 1, evaluation: 0.0, bound: Bound::Exact, best_move });
+        assert_eq!(table.probe(12345).unwrap().best_move, None);
+
+        table.clear();
+        assert_eq!(table.probe(12345 + 1024), None);
+    }
+
+    #[test]
+    fn transposition_table_disabled() {
+        let table = TranspositionTable::create(0);
+        table.store(1, &TranspositionEntry { depth: 2, evaluation: 1.0, bound: Bound::Exact, best_move: None });
+        assert_eq!(table.probe(1), None);
+    }
+}
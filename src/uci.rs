@@ -0,0 +1,497 @@
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::move_generation::*;
+use super::opening_book::*;
+use super::search::*;
+use super::search_handle::*;
+use super::syzygy::*;
+use super::tablebase::*;
+use super::time_manager::*;
+use super::variant::*;
+
+// Size of a transposition table entry, for converting the Hash option from megabytes
+const TRANSPOSITION_ENTRY_SIZE: usize = 16;
+
+// A search started with `go`, running until it finishes or `stop` is received
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    // Reports the search's info lines and its best move
+    reporter: JoinHandle<()>,
+}
+
+// Engine side of the Universal Chess Interface. Commands are passed to handle() line by line,
+// responses are written to `output`.
+pub struct UciEngine<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    board: Board,
+    options: SearchOptions,
+    search: Option<RunningSearch>,
+
+    // Book moves are played without searching while the position is in the book
+    book: Option<OpeningBook>,
+    own_book: bool,
+    book_selection: BookSelection,
+
+    tablebase: Option<Arc<dyn Tablebase>>,
+
+    // Castling moves are written as the king taking its own rook, see Move::uci_notation()
+    chess960: bool,
+    // Rule set of the positions set up from now on if UCI_Variant was set, otherwise positions keep
+    // the variant their FEN implies
+    variant: Option<Variant>,
+}
+
+impl<W: Write + Send + 'static> UciEngine<W> {
+    pub fn create(output: W) -> UciEngine<W> {
+        UciEngine {
+            output: Arc::new(Mutex::new(output)),
+            board: Board::create_populated(),
+            options: SearchOptions::create(),
+            search: None,
+            book: None,
+            own_book: true,
+            book_selection: BookSelection::WeightedRandom,
+            tablebase: None,
+            chess960: false,
+            variant: None,
+        }
+    }
+
+    pub fn get_output(&self) -> Arc<Mutex<W>> {
+        self.output.clone()
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    // Handle one command, returns false once the engine is asked to quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return true;
+        }
+
+        match tokens[0] {
+            "uci" => {
+                self.send("id name mess");
+                self.send("id author mrzzzrm");
+                self.send("option name Hash type spin default 1 min 0 max 4096");
+                self.send("option name Threads type spin default 1 min 1 max 64");
+                self.send("option name MultiPV type spin default 1 min 1 max 256");
+                self.send("option name Ponder type check default false");
+                self.send("option name OwnBook type check default true");
+                self.send("option name BookFile type string default <empty>");
+                self.send("option name BookBestMove type check default false");
+                self.send("option name SyzygyPath type string default <empty>");
+                self.send("option name UCI_Chess960 type check default false");
+                self.send("option name UCI_Variant type combo default chess var chess var kingofthehill var 3check var antichess var crazyhouse var atomic var capablanca var losalamos");
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "setoption" => self.set_option(&tokens[1..]),
+            "ucinewgame" => {
+                self.wait_for_search();
+                self.board = self.variant.unwrap_or(Variant::Standard).start_board();
+            }
+            "position" => {
+                self.wait_for_search();
+                if let Err(error) = self.set_position(&tokens[1..]) {
+                    self.send(&format!("info string {}", error));
+                }
+            }
+            "go" => {
+                self.wait_for_search();
+                self.go(&tokens[1..]);
+            }
+            "stop" => self.stop(),
+            "ponderhit" => {
+                if let Some(search) = &self.search {
+                    search.ponder.store(false, Ordering::Relaxed);
+                }
+            }
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            _ => self.send(&format!("info string Unknown command '{}'", line.trim())),
+        }
+
+        return true;
+    }
+
+    // Abort the running search, if any, and wait for its best move to be reported
+    pub fn stop(&mut self) {
+        if let Some(search) = &self.search {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait_for_search();
+    }
+
+    // Wait for the running search, if any, to report its best move
+    pub fn wait_for_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.reporter.join().unwrap();
+        }
+    }
+
+    fn set_option(&mut self, tokens: &[&str]) {
+        // setoption name <name> value <value>
+        let value_idx = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+        let name = tokens[1.min(value_idx)..value_idx].join(" ");
+        let value = tokens[(value_idx + 1).min(tokens.len())..].join(" ");
+
+        match (name.to_lowercase().as_str(), value.parse::<usize>()) {
+            ("hash", Ok(megabytes)) => self.options.transposition_table_size = megabytes * 1024 * 1024 / TRANSPOSITION_ENTRY_SIZE,
+            ("threads", Ok(threads)) => self.options.threads = threads.max(1),
+            ("multipv", Ok(multi_pv)) => self.options.multi_pv = multi_pv.max(1),
+            // Whether the GUI will ask the engine to ponder, nothing to prepare
+            ("ponder", _) => {}
+            ("ownbook", _) => self.own_book = value == "true",
+            ("bookfile", _) => {
+                self.book = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match OpeningBook::open(&value) {
+                        Ok(book) => self.book = Some(book),
+                        Err(error) => self.send(&format!("info string Can't open book '{}': {}", value, error)),
+                    }
+                }
+            }
+            ("syzygypath", _) => {
+                self.tablebase = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match SyzygyTablebase::open(&value) {
+                        Ok(tablebase) => {
+                            self.send(&format!("info string Found {} tablebases", tablebase.len()));
+                            self.tablebase = Some(Arc::new(tablebase));
+                        }
+                        Err(error) => self.send(&format!("info string Can't open tablebases '{}': {}", value, error)),
+                    }
+                }
+            }
+            ("uci_chess960", _) => self.chess960 = value == "true",
+            ("uci_variant", _) => match Variant::from_name(&value) {
+                Some(variant) => self.variant = Some(variant),
+                None => self.send(&format!("info string Unknown variant '{}'", value)),
+            },
+            ("bookbestmove", _) => {
+                self.book_selection = if value == "true" { BookSelection::Best } else { BookSelection::WeightedRandom };
+            }
+            _ => self.send(&format!("info string Unknown option '{}' or invalid value '{}'", name, value)),
+        }
+    }
+
+    fn set_position(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let moves_idx = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+
+        let mut board = match tokens.first() {
+            Some(&"startpos") => self.variant.unwrap_or(Variant::Standard).start_board(),
+            Some(&"fen") => Board::from_fen(&tokens[1..moves_idx].join(" "))?,
+            _ => return Err(format!("Invalid position '{}'", tokens.join(" ")))
+        };
+        if let Some(variant) = self.variant {
+            board.variant = variant;
+        }
+
+        for text in tokens[(moves_idx + 1).min(tokens.len())..].iter() {
+            let m = find_move(&mut board, text, self.chess960).ok_or(format!("Illegal move '{}'", text))?;
+            board.apply_move(m);
+        }
+
+        self.board = board;
+        return Ok(());
+    }
+
+    fn go(&mut self, tokens: &[&str]) {
+        let mut limits = SearchLimits::infinite();
+        let mut search_moves: Option<Vec<Move>> = None;
+        let mut ponder = false;
+        let mut infinite = false;
+
+        let mut time = [None, None];
+        let mut increment = [Duration::from_millis(0), Duration::from_millis(0)];
+        let mut moves_to_go = None;
+
+        let mut idx = 0;
+        while idx < tokens.len() {
+            let value = tokens.get(idx + 1).and_then(|value| value.parse::<u64>().ok());
+
+            match tokens[idx] {
+                "searchmoves" => {
+                    let mut moves = Vec::new();
+                    while let Some(m) = tokens.get(idx + 1).and_then(|text| find_move(&mut self.board, text, self.chess960)) {
+                        moves.push(m);
+                        idx += 1;
+                    }
+                    search_moves = Some(moves);
+                }
+                "ponder" => ponder = true,
+                "infinite" => infinite = true,
+                "wtime" => time[Color::White.index()] = value.map(Duration::from_millis),
+                "btime" => time[Color::Black.index()] = value.map(Duration::from_millis),
+                "winc" => increment[Color::White.index()] = Duration::from_millis(value.unwrap_or(0)),
+                "binc" => increment[Color::Black.index()] = Duration::from_millis(value.unwrap_or(0)),
+                "movestogo" => moves_to_go = value.map(|value| value as u32),
+                "depth" => limits.depth = value.map(|value| value as u32),
+                "nodes" => limits.nodes = value,
+                "movetime" => limits.move_time = value.map(Duration::from_millis),
+                _ => {}
+            }
+
+            idx += 1;
+        }
+
+        // Searches the GUI controls, i.e. pondering and analysis of given moves, skip the book
+        if self.own_book && !ponder && search_moves.is_none() {
+            let book_move = match &mut self.book {
+                Some(book) => book.pick(&mut self.board, self.book_selection),
+                None => None
+            };
+            if let Some(book_move) = book_move {
+                self.send(&format!("bestmove {}", book_move.uci_notation(self.chess960)));
+                return;
+            }
+        }
+
+        if limits.move_time.is_none() {
+            let side = self.board.side.index();
+            if let Some(remaining) = time[side] {
+                limits.move_time = Some(TimeControl::create(remaining, increment[side], moves_to_go).allocate());
+            }
+        }
+
+        let mut evaluator = SearchEvaluator::create_with_options(0, self.options);
+        evaluator.set_limits(limits);
+        evaluator.set_root_moves(search_moves, Vec::new());
+        evaluator.set_tablebase(self.tablebase.clone());
+        evaluator.get_ponder_flag().store(ponder, Ordering::Relaxed);
+
+        let handle = start_search_with_evaluator(&self.board, evaluator);
+        let stop = handle.get_stop_flag();
+        let ponder = handle.get_ponder_flag();
+        let output = self.output.clone();
+        let chess960 = self.chess960;
+
+        let reporter = std::thread::spawn(move || report_search(handle, output, chess960, infinite));
+
+        self.search = Some(RunningSearch { stop, ponder, reporter });
+    }
+}
+
+// Write a line to the GUI, also used by the XBoard front-end
+pub fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().unwrap();
+    // Nothing to be done if the GUI has gone away
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+// Forward the info lines of a search and report its best move. The best move of a ponder search
+// is held back until the GUI sends `ponderhit` or `stop`, that of an infinite search until `stop`,
+// as UCI requires.
+fn report_search<W: Write>(handle: SearchHandle, output: Arc<Mutex<W>>, chess960: bool, infinite: bool) {
+    for info in handle.get_info_receiver().iter() {
+        send(&output, &info.to_uci_string(chess960));
+    }
+
+    let stop = handle.get_stop_flag();
+    let ponder = handle.get_ponder_flag();
+    let result = handle.wait();
+
+    while (infinite || ponder.load(Ordering::Relaxed)) && !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let line = match (result.line.moves.first(), result.line.moves.get(1)) {
+        (Some(best_move), Some(ponder_move)) =>
+            format!("bestmove {} ponder {}", best_move.uci_notation(chess960), ponder_move.uci_notation(chess960)),
+        (Some(best_move), None) => format!("bestmove {}", best_move.uci_notation(chess960)),
+        // No legal move, UCI's null move
+        _ => "bestmove 0000".to_string()
+    };
+    send(&output, &line);
+}
+
+// Find the legal move written in coordinate notation, e.g. "e2e4" or "e7e8q"
+pub fn parse_move(board: &mut Board, text: &str) -> Option<Move> {
+    find_move(board, text, false)
+}
+
+// Like parse_move(), with castling written as in Chess960 if `chess960` is set
+pub fn find_move(board: &mut Board, text: &str, chess960: bool) -> Option<Move> {
+    generate_moves(board).into_iter().find(|m| m.uci_notation(chess960) == text)
+}
+
+// Talk UCI on `input` and `output` until `quit` is received or the input ends
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let mut engine = UciEngine::create(output);
+
+    for line in input.lines() {
+        match line {
+            Ok(line) => {
+                if !engine.handle(&line) {
+                    return;
+                }
+            }
+            Err(_) => break
+        }
+    }
+
+    engine.stop();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output_lines(engine: &UciEngine<Vec<u8>>) -> Vec<String> {
+        let output = engine.get_output();
+        let output = output.lock().unwrap();
+        String::from_utf8(output.clone()).unwrap().lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn uci_handshake() {
+        let mut engine = UciEngine::create(Vec::new());
+        assert!(engine.handle("uci"));
+        assert!(engine.handle("isready"));
+
+        let lines = output_lines(&engine);
+        assert_eq!(lines.first().unwrap(), "id name mess");
+        assert!(lines.contains(&"option name Ponder type check default false".to_string()));
+        assert_eq!(&lines[lines.len() - 2..], ["uciok", "readyok"]);
+
+        assert!(!engine.handle("quit"));
+    }
+
+    #[test]
+    fn uci_position() {
+        let mut engine = UciEngine::create(Vec::new());
+        engine.handle("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(engine.get_board().to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        engine.handle("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 moves e2e4");
+        assert_eq!(engine.get_board().to_fen(), "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1");
+
+        // The move counters go on from the FEN's, pawn moves and captures reset the halfmove clock
+        engine.handle("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 7 30 moves e1d1 e8d8");
+        assert_eq!(engine.get_board().to_fen(), "3k4/8/8/8/8/8/4P3/3K4 w - - 9 31");
+        engine.handle("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 7 30 moves e1d1 e8d8 e2e4 d8c8");
+        assert_eq!(engine.get_board().to_fen(), "2k5/8/8/8/4P3/8/8/3K4 w - - 1 32");
+
+        // Chess960 castling is written as the king taking its own rook
+        engine.handle("setoption name UCI_Chess960 value true");
+        engine.handle("position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1h1");
+        assert_eq!(engine.get_board().to_fen(), "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4");
+        engine.handle("position fen 4k3/8/8/8/8/8/8/1RK5 w B - 0 1 moves c1b1");
+        assert_eq!(engine.get_board().to_fen(), "4k3/8/8/8/8/8/8/2KR4 b - - 1 1");
+
+        // Without UCI_Variant, a FEN keeps the variant it implies
+        engine.handle("position fen 4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1");
+        assert_eq!(engine.get_board().variant, Variant::Crazyhouse);
+
+        // Positions are set up with the selected variant
+        engine.handle("setoption name UCI_Variant value antichess");
+        engine.handle("position startpos moves e2e3 b7b5");
+        assert_eq!(engine.get_board().variant, Variant::Antichess);
+        // The bishop has to take on b5
+        engine.handle("position startpos moves e2e3 b7b5 e3e4");
+        assert_eq!(output_lines(&engine).last().unwrap(), "info string Illegal move 'e3e4'");
+        engine.handle("position startpos moves e2e3 b7b5 f1b5");
+        assert_eq!(engine.get_board().side, Color::Black);
+
+        // Drops are written with an '@'
+        engine.handle("setoption name UCI_Variant value crazyhouse");
+        engine.handle("position startpos moves e2e4 d7d5 e4d5 d8d5 b1c3 d5a5 P@d4");
+        assert_eq!(engine.get_board().to_fen(), "rnb1kbnr/ppp1pppp/8/q7/3P4/2N5/PPPP1PPP/R1BQKBNR[p] b KQkq - 0 4");
+
+        // Variants on other boards start from their own position
+        engine.handle("setoption name UCI_Variant value capablanca");
+        engine.handle("position startpos moves e2e4 j7j5 c1d3");
+        assert_eq!(engine.get_board().to_fen(), "rnabqkbcnr/ppppppppp1/10/9p/4P5/3A6/PPPP1PPPPP/RN1BQKBCNR b KQkq - 1 2");
+    }
+
+    #[test]
+    fn uci_go() {
+        let mut engine = UciEngine::create(Vec::new());
+        engine.handle("position startpos");
+        engine.handle("go depth 3 searchmoves a2a3 h2h3");
+        engine.wait_for_search();
+
+        let lines = output_lines(&engine);
+        assert!(lines[0].starts_with("info depth 1 multipv 1 "));
+        let best_move = lines.last().unwrap().split_whitespace().nth(1).unwrap().to_string();
+        assert!(best_move == "a2a3" || best_move == "h2h3");
+    }
+
+    #[test]
+    fn uci_book() {
+        let path = std::env::temp_dir().join(format!("mess_uci_book_{}.bin", std::process::id()));
+        let mut board = Board::create_populated();
+        let m = parse_move(&mut board, "g1f3").unwrap();
+        let mut bytes = Vec::new();
+        BookEntry { key: crate::polyglot::polyglot_key(&board).unwrap(), move_data: crate::polyglot::encode_move(&m), weight: 1, learn: 0 }.write(&mut bytes);
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut engine = UciEngine::create(Vec::new());
+        engine.handle(&format!("setoption name BookFile value {}", path.display()));
+        engine.handle("position startpos");
+        engine.handle("go wtime 1000 btime 1000");
+        engine.wait_for_search();
+        assert_eq!(output_lines(&engine), vec!("bestmove g1f3"));
+
+        // Out of book
+        engine.handle("position startpos moves e2e4");
+        engine.handle("go depth 1");
+        engine.wait_for_search();
+        assert!(output_lines(&engine).last().unwrap().starts_with("bestmove"));
+        assert!(output_lines(&engine).len() > 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn uci_ponder() {
+        let mut engine = UciEngine::create(Vec::new());
+        engine.handle("position startpos moves e2e4 e7e5");
+        engine.handle("go ponder wtime 1000 btime 1000");
+
+        // The best move isn't reported while pondering, although the time is up
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!output_lines(&engine).iter().any(|line| line.starts_with("bestmove")));
+
+        engine.handle("ponderhit");
+        engine.wait_for_search();
+        assert!(output_lines(&engine).last().unwrap().starts_with("bestmove"));
+
+        // Stopping a ponder search reports its best move as well
+        engine.handle("go ponder wtime 1000 btime 1000");
+        engine.handle("stop");
+        assert!(output_lines(&engine).last().unwrap().starts_with("bestmove"));
+    }
+
+    #[test]
+    fn uci_infinite() {
+        // The search of a mated position ends right away, but its best move waits for `stop`
+        let mut engine = UciEngine::create(Vec::new());
+        engine.handle("position fen k7/1Q6/1K6/8/8/8/8/8 b - - 0 1");
+        engine.handle("go infinite");
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!output_lines(&engine).iter().any(|line| line.starts_with("bestmove")));
+
+        engine.handle("stop");
+        assert!(output_lines(&engine).last().unwrap().starts_with("bestmove"));
+    }
+}
@@ -0,0 +1,385 @@
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::move_generation::*;
+
+// Squares a king has to reach in King of the Hill
+const HILL: [(i8, i8); 4] = [(3, 3), (4, 3), (3, 4), (4, 4)];
+
+// Pieces that can be held in a Crazyhouse pocket, see Board::pockets
+pub const POCKET_KINDS: [PieceKind; 5] = [PieceKind::Pawn, PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen];
+
+// Value of a check given in Three-check, in pawns
+const CHECK_VALUE: f32 = 1.5;
+
+// Value of bringing a king one step closer to the hill, in pawns
+const HILL_PROXIMITY_VALUE: f32 = 0.2;
+
+// Rule set of a game. The Board carries its variant and any state the variant needs, see
+// Board::checks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variant {
+    Standard,
+    // A king reaching one of the four centre squares wins
+    KingOfTheHill,
+    // Giving the third check wins
+    ThreeCheck,
+    // Captures are compulsory, the king is an ordinary piece and losing all pieces or being
+    // stalemated wins
+    Antichess,
+    // Captured pieces change sides and can be dropped back onto the board instead of a move
+    Crazyhouse,
+    // Captures explode, taking the capturing piece and the pieces around the target square other
+    // than pawns with them. Exploding the opponent's king wins.
+    Atomic,
+    // Played on a 10x8 board with an Archbishop and a Chancellor per side
+    Capablanca,
+    // Played on a 6x6 board without bishops, castling or pawn double steps
+    LosAlamos,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win(Color),
+    Draw,
+}
+
+impl Variant {
+    // Name as used by the UCI_Variant option
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::ThreeCheck => "3check",
+            Variant::Antichess => "antichess",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::Atomic => "atomic",
+            Variant::Capablanca => "capablanca",
+            Variant::LosAlamos => "losalamos",
+        }
+    }
+
+    // Inverse of name()
+    pub fn from_name(name: &str) -> Option<Variant> {
+        [Variant::Standard, Variant::KingOfTheHill, Variant::ThreeCheck, Variant::Antichess, Variant::Crazyhouse,
+            Variant::Atomic, Variant::Capablanca, Variant::LosAlamos].iter()
+            .find(|variant| variant.name() == name.to_lowercase())
+            .copied()
+    }
+
+    // Whether kings may be captured and moves may leave the own king in check
+    pub fn has_royal_king(&self) -> bool {
+        *self != Variant::Antichess
+    }
+
+    pub fn allows_castling(&self) -> bool {
+        *self != Variant::Antichess && *self != Variant::LosAlamos
+    }
+
+    // Whether pawns may advance two squares from their home rank
+    pub fn allows_double_step(&self) -> bool {
+        *self != Variant::LosAlamos
+    }
+
+    // Whether captured pieces go into the pockets, see Board::pockets
+    pub fn has_drops(&self) -> bool {
+        *self == Variant::Crazyhouse
+    }
+
+    // Pieces a pawn may promote to
+    pub fn promotions(&self) -> &'static [PieceKind] {
+        match self {
+            Variant::Antichess => &[PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen, PieceKind::King],
+            Variant::Capablanca => &[PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen, PieceKind::Archbishop,
+                PieceKind::Chancellor],
+            Variant::LosAlamos => &[PieceKind::Knight, PieceKind::Rook, PieceKind::Queen],
+            _ => &[PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen],
+        }
+    }
+
+    pub fn start_fen(&self) -> &'static str {
+        match self {
+            Variant::Capablanca => "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1",
+            Variant::LosAlamos => "rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1",
+            _ => START_FEN,
+        }
+    }
+
+    // Board in the variant's starting position
+    pub fn start_board(&self) -> Board {
+        let mut board = Board::from_fen(self.start_fen()).unwrap();
+        board.variant = *self;
+        return board;
+    }
+
+    // Outcome the variant's rules decide regardless of the legal moves, e.g. a king on the hill.
+    // None while the game goes on.
+    pub fn outcome(&self, board: &Board) -> Option<Outcome> {
+        match self {
+            Variant::KingOfTheHill => {
+                for color in [Color::White, Color::Black] {
+                    if let Some(king) = board.king_square(color) {
+                        if HILL.iter().any(|(x, y)| king == Square::at(*x, *y)) {
+                            return Some(Outcome::Win(color));
+                        }
+                    }
+                }
+                None
+            }
+            Variant::ThreeCheck => {
+                for color in [Color::White, Color::Black] {
+                    if board.checks[color.index()] >= 3 {
+                        return Some(Outcome::Win(color));
+                    }
+                }
+                None
+            }
+            Variant::Atomic => {
+                for color in [Color::White, Color::Black] {
+                    if board.king_square(color).is_none() {
+                        return Some(Outcome::Win(color.switch()));
+                    }
+                }
+                None
+            }
+            _ => None
+        }
+    }
+
+    // Outcome of a game in which the side to move has no legal moves
+    pub fn terminal_outcome(&self, board: &Board) -> Outcome {
+        if let Some(outcome) = self.outcome(board) {
+            return outcome;
+        }
+
+        match self {
+            Variant::Antichess => Outcome::Win(board.side),
+            _ => {
+                if is_check(board, board.side) {
+                    Outcome::Win(board.side.switch())
+                } else {
+                    Outcome::Draw
+                }
+            }
+        }
+    }
+
+    // Restrict the pseudo-legal `moves` to the ones the variant allows
+    pub fn filter_moves(&self, moves: Vec<Move>) -> Vec<Move> {
+        match self {
+            Variant::Antichess if moves.iter().any(|m| m.capture.is_some()) => {
+                moves.into_iter().filter(|m| m.capture.is_some()).collect()
+            }
+            // Kings can't capture, they would blow themselves up
+            Variant::Atomic => {
+                moves.into_iter().filter(|m| m.piece_kind != PieceKind::King || m.capture.is_none()).collect()
+            }
+            _ => moves
+        }
+    }
+
+    // Evaluation from White's perspective of what the variant values besides material. Replaces
+    // the material in Antichess, where fewer pieces are better.
+    pub fn evaluation(&self, board: &Board, material: f32) -> f32 {
+        match self {
+            Variant::Standard | Variant::Atomic | Variant::Capablanca | Variant::LosAlamos => material,
+            Variant::KingOfTheHill => {
+                let mut evaluation = material;
+                for color in [Color::White, Color::Black] {
+                    if let Some(king) = board.king_square(color) {
+                        let distance = HILL.iter()
+                            .map(|(x, y)| (king.file() - x).abs().max((king.rank() - y).abs()))
+                            .min()
+                            .unwrap();
+                        evaluation += HILL_PROXIMITY_VALUE * (6 - distance) as f32 * color.evaluation_sign();
+                    }
+                }
+                evaluation
+            }
+            Variant::ThreeCheck => {
+                material + CHECK_VALUE * (board.checks[Color::White.index()] as f32 - board.checks[Color::Black.index()] as f32)
+            }
+            Variant::Antichess => {
+                let pieces = board.pieces();
+                let count = |color: Color| pieces.iter().filter(|piece| piece.piece().color == color).count() as f32;
+                count(Color::Black) - count(Color::White)
+            }
+            Variant::Crazyhouse => {
+                // Pieces in hand count as material
+                let mut evaluation = material;
+                for color in [Color::White, Color::Black] {
+                    for kind in POCKET_KINDS {
+                        evaluation += board.pockets[color.index()][kind as usize] as f32 * board.definitions()[kind as usize].value * color.evaluation_sign();
+                    }
+                }
+                evaluation
+            }
+        }
+    }
+}
+
+// Outcome of the game on `board`, None while it goes on
+pub fn game_outcome(board: &mut Board) -> Option<Outcome> {
+    if generate_moves(board).is_empty() {
+        return Some(board.variant.terminal_outcome(board));
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::*;
+    use crate::evaluation::*;
+    use crate::test_util::*;
+
+    fn board_with_variant(fen: &str, variant: Variant) -> Board {
+        let mut board = Board::from_fen(fen).unwrap();
+        board.variant = variant;
+        board
+    }
+
+    #[test]
+    fn variant_names() {
+        for variant in [Variant::Standard, Variant::KingOfTheHill, Variant::ThreeCheck, Variant::Antichess, Variant::Crazyhouse, Variant::Atomic,
+            Variant::Capablanca, Variant::LosAlamos] {
+            assert_eq!(Variant::from_name(variant.name()), Some(variant));
+        }
+        assert_eq!(Variant::from_name("shogi"), None);
+    }
+
+    #[test]
+    fn king_of_the_hill() {
+        let mut board = board_with_variant("4k3/8/8/8/8/4K3/8/8 w - - 0 1", Variant::KingOfTheHill);
+        assert_eq!(game_outcome(&mut board), None);
+
+        // Stepping onto the hill ends the game
+        let mut evaluator = SearchEvaluator::create(2);
+        assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 1.0);
+        board.apply_move(evaluator.get_best_line().moves[0]);
+        assert_eq!(game_outcome(&mut board), Some(Outcome::Win(Color::White)));
+        assert!(generate_moves(&mut board).is_empty());
+    }
+
+    #[test]
+    fn three_check() {
+        let mut board = board_with_variant("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Variant::ThreeCheck);
+        board.checks = [2, 0];
+        let original_board = board.clone();
+
+        let check = TestMove::from_to(&board, Square::at(0, 0), Square::at(0, 7));
+        board.apply_move(check);
+        assert_eq!(board.checks, [3, 0]);
+        assert_eq!(game_outcome(&mut board), Some(Outcome::Win(Color::White)));
+        board.revert_move(check);
+        assert_eq!(board, original_board);
+
+        // The third check is as good as a mate
+        let mut evaluator = SearchEvaluator::create(2);
+        assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 1.0);
+        assert!(static_evaluation(&board) > 5.0);
+    }
+
+    #[test]
+    fn antichess() {
+        // Captures are compulsory, even with the king
+        let mut board = board_with_variant("8/8/8/8/8/3p4/4K3/R7 w - - 0 1", Variant::Antichess);
+        let moves = generate_moves(&mut board);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].coordinate_notation(), "e2d3");
+
+        // The king may walk into check and promotion to a king is allowed
+        let mut board = board_with_variant("8/P7/8/8/8/8/8/K6r w - - 0 1", Variant::Antichess);
+        let moves = generate_moves(&mut board);
+        assert!(moves.iter().any(|m| m.coordinate_notation() == "a1b1"));
+        assert!(moves.iter().any(|m| m.promotion == Some(PieceKind::King)));
+
+        // Losing all pieces wins
+        let mut board = board_with_variant("8/8/8/8/8/8/8/7r w - - 0 1", Variant::Antichess);
+        assert_eq!(game_outcome(&mut board), Some(Outcome::Win(Color::White)));
+        assert!(static_evaluation(&board) > 0.0);
+
+        // Giving away the last piece is the best move
+        let mut board = board_with_variant("8/8/8/8/8/8/6r1/R7 w - - 0 1", Variant::Antichess);
+        let mut evaluator = SearchEvaluator::create(3);
+        assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 2.0);
+    }
+
+    #[test]
+    fn atomic() {
+        // The rook blows up itself and the pieces around e4, but not the pawn
+        let mut board = board_with_variant("4k3/8/8/3nbr2/3Pq3/8/4R3/4K3 w - - 0 1", Variant::Atomic);
+        let original_board = board.clone();
+        let capture = TestMove::from_to_capture(&board, Square::at(4, 1), Square::at(4, 3), PieceKind::Queen.colored(Color::Black).at(4, 3));
+        board.apply_move(capture);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/3P4/8/8/4K3 b - - 0 1");
+        board.revert_move(capture);
+        assert_eq!(board, original_board);
+
+        // An exploded rook loses its castling rights
+        let mut board = board_with_variant("r3k1nr/8/8/8/8/8/8/4K1R1 w kq - 0 1", Variant::Atomic);
+        let original_board = board.clone();
+        let capture = TestMove::from_to_capture(&board, Square::at(6, 0), Square::at(6, 7), PieceKind::Knight.colored(Color::Black).at(6, 7));
+        board.apply_move(capture);
+        assert_eq!(board.to_fen(), "r3k3/8/8/8/8/8/8/4K3 b q - 0 1");
+        board.revert_move(capture);
+        assert_eq!(board, original_board);
+
+        // Kings next to each other can't be checked and can't capture
+        let mut board = board_with_variant("8/8/8/8/3k4/3K4/8/3r3r w - - 0 1", Variant::Atomic);
+        assert!(!is_check(&board, Color::White));
+        let moves = generate_moves(&mut board);
+        assert!(!moves.iter().any(|m| m.capture.is_some()));
+        assert!(!moves.iter().any(|m| m.to.rank() == 0));
+        assert!(moves.iter().any(|m| m.coordinate_notation() == "d3e3"));
+
+        // Blowing up the king wins
+        let mut board = board_with_variant("4k3/4q3/8/8/8/8/8/4RK2 w - - 0 1", Variant::Atomic);
+        let mut evaluator = SearchEvaluator::create(1);
+        assert_eq!(evaluator.evaluate(&mut board), MATE_SCORE - 1.0);
+        board.apply_move(evaluator.get_best_line().moves[0]);
+        assert_eq!(game_outcome(&mut board), Some(Outcome::Win(Color::White)));
+    }
+
+    #[test]
+    fn crazyhouse() {
+        // Pawns can't be dropped onto the first or the last rank
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K3[Pn] w - - 0 1").unwrap();
+        let moves = generate_moves(&mut board);
+        assert_eq!(moves.iter().filter(|m| m.is_drop).count(), 6 * 8);
+        assert!(!moves.iter().any(|m| m.is_drop && (m.to.rank() == 0 || m.to.rank() == 7)));
+        board.side = Color::Black;
+        assert_eq!(generate_moves(&mut board).iter().filter(|m| m.is_drop).count(), 62);
+
+        // Captured pieces change sides
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K2r[] b - - 0 1").unwrap();
+        let original_board = board.clone();
+        let capture = TestMove::from_to_capture(&board, Square::at(7, 0), Square::at(0, 0), PieceKind::Rook.colored(Color::White).at(0, 0));
+        board.apply_move(capture);
+        assert_eq!(board.pockets[Color::Black.index()], [0, 0, 0, 1, 0]);
+        board.revert_move(capture);
+        assert_eq!(board, original_board);
+
+        // Promoted pieces keep their mark and turn back into pawns when captured
+        let mut board = Board::from_fen("1r2k3/P7/8/8/8/8/8/4K3[] w - - 0 1").unwrap();
+        let original_board = board.clone();
+        let promotion = TestMove::promotion(&board, Square::at(0, 6), Square::at(0, 7), PieceKind::Queen);
+        board.apply_move(promotion);
+        let capture = TestMove::from_to_capture(&board, Square::at(1, 7), Square::at(0, 7), PieceKind::Queen.colored(Color::White).at(0, 7));
+        board.apply_move(capture);
+        assert_eq!(board.to_fen(), "r3k3/8/8/8/8/8/8/4K3[p] w - - 0 2");
+        board.revert_move(capture);
+        assert_eq!(board.to_fen(), "Q~r2k3/8/8/8/8/8/8/4K3[] b - - 0 1");
+        board.side = Color::White;
+        let queen_move = TestMove::from_to_capture(&board, Square::at(0, 7), Square::at(1, 7), PieceKind::Rook.colored(Color::Black).at(1, 7));
+        board.apply_move(queen_move);
+        assert_eq!(board.to_fen(), "1Q~2k3/8/8/8/8/8/8/4K3[R] b - - 0 1");
+        board.revert_move(queen_move);
+        assert_eq!(board.to_fen(), "Q~r2k3/8/8/8/8/8/8/4K3[] w - - 0 1");
+        board.side = Color::Black;
+        board.revert_move(promotion);
+        assert_eq!(board, original_board);
+
+        assert!(static_evaluation(&Board::from_fen("4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1").unwrap()) > 5.0);
+    }
+}
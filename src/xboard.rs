@@ -0,0 +1,536 @@
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use super::board::*;
+use super::core::*;
+use super::move_::*;
+use super::san::*;
+use super::search::*;
+use super::search_handle::*;
+use super::time_manager::*;
+use super::uci::{find_move, send};
+use super::variant::*;
+
+// Size of a transposition table entry, for converting the memory command from megabytes
+const TRANSPOSITION_ENTRY_SIZE: usize = 16;
+
+// Variants as XBoard names them, offered in the `variants` feature
+const VARIANTS: [(&str, Variant); 8] = [
+    ("normal", Variant::Standard),
+    ("kingofthehill", Variant::KingOfTheHill),
+    ("3check", Variant::ThreeCheck),
+    ("giveaway", Variant::Antichess),
+    ("crazyhouse", Variant::Crazyhouse),
+    ("atomic", Variant::Atomic),
+    ("capablanca", Variant::Capablanca),
+    ("losalamos", Variant::LosAlamos),
+];
+
+// A search started for the engine's move, running until it finishes or is interrupted
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    // Set when the move shouldn't be played after all, e.g. on `force` or `undo`
+    discard: Arc<AtomicBool>,
+    // Reports the thinking output and plays the move, returns the move played
+    reporter: JoinHandle<Option<Move>>,
+}
+
+// Time control set with `level`: `moves_per_session` moves (0 for the whole game) in `base` time
+// plus `increment` per move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Level {
+    pub moves_per_session: u32,
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl Level {
+    // Parse the arguments of `level`, e.g. "40 5 0" or "0 2:30 1"
+    pub fn parse(tokens: &[&str]) -> Option<Level> {
+        if tokens.len() != 3 {
+            return None;
+        }
+
+        let moves_per_session = tokens[0].parse().ok()?;
+        let base = match tokens[1].split_once(':') {
+            Some((minutes, seconds)) => Duration::from_secs(minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?),
+            None => Duration::from_secs(tokens[1].parse::<u64>().ok()? * 60),
+        };
+        let increment = parse_seconds(tokens[2])?;
+
+        return Some(Level { moves_per_session, base, increment });
+    }
+}
+
+// Parse a non-negative, finite number of seconds, which may have a fraction
+fn parse_seconds(text: &str) -> Option<Duration> {
+    text.parse::<f32>().ok().filter(|seconds| seconds.is_finite() && *seconds >= 0.0).map(Duration::from_secs_f32)
+}
+
+// Engine side of the Chess Engine Communication Protocol as spoken by XBoard and WinBoard. Commands
+// are passed to handle() line by line, responses are written to `output`. Unlike UCI, the engine
+// keeps the game itself and decides when to move.
+pub struct XboardEngine<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    board: Board,
+    // Moves played so far, for `undo`
+    history: Vec<Move>,
+    options: SearchOptions,
+    search: Option<RunningSearch>,
+    // Rule set chosen with `variant`, otherwise positions keep the variant their FEN implies
+    variant: Option<Variant>,
+
+    // The side the engine plays, None in force mode
+    engine_side: Option<Color>,
+    // Whether to send thinking output
+    post: bool,
+
+    level: Level,
+    // Fixed time per move set with `st`, overrides the level
+    move_time: Option<Duration>,
+    // Depth limit set with `sd`
+    depth: Option<u32>,
+    // Engine's clock as last sent with `time`
+    remaining: Option<Duration>,
+}
+
+impl<W: Write + Send + 'static> XboardEngine<W> {
+    pub fn create(output: W) -> XboardEngine<W> {
+        XboardEngine {
+            output: Arc::new(Mutex::new(output)),
+            board: Board::create_populated(),
+            history: Vec::new(),
+            options: SearchOptions::create(),
+            search: None,
+            variant: None,
+            engine_side: Some(Color::Black),
+            post: false,
+            level: Level { moves_per_session: 0, base: Duration::from_secs(300), increment: Duration::from_secs(0) },
+            move_time: None,
+            depth: None,
+            remaining: None,
+        }
+    }
+
+    pub fn get_output(&self) -> Arc<Mutex<W>> {
+        self.output.clone()
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_engine_side(&self) -> Option<Color> {
+        self.engine_side
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    // Handle one command, returns false once the engine is asked to quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return true;
+        }
+
+        match tokens[0] {
+            // Nothing to answer, the GUI waits for the features after `protover`
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" => {}
+            "protover" => {
+                let variants = VARIANTS.iter().map(|(name, _)| *name).collect::<Vec<&str>>().join(",");
+                self.send(&format!("feature myname=\"mess\" ping=1 setboard=1 usermove=1 san=0 time=1 draw=0 sigint=0 sigterm=0 \
+                    reuse=1 analyze=0 colors=0 memory=1 smp=1 variants=\"{}\" done=1", variants));
+            }
+            "ping" => self.send(&format!("pong {}", tokens.get(1).unwrap_or(&""))),
+            "new" => {
+                self.abort();
+                self.variant = None;
+                self.set_board(Board::create_populated());
+                self.engine_side = Some(Color::Black);
+                self.move_time = None;
+                self.depth = None;
+            }
+            "variant" => {
+                self.abort();
+                match tokens.get(1).and_then(|name| VARIANTS.iter().find(|(variant_name, _)| variant_name == name)) {
+                    Some((_, variant)) => {
+                        self.variant = Some(*variant);
+                        self.set_board(variant.start_board());
+                    }
+                    None => self.send(&format!("Error (unsupported variant): {}", tokens.get(1).unwrap_or(&""))),
+                }
+            }
+            "setboard" => {
+                self.abort();
+                match Board::from_fen(&tokens[1..].join(" ")) {
+                    Ok(mut board) => {
+                        if let Some(variant) = self.variant {
+                            board.variant = variant;
+                        }
+                        self.set_board(board);
+                    }
+                    Err(error) => self.send(&format!("tellusererror Illegal position: {}", error)),
+                }
+            }
+            "force" | "result" => {
+                self.abort();
+                self.engine_side = None;
+            }
+            "go" => {
+                self.wait_for_search();
+                self.engine_side = Some(self.board.side);
+                self.start_search();
+            }
+            "playother" => {
+                self.wait_for_search();
+                self.engine_side = Some(self.board.side.switch());
+            }
+            "usermove" => {
+                self.wait_for_search();
+                match tokens.get(1) {
+                    Some(text) => self.user_move(text),
+                    None => self.send("Error (missing move): usermove"),
+                }
+            }
+            // Move now
+            "?" => self.stop(),
+            "undo" => {
+                self.abort();
+                self.undo(1);
+            }
+            "remove" => {
+                self.abort();
+                self.undo(2);
+            }
+            "level" => match Level::parse(&tokens[1..]) {
+                Some(level) => {
+                    self.level = level;
+                    self.move_time = None;
+                }
+                None => self.send(&format!("Error (invalid level): {}", line.trim())),
+            },
+            "st" => match tokens.get(1).and_then(|seconds| parse_seconds(seconds)) {
+                Some(move_time) => self.move_time = Some(move_time),
+                None => self.send(&format!("Error (invalid time): {}", line.trim())),
+            },
+            "sd" => self.depth = tokens.get(1).and_then(|depth| depth.parse().ok()),
+            // Clocks in centiseconds
+            "time" => self.remaining = tokens.get(1).and_then(|time| time.parse::<u64>().ok()).map(|time| Duration::from_millis(time * 10)),
+            "otim" => {}
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "memory" => {
+                if let Some(megabytes) = tokens.get(1).and_then(|megabytes| megabytes.parse::<usize>().ok()) {
+                    self.options.transposition_table_size = megabytes * 1024 * 1024 / TRANSPOSITION_ENTRY_SIZE;
+                }
+            }
+            "cores" => {
+                if let Some(cores) = tokens.get(1).and_then(|cores| cores.parse::<usize>().ok()) {
+                    self.options.threads = cores.max(1);
+                }
+            }
+            "quit" => {
+                self.abort();
+                return false;
+            }
+            _ => self.send(&format!("Error (unknown command): {}", tokens[0])),
+        }
+
+        return true;
+    }
+
+    // Make the running search, if any, play its best move now
+    pub fn stop(&mut self) {
+        if let Some(search) = &self.search {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait_for_search();
+    }
+
+    // Stop the running search, if any, without playing its move
+    fn abort(&mut self) {
+        if let Some(search) = &self.search {
+            search.discard.store(true, Ordering::Relaxed);
+            search.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait_for_search();
+    }
+
+    // Wait for the running search, if any, to play its move
+    pub fn wait_for_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if let Some(m) = search.reporter.join().unwrap() {
+                self.play(m);
+            }
+        }
+    }
+
+    fn set_board(&mut self, board: Board) {
+        self.board = board;
+        self.history.clear();
+    }
+
+    fn play(&mut self, m: Move) {
+        self.history.push(m);
+        self.board.apply_move(m);
+    }
+
+    // Take back the last `count` moves
+    fn undo(&mut self, count: usize) {
+        for _ in 0..count {
+            match self.history.pop() {
+                Some(m) => self.board.revert_move(m),
+                None => return
+            }
+        }
+    }
+
+    fn user_move(&mut self, text: &str) {
+        let m = find_move(&mut self.board, text, false).or_else(|| parse_san(&mut self.board, text));
+        match m {
+            Some(m) => {
+                self.play(m);
+                match game_outcome(&mut self.board) {
+                    Some(outcome) => self.send(&result_string(outcome)),
+                    None => {
+                        if self.engine_side == Some(self.board.side) {
+                            self.start_search();
+                        }
+                    }
+                }
+            }
+            None => self.send(&format!("Illegal move: {}", text)),
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let mut limits = SearchLimits::infinite();
+        limits.depth = self.depth;
+        limits.move_time = self.move_time;
+
+        if limits.move_time.is_none() {
+            let remaining = self.remaining.unwrap_or(self.level.base);
+            // Moves left until the next session, counting the engine's moves since the start
+            let moves_to_go = match self.level.moves_per_session {
+                0 => None,
+                moves_per_session => Some(moves_per_session - (self.history.len() as u32 / 2) % moves_per_session),
+            };
+            limits.move_time = Some(TimeControl::create(remaining, self.level.increment, moves_to_go).allocate());
+        }
+
+        return limits;
+    }
+
+    fn start_search(&mut self) {
+        if game_outcome(&mut self.board).is_some() {
+            return;
+        }
+
+        let mut evaluator = SearchEvaluator::create_with_options(0, self.options);
+        evaluator.set_limits(self.limits());
+        
+        let handle = start_search_with_evaluator(&self.board, evaluator);
+        let stop = handle.get_stop_flag();
+        let discard = Arc::new(AtomicBool::new(false));
+        let output = self.output.clone();
+        let board = self.board.clone();
+        let post = self.post;
+        let reporter_discard = discard.clone();
+
+        let reporter = std::thread::spawn(move || report_search(handle, output, board, post, reporter_discard));
+
+        self.search = Some(RunningSearch { stop, discard, reporter });
+    }
+}
+
+// The `result` line announcing the end of a game
+fn result_string(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Win(Color::White) => "1-0 {White wins}".to_string(),
+        Outcome::Win(Color::Black) => "0-1 {Black wins}".to_string(),
+        Outcome::Draw => "1/2-1/2 {Draw}".to_string(),
+    }
+}
+
+// Thinking output as XBoard expects it: depth, score in centipawns, time in centiseconds, nodes
+// and the line. Mates are scored 100000 plus the number of moves, negated when being mated.
+pub fn thinking_string(info: &SearchInfo) -> String {
+    let score = match mate_distance(info.evaluation) {
+        Some(moves) if moves > 0 => 100000 + moves,
+        Some(moves) => -100000 + moves,
+        None => (info.evaluation * 100.0).round() as i32,
+    };
+
+    let pv = info.line.moves.iter().map(|m| m.coordinate_notation()).collect::<Vec<String>>().join(" ");
+    format!("{} {} {} {} {}", info.depth, score, info.duration.as_millis() / 10, info.node_count, pv)
+}
+
+// Forward the thinking output of a search if `post` is set, then play its best move on `board`
+// unless `discard` has been set in the meantime
+fn report_search<W: Write>(handle: SearchHandle, output: Arc<Mutex<W>>, mut board: Board, post: bool,
+                           discard: Arc<AtomicBool>) -> Option<Move> {
+    for info in handle.get_info_receiver().iter() {
+        if post && info.multi_pv == 1 {
+            send(&output, &thinking_string(&info));
+        }
+    }
+
+    let result = handle.wait();
+    if discard.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let best_move = result.best_move?;
+    send(&output, &format!("move {}", best_move.coordinate_notation()));
+
+    board.apply_move(best_move);
+    if let Some(outcome) = game_outcome(&mut board) {
+        send(&output, &result_string(outcome));
+    }
+
+    return Some(best_move);
+}
+
+// Talk XBoard on `input` and `output` until `quit` is received or the input ends
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let mut engine = XboardEngine::create(output);
+
+    for line in input.lines() {
+        match line {
+            Ok(line) => {
+                if !engine.handle(&line) {
+                    return;
+                }
+            }
+            Err(_) => break
+        }
+    }
+
+    engine.abort();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output_lines(engine: &XboardEngine<Vec<u8>>) -> Vec<String> {
+        let output = engine.get_output();
+        let output = output.lock().unwrap();
+        String::from_utf8(output.clone()).unwrap().lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn xboard_handshake() {
+        let mut engine = XboardEngine::create(Vec::new());
+        assert!(engine.handle("xboard"));
+        assert!(engine.handle("protover 2"));
+        assert!(engine.handle("ping 7"));
+        assert!(engine.handle("foo"));
+
+        let lines = output_lines(&engine);
+        assert!(lines[0].starts_with("feature myname=\"mess\" "));
+        assert!(lines[0].contains("usermove=1"));
+        assert!(lines[0].ends_with(" done=1"));
+        assert_eq!(&lines[1..], ["pong 7", "Error (unknown command): foo"]);
+
+        assert!(!engine.handle("quit"));
+    }
+
+    #[test]
+    fn xboard_game() {
+        let mut engine = XboardEngine::create(Vec::new());
+        engine.handle("new");
+        engine.handle("sd 2");
+        engine.handle("post");
+
+        // The engine plays Black and answers right away
+        engine.handle("usermove e2e4");
+        engine.wait_for_search();
+        let lines = output_lines(&engine);
+        assert!(lines[0].starts_with("1 "));
+        assert!(lines.last().unwrap().starts_with("move "));
+        assert_eq!(engine.get_board().side, Color::White);
+
+        // In force mode moves are only recorded, and can be taken back
+        engine.handle("force");
+        engine.handle("usermove Nf3");
+        assert_eq!(engine.get_board().side, Color::Black);
+        engine.handle("remove");
+        assert_eq!(engine.get_board().to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        engine.handle("undo");
+        assert!(engine.get_board().semantic_eq(&Board::create_populated()));
+        engine.handle("usermove e2e5");
+        assert_eq!(output_lines(&engine).last().unwrap(), "Illegal move: e2e5");
+
+        // `go` makes the engine play the side to move
+        engine.handle("setboard 4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        engine.handle("sd 3");
+        engine.handle("go");
+        engine.wait_for_search();
+        assert_eq!(engine.get_engine_side(), Some(Color::White));
+        assert_eq!(engine.get_board().side, Color::Black);
+
+        // The move counters go on from the FEN's
+        engine.handle("setboard 4k3/8/8/8/8/8/8/R3K3 w - - 37 60");
+        engine.handle("usermove a1a2");
+        assert_eq!(engine.get_board().to_fen(), "4k3/8/8/8/8/8/R7/4K3 b - - 38 60");
+
+        // A finished game is announced
+        engine.handle("setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        engine.handle("force");
+        engine.handle("usermove a1a8");
+        assert_eq!(output_lines(&engine).last().unwrap(), "1-0 {White wins}");
+    }
+
+    #[test]
+    fn xboard_variant() {
+        let mut engine = XboardEngine::create(Vec::new());
+        engine.handle("new");
+        // Without a chosen variant, a FEN keeps the variant it implies
+        engine.handle("setboard 4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1");
+        assert_eq!(engine.get_board().variant, Variant::Crazyhouse);
+        engine.handle("variant normal");
+        engine.handle("setboard 4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1");
+        assert_eq!(engine.get_board().variant, Variant::Standard);
+
+        engine.handle("variant capablanca");
+        engine.handle("force");
+        engine.handle("usermove c1d3");
+        assert_eq!(engine.get_board().to_fen(), "rnabqkbcnr/pppppppppp/10/10/10/3A6/PPPPPPPPPP/RN1BQKBCNR b KQkq - 1 1");
+        engine.handle("variant shogi");
+        assert_eq!(output_lines(&engine).last().unwrap(), "Error (unsupported variant): shogi");
+    }
+
+    #[test]
+    fn xboard_level() {
+        assert_eq!(Level::parse(&["40", "5", "0"]),
+            Some(Level { moves_per_session: 40, base: Duration::from_secs(300), increment: Duration::from_secs(0) }));
+        assert_eq!(Level::parse(&["0", "2:30", "1"]),
+            Some(Level { moves_per_session: 0, base: Duration::from_secs(150), increment: Duration::from_secs(1) }));
+        assert_eq!(Level::parse(&["0", "2"]), None);
+        assert_eq!(Level::parse(&["0", "5", "-1"]), None);
+        assert_eq!(Level::parse(&["0", "5", "inf"]), None);
+        assert_eq!(Level::parse(&["0", "5", "NaN"]), None);
+
+        // The engine's clock overrides the level's base time
+        let mut engine = XboardEngine::create(Vec::new());
+        engine.handle("level 0 1 0");
+        engine.handle("time 6000");
+        assert_eq!(engine.limits().move_time, Some(Duration::from_secs(2)));
+        engine.handle("st 5");
+        assert_eq!(engine.limits().move_time, Some(Duration::from_secs(5)));
+
+        // Invalid times are rejected and change nothing
+        engine.handle("st -1");
+        engine.handle("st inf");
+        engine.handle("level 0 5 inf");
+        assert_eq!(&output_lines(&engine)[..], ["Error (invalid time): st -1", "Error (invalid time): st inf",
+            "Error (invalid level): level 0 5 inf"]);
+        assert_eq!(engine.limits().move_time, Some(Duration::from_secs(5)));
+    }
+}